@@ -130,18 +130,18 @@ fn transform_mut_shape(c: &mut Criterion) {
             let trans = &Transform2::new(PI / 3., (0.2, -5.3));
             let mut shape = LineShape::from_radial("Polygon", vec![1.; sides])
                 .expect("Creation of shape failed");
-            b.iter(|| shape.transform_mut(trans))
+            b.iter(|| shape = shape.transform(trans))
         });
     }
     group.bench_function(BenchmarkId::new("Molecule", 1), |b| {
         let mut shape = MolecularShape2::circle();
         let trans = &Transform2::new(PI / 3., (0.2, -5.3));
-        b.iter(|| shape.transform_mut(trans))
+        b.iter(|| shape = shape.transform(trans))
     });
     group.bench_function(BenchmarkId::new("Molecule", 3), |b| {
         let trans = &Transform2::new(PI / 3., (0.2, -5.3));
         let mut shape = MolecularShape2::from_trimer(0.637_556, 180., 1.0);
-        b.iter(|| shape.transform_mut(trans))
+        b.iter(|| shape = shape.transform(trans))
     });
     group.finish();
 }
@@ -167,16 +167,18 @@ fn site_positions(c: &mut Criterion) {
     });
 }
 
-fn state_modify_basis(c: &mut Criterion) {
-    let state = create_packed_state(256).expect("Creation of state failed");
-    let mut basis = state.generate_basis();
+fn state_modify_parameters(c: &mut Criterion) {
+    let mut state = create_packed_state(256).expect("Creation of state failed");
+    let mut parameters = state.parameters();
 
-    c.bench_function("Modify Basis", |b| {
+    c.bench_function("Modify Parameters", |b| {
         b.iter(|| {
-            for _ in 0..1000 {
-                for value in basis.iter_mut() {
-                    value.set_value(value.get_value() + 0.1);
-                    value.reset_value();
+            for _ in 0..10 {
+                for index in 0..parameters.len() {
+                    let previous = parameters.set(index, parameters.get(index) + 0.1);
+                    state.set_parameters(parameters.values());
+                    parameters.set(index, previous);
+                    state.set_parameters(parameters.values());
                 }
             }
         })
@@ -192,6 +194,6 @@ criterion_group!(
     site_positions,
 );
 
-criterion_group!(general, site_positions, state_modify_basis);
+criterion_group!(general, site_positions, state_modify_parameters);
 
 criterion_main!(intersections, general);
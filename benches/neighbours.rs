@@ -0,0 +1,123 @@
+//
+// neighbours.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use criterion::BenchmarkId;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use packing::traits::*;
+use packing::wallpaper::{get_wallpaper_group, WallpaperGroups};
+use packing::{LJShape2, LineShape, PackedState, PotentialState};
+
+static BENCH_Z_PRIME: &[usize] = &[1, 4, 16];
+
+/// A state of trimers with `z_prime` copies on the general position of p2
+fn create_potential_state(z_prime: usize) -> PotentialState<LJShape2> {
+    let group = get_wallpaper_group(WallpaperGroups::p2).expect("Invalid wallpaper group");
+    let trimer = LJShape2::from_trimer(0.637_556, 120., 1.);
+    PotentialState::from_group_z_prime(trimer, &group, z_prime).expect("Creation of state failed")
+}
+
+/// A state of hexagons with `z_prime` copies on the general position of p2
+fn create_packed_state(z_prime: usize) -> PackedState<LineShape> {
+    let group = get_wallpaper_group(WallpaperGroups::p2).expect("Invalid wallpaper group");
+    let shape = LineShape::from_radial("Polygon", vec![1.; 6]).expect("Invalid shape");
+    PackedState::from_group_z_prime(shape, &group, z_prime).expect("Creation of state failed")
+}
+
+fn potential_score(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Potential Score");
+
+    for &z_prime in BENCH_Z_PRIME.iter() {
+        let state = create_potential_state(z_prime);
+        group.bench_with_input(
+            BenchmarkId::new("All Pairs", z_prime),
+            &state,
+            |b, state| b.iter(|| state.score_all_pairs()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Neighbour List", z_prime),
+            &state,
+            |b, state| b.iter(|| state.score()),
+        );
+    }
+    group.finish();
+}
+
+fn packed_score(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Packed Score");
+
+    for &z_prime in BENCH_Z_PRIME.iter() {
+        let state = create_packed_state(z_prime);
+        group.bench_with_input(
+            BenchmarkId::new("All Pairs", z_prime),
+            &state,
+            |b, state| b.iter(|| state.score_all_pairs()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Neighbour List", z_prime),
+            &state,
+            |b, state| b.iter(|| state.score()),
+        );
+    }
+    group.finish();
+}
+
+/// Score moving the last site of the `state`, using either the complete score or only the
+/// contribution of the moved site
+fn bench_site_move<S: State>(c: &mut Criterion, name: &str, create: impl Fn(usize) -> S) {
+    let mut group = c.benchmark_group(name);
+
+    for &z_prime in BENCH_Z_PRIME.iter() {
+        let mut state = create(z_prime);
+        let mut parameters = state.parameters();
+        let index = parameters.len() - 2;
+        let original = parameters.values().to_vec();
+        parameters.set(index, parameters.get(index) + 0.01);
+        let moved = parameters.values().to_vec();
+        state.set_parameters(&original);
+
+        group.bench_function(BenchmarkId::new("Score", z_prime), |b| {
+            b.iter(|| {
+                state.set_parameters(&moved);
+                let score = state.score();
+                state.set_parameters(&original);
+                score
+            })
+        });
+        group.bench_function(BenchmarkId::new("Parameter Score", z_prime), |b| {
+            b.iter(|| {
+                // Like the optimiser, fall back to the complete score when it can't be separated
+                let before = state.parameter_score_before(index).flatten();
+                state.set_parameters(&moved);
+                let score = match before {
+                    Some(before) => state.parameter_score(index).flatten().map(|s| s - before),
+                    None => state.score(),
+                };
+                state.set_parameters(&original);
+                score
+            })
+        });
+    }
+    group.finish();
+}
+
+fn potential_site_move(c: &mut Criterion) {
+    bench_site_move(c, "Potential Site Move", create_potential_state);
+}
+
+fn packed_site_move(c: &mut Criterion) {
+    bench_site_move(c, "Packed Site Move", create_packed_state);
+}
+
+criterion_group!(
+    neighbours,
+    potential_score,
+    packed_score,
+    potential_site_move,
+    packed_site_move
+);
+
+criterion_main!(neighbours);
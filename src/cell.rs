@@ -7,10 +7,10 @@
 use std::f64::consts::PI;
 
 use itertools::iproduct;
-use nalgebra::{Point2, Translation2};
+use nalgebra::{Point2, Translation2, Vector2};
 use serde::{Deserialize, Serialize};
 
-use crate::{SharedValue, StandardBasis, Transform2};
+use crate::{Parameters, Transform2};
 
 /// The different crystal families that can be represented
 ///
@@ -53,25 +53,14 @@ mod crystal_family_test {
 /// addition to the contained angles. Each cell belongs to one of the Crystal Families which
 /// dictate the degrees of freedom the cell can take.
 ///
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cell2 {
-    length: SharedValue,
-    ratio: SharedValue,
-    angle: SharedValue,
+    length: f64,
+    ratio: f64,
+    angle: f64,
     family: CrystalFamily,
 }
 
-impl Clone for Cell2 {
-    fn clone(&self) -> Self {
-        Cell2 {
-            length: SharedValue::new(self.length.get_value()),
-            ratio: SharedValue::new(self.ratio.get_value()),
-            angle: SharedValue::new(self.angle.get_value()),
-            family: self.family,
-        }
-    }
-}
-
 impl std::fmt::Display for Cell2 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
@@ -87,9 +76,9 @@ impl std::fmt::Display for Cell2 {
 impl Default for Cell2 {
     fn default() -> Self {
         Self {
-            length: SharedValue::new(1.),
-            ratio: SharedValue::new(1.),
-            angle: SharedValue::new(PI / 2.),
+            length: 1.,
+            ratio: 1.,
+            angle: PI / 2.,
             family: CrystalFamily::Monoclinic,
         }
     }
@@ -97,15 +86,15 @@ impl Default for Cell2 {
 
 impl Cell2 {
     pub fn a(&self) -> f64 {
-        self.length.get_value()
+        self.length
     }
 
     pub fn b(&self) -> f64 {
-        self.length.get_value() * self.ratio.get_value()
+        self.length * self.ratio
     }
 
     pub fn angle(&self) -> f64 {
-        self.angle.get_value()
+        self.angle
     }
 
     /// Convert a transformation into Cartesian coordinates
@@ -139,31 +128,81 @@ impl Cell2 {
     /// This finds the values of the unit cell which are allowed to be changed and how
     ///
     /// Each of the different crystal families impose different restrictions on the degrees of
-    /// freedom of a unit cell. This compiles these degrees of freedom into a vector of Bases,
-    /// which is the data structure used to modify the values.
-    pub fn get_degrees_of_freedom(&self) -> Vec<StandardBasis> {
-        let mut basis: Vec<StandardBasis> = vec![];
-
-        // All cells have at least a single variable cell length
-        basis.push(StandardBasis::new(
-            &self.length,
-            0.01,
-            self.length.get_value(),
-        ));
+    /// freedom of a unit cell. This compiles these degrees of freedom into the Parameters, along
+    /// with the range of values each is able to take. The cell is only able to shrink from the
+    /// current size.
+    ///
+    pub fn parameters(&self) -> Parameters {
+        let values = self.select_degrees_of_freedom(self.length, self.ratio, self.angle);
+        let bounds = self.select_degrees_of_freedom(
+            // All cells have at least a single variable cell length
+            (0.01, self.length),
+            // The Orthorhombic and Monoclinic have a second variable cell length in the ratio
+            (0.1, self.ratio),
+            // Monoclinic additionally has a variable angle
+            (PI / 6., PI / 2.),
+        );
+        let mut parameters = Parameters::new();
+        for (value, (min, max)) in values.into_iter().zip(bounds) {
+            parameters.push(value, min, max);
+        }
+        parameters
+    }
+
+    /// The number of values in `parameters`
+    pub fn num_parameters(&self) -> usize {
+        self.select_degrees_of_freedom((), (), ()).len()
+    }
 
+    /// Update the cell from `values`, which are in the same order as `parameters`
+    pub fn set_parameters(&mut self, values: &[f64]) {
+        self.length = values[0];
         match self.family {
-            // Monoclinic has both variable angle and varaible ratio of sides
             CrystalFamily::Monoclinic => {
-                basis.push(StandardBasis::new(&self.ratio, 0.1, self.ratio.get_value()));
-                basis.push(StandardBasis::new(&self.angle, PI / 6., PI / 2.));
-            }
-            // The Orthorhombic have a second variable cell length in the ratio
-            CrystalFamily::Orthorhombic => {
-                basis.push(StandardBasis::new(&self.ratio, 0.1, self.ratio.get_value()));
+                self.ratio = values[1];
+                self.angle = values[2];
             }
+            CrystalFamily::Orthorhombic => self.ratio = values[1],
             _ => {}
         }
-        basis
+    }
+
+    /// Arrange values for each of the cell parameters in the order of the degrees of freedom
+    fn select_degrees_of_freedom<T>(&self, length: T, ratio: T, angle: T) -> Vec<T> {
+        match self.family {
+            CrystalFamily::Monoclinic => vec![length, ratio, angle],
+            CrystalFamily::Orthorhombic => vec![length, ratio],
+            _ => vec![length],
+        }
+    }
+
+    /// The derivatives of the Cartesian position of a point with respect to the cell parameters
+    ///
+    /// The `point` is in fractional coordinates, with a derivative for each of the values in
+    /// `parameters`, in the same order.
+    ///
+    pub fn point_derivatives(&self, point: Point2<f64>) -> Vec<Vector2<f64>> {
+        let (length, ratio) = (self.length, self.ratio);
+        let (sin, cos) = self.angle().sin_cos();
+        self.select_degrees_of_freedom(
+            Vector2::new(point.x + point.y * ratio * cos, point.y * ratio * sin),
+            Vector2::new(point.y * length * cos, point.y * length * sin),
+            Vector2::new(-point.y * self.b() * sin, point.y * self.b() * cos),
+        )
+    }
+
+    /// The derivatives of the area of the cell with respect to each of the cell parameters
+    ///
+    /// There is a derivative for each of the values in `parameters`, in the same order.
+    ///
+    pub fn area_derivatives(&self) -> Vec<f64> {
+        let (length, ratio) = (self.length, self.ratio);
+        let (sin, cos) = self.angle().sin_cos();
+        self.select_degrees_of_freedom(
+            2. * sin * length * ratio,
+            sin * length.powi(2),
+            cos * length.powi(2) * ratio,
+        )
     }
 
     /// The center of the cell in real space
@@ -202,10 +241,10 @@ impl Cell2 {
             _ => PI / 2.,
         };
         Cell2 {
-            length: SharedValue::new(length),
+            length,
             // The radio is initially always 1
-            ratio: SharedValue::new(1.0),
-            angle: SharedValue::new(angle),
+            ratio: 1.0,
+            angle,
             family,
         }
     }
@@ -276,12 +315,12 @@ mod cell_tests {
 
     #[test]
     fn to_cartesian_test() {
-        let cell = Cell2::default();
+        let mut cell = Cell2::default();
         let trans = Transform2::new(0., (0.5, 0.5));
 
         assert_eq!(cell.to_cartesian_isometry(trans), trans);
 
-        cell.angle.set_value(PI / 4.);
+        cell.angle = PI / 4.;
         let expected = Transform2::new(
             0.,
             (0.5 + 0.5 * 1. / f64::sqrt(2.), 0.5 * 1. / f64::sqrt(2.)),
@@ -289,6 +328,39 @@ mod cell_tests {
         assert_abs_diff_eq!(cell.to_cartesian_isometry(trans), expected);
     }
 
+    #[test]
+    fn derivatives() {
+        let mut cell = Cell2::from_family(CrystalFamily::Monoclinic, 2.);
+        cell.set_parameters(&[2., 0.8, 1.2]);
+        let point = Point2::new(0.3, -0.4);
+        let h = 1e-6;
+
+        let values = cell.parameters().values().to_vec();
+        let derivatives = cell.point_derivatives(point);
+        let area_derivatives = cell.area_derivatives();
+        assert_eq!(derivatives.len(), cell.num_parameters());
+        for (index, derivative, area_derivative) in izip!(0.., derivatives, area_derivatives) {
+            let mut shifted = values.clone();
+            shifted[index] = values[index] + h;
+            cell.set_parameters(&shifted);
+            let (forward, forward_area) = (cell.to_cartesian_point(point), cell.area());
+            shifted[index] = values[index] - h;
+            cell.set_parameters(&shifted);
+            let (backward, backward_area) = (cell.to_cartesian_point(point), cell.area());
+            cell.set_parameters(&values);
+            assert_abs_diff_eq!(derivative, (forward - backward) / (2. * h), epsilon = 1e-8);
+            assert_abs_diff_eq!(
+                area_derivative,
+                (forward_area - backward_area) / (2. * h),
+                epsilon = 1e-8
+            );
+        }
+
+        let hexagonal = Cell2::from_family(CrystalFamily::Hexagonal, 2.);
+        assert_eq!(hexagonal.point_derivatives(point).len(), 1);
+        assert_eq!(hexagonal.area_derivatives().len(), 1);
+    }
+
     #[test]
     fn periodic_intersection() {
         let shape = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
@@ -373,9 +445,9 @@ mod cell_tests {
     fn invalid_intersection() {
         let shape = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
         let cell = Cell2 {
-            length: SharedValue::new(1.59),
-            ratio: SharedValue::new(0.83),
-            angle: SharedValue::new(1.21),
+            length: 1.59,
+            ratio: 0.83,
+            angle: 1.21,
             family: CrystalFamily::Monoclinic,
         };
 
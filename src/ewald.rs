@@ -0,0 +1,261 @@
+//
+// ewald.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use anyhow::{bail, Error};
+use itertools::iproduct;
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::Cell2;
+
+/// The Ewald summation of the Coulomb interactions of charges in a periodic cell
+///
+/// The charges lie within a plane which is periodic in two dimensions, interacting through the
+/// $1/r$ Coulomb potential. The slowly converging sum over the periodic images is split into a
+/// short ranged sum in real space and a sum over the reciprocal lattice [Parry 1975], which both
+/// converge rapidly.
+///
+/// The splitting between the two sums is scaled with the size of the cell, which changes
+/// throughout an optimisation, so the same number of shells of the lattice give a consistent
+/// accuracy.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Ewald {
+    /// The splitting parameter $\alpha$ multiplied by the square root of the cell area
+    pub splitting: f64,
+    /// The number of shells of periodic images summed in real space
+    pub real_shells: i64,
+    /// The number of shells of the reciprocal lattice summed
+    pub reciprocal_shells: i64,
+    /// The energy of two unit charges separated by a unit distance
+    pub coulomb: f64,
+}
+
+impl Default for Ewald {
+    fn default() -> Self {
+        Self {
+            splitting: 3.,
+            real_shells: 2,
+            reciprocal_shells: 6,
+            coulomb: 1.,
+        }
+    }
+}
+
+impl Ewald {
+    /// Ensure the charges sum to zero, since the energy of a charged cell diverges
+    pub fn check_neutral<'a>(
+        charges: impl IntoIterator<Item = &'a (Point2<f64>, f64)>,
+    ) -> Result<(), Error> {
+        let total: f64 = charges.into_iter().map(|(_, q)| q).sum();
+        if total.abs() > 1e-8 {
+            bail!(
+                "The charges need to be neutral, found a total charge of {}",
+                total
+            )
+        }
+        Ok(())
+    }
+
+    /// The electrostatic energy of a single unit cell
+    ///
+    /// Each of the `molecules` is a collection of charges at a position, with the interactions
+    /// between the charges of a molecule and themselves excluded from the energy. All the other
+    /// interactions, including those between a molecule and its own periodic images, are
+    /// included.
+    ///
+    pub fn energy(
+        &self,
+        cell: &Cell2,
+        molecules: &[Vec<(Point2<f64>, f64)>],
+    ) -> Result<f64, Error> {
+        let charges: Vec<(Point2<f64>, f64)> = molecules.iter().flatten().cloned().collect();
+        Self::check_neutral(&charges)?;
+
+        let origin = cell.to_cartesian_point(Point2::origin());
+        let a1 = cell.to_cartesian_point(Point2::new(1., 0.)) - origin;
+        let a2 = cell.to_cartesian_point(Point2::new(0., 1.)) - origin;
+        let det = a1.x * a2.y - a1.y * a2.x;
+        let area = det.abs();
+        let alpha = self.splitting / area.sqrt();
+
+        // Real space sum, excluding each charge with itself in the central cell
+        let mut real = 0.;
+        for (i, (pi, qi)) in charges.iter().enumerate() {
+            for (j, (pj, qj)) in charges.iter().enumerate() {
+                for (x, y) in iproduct!(
+                    -self.real_shells..=self.real_shells,
+                    -self.real_shells..=self.real_shells
+                ) {
+                    if i == j && x == 0 && y == 0 {
+                        continue;
+                    }
+                    let r = (pj - pi + a1 * x as f64 + a2 * y as f64).norm();
+                    real += qi * qj * erfc(alpha * r) / r;
+                }
+            }
+        }
+        real /= 2.;
+
+        // Reciprocal space sum
+        let b1 = Vector2::new(a2.y, -a2.x) * 2. * PI / det;
+        let b2 = Vector2::new(-a1.y, a1.x) * 2. * PI / det;
+        let mut reciprocal = 0.;
+        for (x, y) in iproduct!(
+            -self.reciprocal_shells..=self.reciprocal_shells,
+            -self.reciprocal_shells..=self.reciprocal_shells
+        ) {
+            if x == 0 && y == 0 {
+                continue;
+            }
+            let k = b1 * x as f64 + b2 * y as f64;
+            let k_norm = k.norm();
+            let (cos, sin) = charges.iter().fold((0., 0.), |(c, s), (p, q)| {
+                let phase = k.dot(&p.coords);
+                (c + q * phase.cos(), s + q * phase.sin())
+            });
+            reciprocal += erfc(k_norm / (2. * alpha)) / k_norm * (cos.powi(2) + sin.powi(2));
+        }
+        reciprocal *= PI / area;
+
+        let self_energy = alpha / PI.sqrt() * charges.iter().map(|(_, q)| q * q).sum::<f64>();
+
+        // The interactions within a molecule are included in the sums above
+        let intramolecular: f64 = molecules
+            .iter()
+            .map(|molecule| {
+                let mut sum = 0.;
+                for (i, (pi, qi)) in molecule.iter().enumerate() {
+                    for (pj, qj) in molecule.iter().skip(i + 1) {
+                        sum += qi * qj / nalgebra::distance(pi, pj);
+                    }
+                }
+                sum
+            })
+            .sum();
+
+        Ok(self.coulomb * (real + reciprocal - self_energy - intramolecular))
+    }
+}
+
+/// The complementary error function
+///
+/// This uses the Taylor series of the error function for small values, and a continued fraction
+/// for large values, both of which are accurate to close to machine precision.
+///
+fn erfc(x: f64) -> f64 {
+    if x < 0. {
+        return 2. - erfc(-x);
+    }
+    if x < 2. {
+        // erf(x) = 2/sqrt(pi) sum_n (-1)^n x^(2n+1) / (n! (2n + 1))
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 0.;
+        while term.abs() > 1e-17 * sum.abs() {
+            n += 1.;
+            term *= -x * x / n;
+            sum += term / (2. * n + 1.);
+        }
+        1. - 2. / PI.sqrt() * sum
+    } else {
+        // Evaluate the continued fraction from the tail
+        // erfc(x) = exp(-x^2)/sqrt(pi) * 1/(x + (1/2)/(x + 1/(x + (3/2)/(x + ...))))
+        let mut fraction = x;
+        for n in (1..60).rev() {
+            fraction = x + (n as f64 / 2.) / fraction;
+        }
+        f64::exp(-x * x) / PI.sqrt() / fraction
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::CrystalFamily;
+
+    /// Each ion as a separate molecule, so all the interactions are included
+    fn ions(cell: &Cell2, fractional: &[(f64, f64, f64)]) -> Vec<Vec<(Point2<f64>, f64)>> {
+        fractional
+            .iter()
+            .map(|&(x, y, q)| vec![(cell.to_cartesian_point(Point2::new(x, y)), q)])
+            .collect()
+    }
+
+    #[test]
+    fn erfc_values() {
+        assert_abs_diff_eq!(erfc(0.), 1.);
+        assert_abs_diff_eq!(erfc(0.5), 0.479_500_122_186_953_5, epsilon = 1e-15);
+        assert_abs_diff_eq!(erfc(1.), 0.157_299_207_050_285_1, epsilon = 1e-15);
+        assert_abs_diff_eq!(erfc(2.9), 4.109_787_809_945_886e-5, epsilon = 1e-16);
+        assert_abs_diff_eq!(erfc(3.), 2.209_049_699_858_544e-5, epsilon = 1e-16);
+        assert_abs_diff_eq!(erfc(5.), 1.537_459_794_428_035e-12, epsilon = 1e-22);
+        assert_abs_diff_eq!(erfc(-1.), 1.842_700_792_949_715, epsilon = 1e-15);
+    }
+
+    #[test]
+    fn madelung_square() {
+        // The checkerboard lattice with a nearest neighbour distance of 1
+        let cell = Cell2::from_family(CrystalFamily::Tetragonal, 2_f64.sqrt());
+        let molecules = ions(&cell, &[(0., 0., 1.), (0.5, 0.5, -1.)]);
+        let energy = Ewald::default().energy(&cell, &molecules).unwrap();
+        assert_abs_diff_eq!(-energy, 1.615_542_626_712_824_7, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn madelung_hexagonal() {
+        // The honeycomb lattice of alternating charges with a nearest neighbour distance of 1
+        let cell = Cell2::from_family(CrystalFamily::Hexagonal, 3_f64.sqrt());
+        let molecules = ions(&cell, &[(1. / 3., 1. / 3., 1.), (2. / 3., 2. / 3., -1.)]);
+        let energy = Ewald::default().energy(&cell, &molecules).unwrap();
+        assert_abs_diff_eq!(-energy, 1.542_219_721_706_505, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn independent_of_splitting() {
+        let cell = Cell2::from_family(CrystalFamily::Monoclinic, 3.);
+        let molecules = ions(&cell, &[(0.1, 0.2, 1.), (0.6, 0.3, -0.5), (0.4, 0.8, -0.5)]);
+        let reference = Ewald::default().energy(&cell, &molecules).unwrap();
+        for &splitting in &[2.5, 3.5, 4.] {
+            let ewald = Ewald {
+                splitting,
+                real_shells: 3,
+                reciprocal_shells: 8,
+                ..Default::default()
+            };
+            assert_abs_diff_eq!(
+                ewald.energy(&cell, &molecules).unwrap(),
+                reference,
+                epsilon = 1e-8
+            );
+        }
+    }
+
+    #[test]
+    fn excludes_intramolecular() {
+        let cell = Cell2::from_family(CrystalFamily::Tetragonal, 2_f64.sqrt());
+        let separate = ions(&cell, &[(0., 0., 1.), (0.5, 0.5, -1.)]);
+        let combined = vec![separate.iter().flatten().cloned().collect()];
+        let ewald = Ewald::default();
+        // The pair of ions are separated by a distance of 1
+        assert_abs_diff_eq!(
+            ewald.energy(&cell, &combined).unwrap(),
+            ewald.energy(&cell, &separate).unwrap() + 1.,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn requires_neutral() {
+        let cell = Cell2::from_family(CrystalFamily::Tetragonal, 2.);
+        let molecules = ions(&cell, &[(0., 0., 1.), (0.5, 0.5, -0.5)]);
+        assert!(Ewald::default().energy(&cell, &molecules).is_err());
+    }
+}
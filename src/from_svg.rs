@@ -0,0 +1,833 @@
+//
+// from_svg.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+// This is the reverse of the `to_svg` module, reading the outline of a shape from an SVG file
+// rather than drawing a shape to one. Only the geometry is read from the document, including the
+// `transform` attributes of the shapes and the groups containing them, while any styling is
+// ignored.
+//
+
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, bail, Error};
+use log::warn;
+use nalgebra::{Matrix3, Point2, Vector2, U2};
+use svg::node::element::path::{Command, Data, Position};
+use svg::node::element::tag::Type;
+use svg::node::Attributes;
+use svg::parser::Event;
+use svg::Parser;
+
+use crate::traits::FromSVG;
+use crate::{Atom2, Line2, LineShape, MolecularShape2, ShapeFile};
+
+/// The geometry found within an SVG document
+#[derive(Debug, Default)]
+struct Outlines {
+    /// Each of the closed outlines, with curves flattened to a series of points
+    polygons: Vec<Vec<Point2<f64>>>,
+    circles: Vec<Atom2>,
+}
+
+/// Read a numeric attribute of an element, ignoring any units
+fn attribute(attributes: &Attributes, name: &str) -> Result<f64, Error> {
+    match attributes.get(name) {
+        None => Ok(0.),
+        Some(value) => value
+            .trim()
+            .trim_end_matches(char::is_alphabetic)
+            .parse()
+            .map_err(|_| anyhow!("Unable to parse attribute {}=\"{}\"", name, &**value)),
+    }
+}
+
+/// The number of straight lines required to approximate an arc to within `tolerance`
+///
+/// The largest distance between a chord and the arc it replaces is the sagitta, which is
+/// $r(1 - \cos(\theta/2))$ for a chord spanning an angle of $\theta$.
+///
+fn arc_segments(radius: f64, angle: f64, tolerance: f64) -> usize {
+    let step = 2. * f64::acos((1. - tolerance / radius).max(-1.));
+    usize::max((angle.abs() / step).ceil() as usize, 1)
+}
+
+/// Approximate a quadratic Bézier curve by a series of points
+///
+/// The returned points exclude the start point and include the end point. With $n$ equal steps
+/// the distance of the chords from the curve is bounded by $|p_0 - 2c + p_1| / 4n^2$.
+///
+fn flatten_quadratic(
+    start: Point2<f64>,
+    control: Point2<f64>,
+    end: Point2<f64>,
+    tolerance: f64,
+) -> Vec<Point2<f64>> {
+    let deviation = (start.coords - 2. * control.coords + end.coords).norm();
+    let steps = usize::max((deviation / (4. * tolerance)).sqrt().ceil() as usize, 1);
+    (1..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            Point2::from(
+                start.coords * (1. - t).powi(2)
+                    + control.coords * 2. * t * (1. - t)
+                    + end.coords * t.powi(2),
+            )
+        })
+        .collect()
+}
+
+/// Approximate a cubic Bézier curve by a series of points
+///
+/// The returned points exclude the start point and include the end point. With $n$ equal steps
+/// the distance of the chords from the curve is bounded by $3M / 4n^2$, where $M$ is the largest
+/// second difference of the control points.
+///
+fn flatten_cubic(
+    start: Point2<f64>,
+    control1: Point2<f64>,
+    control2: Point2<f64>,
+    end: Point2<f64>,
+    tolerance: f64,
+) -> Vec<Point2<f64>> {
+    let deviation = f64::max(
+        (start.coords - 2. * control1.coords + control2.coords).norm(),
+        (control1.coords - 2. * control2.coords + end.coords).norm(),
+    );
+    let steps = usize::max(
+        (3. * deviation / (4. * tolerance)).sqrt().ceil() as usize,
+        1,
+    );
+    (1..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            Point2::from(
+                start.coords * (1. - t).powi(3)
+                    + control1.coords * 3. * t * (1. - t).powi(2)
+                    + control2.coords * 3. * t.powi(2) * (1. - t)
+                    + end.coords * t.powi(3),
+            )
+        })
+        .collect()
+}
+
+/// Approximate an elliptical arc by a series of points
+///
+/// This converts the endpoint parameterisation used by SVG to a centre parameterisation following
+/// the implementation notes of the SVG specification. The returned points exclude the start
+/// point and include the end point.
+///
+fn flatten_arc(
+    start: Point2<f64>,
+    radii: (f64, f64),
+    rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    end: Point2<f64>,
+    tolerance: f64,
+) -> Vec<Point2<f64>> {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    // Arcs with no radius are drawn as a straight line
+    if start == end || rx == 0. || ry == 0. {
+        return vec![end];
+    }
+    let (sin_phi, cos_phi) = rotation.to_radians().sin_cos();
+    let half = (start - end) / 2.;
+    let x1 = cos_phi * half.x + sin_phi * half.y;
+    let y1 = -sin_phi * half.x + cos_phi * half.y;
+
+    // Scale up radii which are too small to reach the end point
+    let lambda = x1.powi(2) / rx.powi(2) + y1.powi(2) / ry.powi(2);
+    if lambda > 1. {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx.powi(2) * ry.powi(2) - rx.powi(2) * y1.powi(2) - ry.powi(2) * x1.powi(2);
+    let denominator = rx.powi(2) * y1.powi(2) + ry.powi(2) * x1.powi(2);
+    let sign = if large_arc == sweep { -1. } else { 1. };
+    let coefficient = sign * (numerator / denominator).max(0.).sqrt();
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let centre = nalgebra::center(&start, &end)
+        + Vector2::new(cos_phi * cx1 - sin_phi * cy1, sin_phi * cx1 + cos_phi * cy1);
+
+    let theta_start = f64::atan2((y1 - cy1) / ry, (x1 - cx1) / rx);
+    let theta_end = f64::atan2((-y1 - cy1) / ry, (-x1 - cx1) / rx);
+    let mut delta = (theta_end - theta_start) % (2. * PI);
+    if sweep && delta < 0. {
+        delta += 2. * PI;
+    } else if !sweep && delta > 0. {
+        delta -= 2. * PI;
+    }
+
+    let steps = arc_segments(f64::max(rx, ry), delta, tolerance);
+    (1..=steps)
+        .map(|i| {
+            if i == steps {
+                // Ensure the arc finishes exactly on the end point
+                return end;
+            }
+            let (sin, cos) = (theta_start + delta * i as f64 / steps as f64).sin_cos();
+            centre
+                + Vector2::new(
+                    cos_phi * rx * cos - sin_phi * ry * sin,
+                    sin_phi * rx * cos + cos_phi * ry * sin,
+                )
+        })
+        .collect()
+}
+
+/// Convert the data of a `<path>` element into a collection of closed outlines
+///
+/// Each subpath of the path data becomes a separate outline, which is closed regardless of
+/// whether the path data explicitly closes it, in the same way a fill would be drawn.
+///
+fn flatten_path(data: &str, tolerance: f64) -> Result<Vec<Vec<Point2<f64>>>, Error> {
+    let data = Data::parse(data)?;
+
+    let mut outlines = vec![];
+    let mut current: Vec<Point2<f64>> = vec![];
+    let mut position = Point2::origin();
+    let mut subpath_start = Point2::origin();
+    // The final control point of the previous curve, which is reflected for the smooth curves
+    let mut quadratic_control: Option<Point2<f64>> = None;
+    let mut cubic_control: Option<Point2<f64>> = None;
+
+    for command in data.iter() {
+        let (num_params, params) = match command {
+            Command::Move(_, p) | Command::Line(_, p) | Command::SmoothQuadraticCurve(_, p) => {
+                (2, p)
+            }
+            Command::HorizontalLine(_, p) | Command::VerticalLine(_, p) => (1, p),
+            Command::QuadraticCurve(_, p) | Command::SmoothCubicCurve(_, p) => (4, p),
+            Command::CubicCurve(_, p) => (6, p),
+            Command::EllipticalArc(_, p) => (7, p),
+            Command::Close => {
+                outlines.push(current);
+                current = vec![subpath_start];
+                position = subpath_start;
+                quadratic_control = None;
+                cubic_control = None;
+                continue;
+            }
+        };
+        if params.is_empty() || params.len() % num_params != 0 {
+            bail!("Invalid number of parameters in path command {:?}", command)
+        }
+
+        for (index, chunk) in params.chunks(num_params).enumerate() {
+            let values: Vec<f64> = chunk.iter().map(|&v| f64::from(v)).collect();
+            // All the points within a command are relative to the position at the start.
+            let origin = position;
+            let point = |x: f64, y: f64, position: &Position| match position {
+                Position::Absolute => Point2::new(x, y),
+                Position::Relative => origin + Vector2::new(x, y),
+            };
+            let mut new_quadratic = None;
+            let mut new_cubic = None;
+
+            match command {
+                Command::Move(pos, _) => {
+                    position = point(values[0], values[1], pos);
+                    // Subsequent pairs of a move are treated as lines
+                    if index == 0 {
+                        if current.len() > 1 {
+                            outlines.push(current);
+                        }
+                        current = vec![position];
+                        subpath_start = position;
+                    } else {
+                        current.push(position);
+                    }
+                }
+                Command::Line(pos, _) => {
+                    position = point(values[0], values[1], pos);
+                    current.push(position);
+                }
+                Command::HorizontalLine(pos, _) => {
+                    position = match pos {
+                        Position::Absolute => Point2::new(values[0], origin.y),
+                        Position::Relative => Point2::new(origin.x + values[0], origin.y),
+                    };
+                    current.push(position);
+                }
+                Command::VerticalLine(pos, _) => {
+                    position = match pos {
+                        Position::Absolute => Point2::new(origin.x, values[0]),
+                        Position::Relative => Point2::new(origin.x, origin.y + values[0]),
+                    };
+                    current.push(position);
+                }
+                Command::QuadraticCurve(pos, _) | Command::SmoothQuadraticCurve(pos, _) => {
+                    let (control, end) = match command {
+                        Command::QuadraticCurve(..) => (
+                            point(values[0], values[1], pos),
+                            point(values[2], values[3], pos),
+                        ),
+                        _ => (
+                            quadratic_control.map_or(origin, |c| origin + (origin - c)),
+                            point(values[0], values[1], pos),
+                        ),
+                    };
+                    current.extend(flatten_quadratic(origin, control, end, tolerance));
+                    new_quadratic = Some(control);
+                    position = end;
+                }
+                Command::CubicCurve(pos, _) | Command::SmoothCubicCurve(pos, _) => {
+                    let (control1, control2, end) = match command {
+                        Command::CubicCurve(..) => (
+                            point(values[0], values[1], pos),
+                            point(values[2], values[3], pos),
+                            point(values[4], values[5], pos),
+                        ),
+                        _ => (
+                            cubic_control.map_or(origin, |c| origin + (origin - c)),
+                            point(values[0], values[1], pos),
+                            point(values[2], values[3], pos),
+                        ),
+                    };
+                    current.extend(flatten_cubic(origin, control1, control2, end, tolerance));
+                    new_cubic = Some(control2);
+                    position = end;
+                }
+                Command::EllipticalArc(pos, _) => {
+                    let end = point(values[5], values[6], pos);
+                    current.extend(flatten_arc(
+                        origin,
+                        (values[0], values[1]),
+                        values[2],
+                        values[3] != 0.,
+                        values[4] != 0.,
+                        end,
+                        tolerance,
+                    ));
+                    position = end;
+                }
+                Command::Close => unreachable!(),
+            }
+            quadratic_control = new_quadratic;
+            cubic_control = new_cubic;
+        }
+    }
+    if current.len() > 1 {
+        outlines.push(current);
+    }
+    Ok(outlines)
+}
+
+/// Parse the `points` attribute of a `<polygon>` element
+fn parse_points(points: &str) -> Result<Vec<Point2<f64>>, Error> {
+    let values = points
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() % 2 != 0 {
+        bail!("Polygon has an odd number of coordinates")
+    }
+    Ok(values.chunks(2).map(|p| Point2::new(p[0], p[1])).collect())
+}
+
+/// An affine transformation moving a point by `(x, y)`
+fn translation(x: f64, y: f64) -> Matrix3<f64> {
+    Matrix3::new(1., 0., x, 0., 1., y, 0., 0., 1.)
+}
+
+/// Parse the `transform` attribute of an element into the matrix of an affine transformation
+///
+/// The attribute is a list of transformations, which are applied from right to left in the same
+/// way as multiplying their matrices.
+///
+fn parse_transform(value: &str) -> Result<Matrix3<f64>, Error> {
+    let mut transform = Matrix3::identity();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let (open, close) = match (rest.find('('), rest.find(')')) {
+            (Some(open), Some(close)) if open < close => (open, close),
+            _ => bail!("Unable to parse the transform \"{}\"", value),
+        };
+        let name = rest[..open].trim();
+        let values = rest[open + 1..close]
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let rotation = |angle: f64| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            Matrix3::new(cos, -sin, 0., sin, cos, 0., 0., 0., 1.)
+        };
+        transform *= match (name, values.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => Matrix3::new(a, c, e, b, d, f, 0., 0., 1.),
+            ("translate", &[x]) => translation(x, 0.),
+            ("translate", &[x, y]) => translation(x, y),
+            ("scale", &[x]) => Matrix3::new(x, 0., 0., 0., x, 0., 0., 0., 1.),
+            ("scale", &[x, y]) => Matrix3::new(x, 0., 0., 0., y, 0., 0., 0., 1.),
+            ("rotate", &[angle]) => rotation(angle),
+            ("rotate", &[angle, x, y]) => translation(x, y) * rotation(angle) * translation(-x, -y),
+            ("skewX", &[angle]) => {
+                Matrix3::new(1., angle.to_radians().tan(), 0., 0., 1., 0., 0., 0., 1.)
+            }
+            ("skewY", &[angle]) => {
+                Matrix3::new(1., 0., 0., angle.to_radians().tan(), 1., 0., 0., 0., 1.)
+            }
+            _ => bail!("Unable to parse the transform \"{}\"", &rest[..=close]),
+        };
+        rest = rest[close + 1..].trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    Ok(transform)
+}
+
+/// The path data describing the outline of a `<rect>` element, including any rounded corners
+fn rect_path(attributes: &Attributes) -> Result<String, Error> {
+    let (x, y) = (attribute(attributes, "x")?, attribute(attributes, "y")?);
+    let (width, height) = (
+        attribute(attributes, "width")?,
+        attribute(attributes, "height")?,
+    );
+    // Where only one radius of the corners is given, it is used for both
+    let (rx, ry) = match (attributes.get("rx"), attributes.get("ry")) {
+        (Some(_), None) => (attribute(attributes, "rx")?, attribute(attributes, "rx")?),
+        (None, Some(_)) => (attribute(attributes, "ry")?, attribute(attributes, "ry")?),
+        _ => (attribute(attributes, "rx")?, attribute(attributes, "ry")?),
+    };
+    let (rx, ry) = (rx.min(width / 2.), ry.min(height / 2.));
+    Ok(format!(
+        "M {} {} H {} A {rx} {ry} 0 0 1 {} {} V {} A {rx} {ry} 0 0 1 {} {} H {} \
+         A {rx} {ry} 0 0 1 {} {} V {} A {rx} {ry} 0 0 1 {} {} Z",
+        x + rx,
+        y,
+        x + width - rx,
+        x + width,
+        y + ry,
+        y + height - ry,
+        x + width - rx,
+        y + height,
+        x + rx,
+        x,
+        y + height - ry,
+        y + ry,
+        x + rx,
+        y,
+        rx = rx,
+        ry = ry,
+    ))
+}
+
+/// The path data describing an ellipse centred on `(cx, cy)` with the radii `rx` and `ry`
+fn ellipse_path(cx: f64, cy: f64, rx: f64, ry: f64) -> String {
+    format!(
+        "M {} {cy} A {rx} {ry} 0 1 0 {} {cy} A {rx} {ry} 0 1 0 {} {cy} Z",
+        cx - rx,
+        cx + rx,
+        cx - rx,
+        cy = cy,
+        rx = rx,
+        ry = ry,
+    )
+}
+
+impl Outlines {
+    /// Add the outlines described by the path data `data`, transformed by `transform`
+    fn add_path(
+        &mut self,
+        data: &str,
+        transform: &Matrix3<f64>,
+        tolerance: f64,
+    ) -> Result<(), Error> {
+        // Curves are flattened before they are transformed, so the tolerance is reduced by the
+        // largest amount the transform stretches any distance.
+        let stretch = transform
+            .fixed_slice::<U2, U2>(0, 0)
+            .into_owned()
+            .singular_values()
+            .max();
+        let tolerance = if stretch > 0. {
+            tolerance / stretch
+        } else {
+            tolerance
+        };
+        for outline in flatten_path(data, tolerance)? {
+            self.add_polygon(outline, transform);
+        }
+        Ok(())
+    }
+
+    fn add_polygon(&mut self, points: Vec<Point2<f64>>, transform: &Matrix3<f64>) {
+        self.polygons.push(
+            points
+                .iter()
+                .map(|p| transform.transform_point(p))
+                .collect(),
+        );
+    }
+
+    /// Add the geometry of an element with the name `name`, transformed by `transform`
+    ///
+    /// Elements which can't be read as an outline are ignored with a warning, except for `<use>`
+    /// which would otherwise silently drop the outlines it refers to.
+    ///
+    fn add_element(
+        &mut self,
+        name: &str,
+        attributes: &Attributes,
+        transform: &Matrix3<f64>,
+        tolerance: f64,
+    ) -> Result<(), Error> {
+        match name {
+            "path" => {
+                let data = attributes
+                    .get("d")
+                    .ok_or_else(|| anyhow!("Found path without any data"))?;
+                self.add_path(data, transform, tolerance)?;
+            }
+            // A polyline is closed in the same way as the fill of an open path
+            "polygon" | "polyline" => {
+                let points = attributes
+                    .get("points")
+                    .ok_or_else(|| anyhow!("Found {} without any points", name))?;
+                self.add_polygon(parse_points(points)?, transform);
+            }
+            "rect" => self.add_path(&rect_path(attributes)?, transform, tolerance)?,
+            "ellipse" => self.add_path(
+                &ellipse_path(
+                    attribute(attributes, "cx")?,
+                    attribute(attributes, "cy")?,
+                    attribute(attributes, "rx")?,
+                    attribute(attributes, "ry")?,
+                ),
+                transform,
+                tolerance,
+            )?,
+            "circle" => {
+                let (cx, cy, radius) = (
+                    attribute(attributes, "cx")?,
+                    attribute(attributes, "cy")?,
+                    attribute(attributes, "r")?,
+                );
+                let linear = transform.fixed_slice::<U2, U2>(0, 0).into_owned();
+                let scale = linear.determinant().abs().sqrt();
+                // Only a rotation, reflection and uniform scale keep a circle circular, otherwise
+                // it becomes an elliptical outline.
+                if (linear.transpose() * linear).relative_eq(
+                    &(nalgebra::Matrix2::identity() * scale.powi(2)),
+                    1e-9,
+                    1e-9,
+                ) {
+                    let centre = transform.transform_point(&Point2::new(cx, cy));
+                    self.circles
+                        .push(Atom2::new(centre.x, centre.y, radius * scale));
+                } else {
+                    self.add_path(&ellipse_path(cx, cy, radius, radius), transform, tolerance)?;
+                }
+            }
+            "use" => {
+                bail!("The <use> element is not supported, the referenced shape would be ignored")
+            }
+            "line" | "text" | "image" => {
+                warn!(
+                    "Ignoring the <{}> element, which doesn't enclose an area",
+                    name
+                )
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Find all the outlines and circles within an SVG document
+///
+/// The `transform` attribute of each element is applied to the element and everything it
+/// contains, so the outlines are in the coordinates of the document.
+///
+fn parse_document(contents: &str, tolerance: f64) -> Result<Outlines, Error> {
+    if tolerance.is_nan() || tolerance <= 0. {
+        bail!("The tolerance has to be positive, found {}", tolerance)
+    }
+    let mut outlines = Outlines::default();
+    // The transform of each of the elements currently open
+    let mut transforms = vec![Matrix3::identity()];
+    for event in Parser::new(contents) {
+        match event {
+            Event::Error(e) => return Err(e.into()),
+            Event::Tag(_, Type::End, _) if transforms.len() > 1 => {
+                transforms.pop();
+            }
+            Event::Tag(_, Type::End, _) => (),
+            Event::Tag(name, tag_type, attributes) => {
+                let mut transform = transforms[transforms.len() - 1];
+                if let Some(value) = attributes.get("transform") {
+                    transform *= parse_transform(value)?;
+                }
+                outlines.add_element(name, &attributes, &transform, tolerance)?;
+                if tag_type == Type::Start {
+                    transforms.push(transform);
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(outlines)
+}
+
+/// Convert a collection of points into the lines joining them
+///
+/// The final point is joined back to the first, with any repeated points removed. Numbers in an
+/// SVG document are only single precision, so points are considered repeated when they are
+/// closer than a small fraction of the `tolerance`.
+///
+fn to_lines(mut points: Vec<Point2<f64>>, tolerance: f64) -> Vec<Line2> {
+    let repeated = |a: &Point2<f64>, b: &Point2<f64>| nalgebra::distance(a, b) < tolerance / 100.;
+    points.dedup_by(|a, b| repeated(a, b));
+    while points.len() > 1 && repeated(&points[0], &points[points.len() - 1]) {
+        points.pop();
+    }
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(start, end)| Line2 {
+            start: *start,
+            end: *end,
+        })
+        .collect()
+}
+
+impl FromSVG for LineShape {
+    /// Create a LineShape from the single outline within an SVG document
+    ///
+    /// The outline can be defined by a `<path>`, `<polygon>`, `<polyline>`, `<rect>` or
+    /// `<ellipse>` element. A `<circle>` is also accepted, being approximated by a regular
+    /// polygon.
+    ///
+    fn from_svg(name: &str, contents: &str, tolerance: f64) -> Result<Self, Error> {
+        let mut outlines = parse_document(contents, tolerance)?;
+        for circle in outlines.circles.iter() {
+            let steps = usize::max(arc_segments(circle.radius, 2. * PI, tolerance), 3);
+            outlines.polygons.push(
+                (0..steps)
+                    .map(|i| {
+                        let (sin, cos) = (2. * PI * i as f64 / steps as f64).sin_cos();
+                        circle.position + Vector2::new(sin, cos) * circle.radius
+                    })
+                    .collect(),
+            );
+        }
+        outlines.polygons.retain(|p| p.len() > 2);
+        if outlines.polygons.len() != 1 {
+            bail!(
+                "A LineShape requires exactly one outline, found {}",
+                outlines.polygons.len()
+            )
+        }
+        let shape = LineShape {
+            name: String::from(name),
+            items: to_lines(outlines.polygons.remove(0), tolerance),
+        };
+        shape.validate()?;
+        Ok(shape.recentre())
+    }
+}
+
+impl FromSVG for MolecularShape2 {
+    /// Create a MolecularShape2 from the `<circle>` elements of an SVG document
+    ///
+    /// Any other outlines are ignored, although a warning is given when they are present.
+    ///
+    fn from_svg(name: &str, contents: &str, tolerance: f64) -> Result<Self, Error> {
+        let outlines = parse_document(contents, tolerance)?;
+        if !outlines.polygons.is_empty() {
+            warn!(
+                "Ignoring {} outlines which are not circles",
+                outlines.polygons.len()
+            );
+        }
+        let shape = MolecularShape2 {
+            name: String::from(name),
+            items: outlines.circles,
+        };
+        shape.validate()?;
+        Ok(shape.recentre())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::traits::{Intersect, Shape, ToSVG};
+
+    #[test]
+    fn polygon_element() {
+        let contents = r#"<svg><polygon points="0,0 2,0 2,2 0,2" /></svg>"#;
+        let shape = LineShape::from_svg("Square", contents, 0.01).unwrap();
+        assert_eq!(shape.items.len(), 4);
+        assert_abs_diff_eq!(shape.items[0].start, Point2::new(-1., -1.), epsilon = 1e-12);
+        assert_abs_diff_eq!(shape.items[3].end, Point2::new(-1., -1.), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn path_relative() {
+        let contents = r#"<svg><path d="M 1 1 h 2 v 2 l -2 0 z" /></svg>"#;
+        let shape = LineShape::from_svg("Square", contents, 0.01).unwrap();
+        assert_eq!(shape.items.len(), 4);
+        assert_abs_diff_eq!(shape.items[1].start, Point2::new(1., -1.), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn path_arc_tolerance() {
+        // A circle of radius 1 made from two arcs
+        let contents = r#"<svg><path d="M -1 0 A 1 1 0 0 1 1 0 A 1 1 0 0 1 -1 0 Z" /></svg>"#;
+        for &tolerance in &[0.1, 0.01, 0.001] {
+            let shape = LineShape::from_svg("Circle", contents, tolerance).unwrap();
+            for line in shape.iter() {
+                let midpoint = nalgebra::center(&line.start, &line.end);
+                assert_abs_diff_eq!(line.start.coords.norm(), 1., epsilon = 1e-6);
+                assert!(1. - midpoint.coords.norm() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn path_cubic_endpoints() {
+        let contents = r#"<svg><path d="M 0 0 C 1 1 2 1 3 0 S 5 -1 6 0 L 3 -3 Z" /></svg>"#;
+        let shape = LineShape::from_svg("Curve", contents, 0.01).unwrap();
+        assert!(shape.items.len() > 3);
+        let points: Vec<_> = shape.iter().map(|l| l.start).collect();
+        // The curve passes through the end point of the first segment
+        let offset = points[0] - Point2::new(0., 0.);
+        assert!(points
+            .iter()
+            .any(|p| nalgebra::distance(p, &(Point2::new(3., 0.) + offset)) < 1e-6));
+    }
+
+    #[test]
+    fn multiple_outlines() {
+        let contents = r#"<svg>
+            <polygon points="0,0 1,0 1,1" />
+            <polygon points="2,2 3,2 3,3" />
+        </svg>"#;
+        assert!(LineShape::from_svg("Invalid", contents, 0.01).is_err());
+    }
+
+    #[test]
+    fn circle_elements() {
+        let contents = r#"<svg>
+            <circle cx="1" cy="0" r="1" />
+            <circle cx="3" cy="0" r="1" />
+        </svg>"#;
+        let shape = MolecularShape2::from_svg("Dimer", contents, 0.01).unwrap();
+        assert_eq!(shape.items.len(), 2);
+        assert_abs_diff_eq!(shape.items[0].position, Point2::new(-1., 0.));
+        assert_abs_diff_eq!(shape.items[1].radius, 1.);
+    }
+
+    #[test]
+    fn no_circles() {
+        let contents = r#"<svg><polygon points="0,0 1,0 1,1" /></svg>"#;
+        assert!(MolecularShape2::from_svg("Invalid", contents, 0.01).is_err());
+    }
+
+    #[test]
+    fn group_transform() {
+        // The square is scaled to a side of 4, then rotated by 45 degrees
+        let contents = r#"<svg><g transform="rotate(45)">
+            <polygon transform="translate(10, 0) scale(2)" points="0,0 2,0 2,2 0,2" />
+        </g></svg>"#;
+        let shape = LineShape::from_svg("Square", contents, 0.01).unwrap();
+        assert_abs_diff_eq!(shape.area(), 16., epsilon = 1e-9);
+        assert_abs_diff_eq!(
+            shape.items[0].start,
+            Point2::new(0., -2. * 2f64.sqrt()),
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn transform_ends_with_group() {
+        let contents = r#"<svg>
+            <g transform="scale(3)"><circle cx="0" cy="0" r="1" /></g>
+            <circle cx="4" cy="0" r="1" />
+        </svg>"#;
+        let shape = MolecularShape2::from_svg("Dimer", contents, 0.01).unwrap();
+        assert_abs_diff_eq!(shape.items[0].radius, 3.);
+        assert_abs_diff_eq!(shape.items[1].radius, 1.);
+        assert_abs_diff_eq!(
+            shape.items[1].position - shape.items[0].position,
+            Vector2::new(4., 0.)
+        );
+    }
+
+    #[test]
+    fn stretched_circle() {
+        // A circle stretched along one axis is an ellipse, which is an outline not a circle
+        let contents = r#"<svg><circle transform="matrix(2 0 0 1 0 0)" r="1" /></svg>"#;
+        assert!(MolecularShape2::from_svg("Ellipse", contents, 0.01).is_err());
+        let shape = LineShape::from_svg("Ellipse", contents, 0.001).unwrap();
+        assert_abs_diff_eq!(shape.area(), 2. * PI, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn invalid_transform() {
+        let contents = r#"<svg><polygon transform="spin(4)" points="0,0 1,0 1,1" /></svg>"#;
+        assert!(LineShape::from_svg("Invalid", contents, 0.01).is_err());
+    }
+
+    #[test]
+    fn rect_element() {
+        let contents = r#"<svg><rect x="1" y="1" width="4" height="2" /></svg>"#;
+        let shape = LineShape::from_svg("Rectangle", contents, 0.01).unwrap();
+        assert_eq!(shape.items.len(), 4);
+        assert_abs_diff_eq!(shape.area(), 8., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn rounded_rect_element() {
+        let contents = r#"<svg><rect width="4" height="2" rx="1" /></svg>"#;
+        let shape = LineShape::from_svg("Rounded", contents, 0.001).unwrap();
+        assert_abs_diff_eq!(shape.area(), 8. - (4. - PI), epsilon = 1e-2);
+    }
+
+    #[test]
+    fn ellipse_element() {
+        let contents = r#"<svg><ellipse cx="1" cy="1" rx="2" ry="1" /></svg>"#;
+        let shape = LineShape::from_svg("Ellipse", contents, 0.001).unwrap();
+        assert_abs_diff_eq!(shape.area(), 2. * PI, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn use_element() {
+        let contents = r##"<svg>
+            <defs><polygon id="a" points="0,0 1,0 1,1" /></defs>
+            <use href="#a" />
+        </svg>"##;
+        assert!(LineShape::from_svg("Invalid", contents, 0.01).is_err());
+    }
+
+    #[test]
+    fn round_trip_molecule() {
+        let shape = MolecularShape2::from_trimer(0.7, 120., 1.);
+        let contents = svg::Document::new().add(shape.as_svg()).to_string();
+        let read = MolecularShape2::from_svg("Trimer", &contents, 0.01).unwrap();
+        let centroid = shape.centroid();
+        for (original, new) in shape.iter().zip(read.iter()) {
+            assert_abs_diff_eq!(
+                original.position - centroid,
+                new.position.coords,
+                epsilon = 1e-6
+            );
+            assert_abs_diff_eq!(original.radius, new.radius, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn round_trip_polygon() {
+        let shape = LineShape::polygon(6).unwrap();
+        let contents = svg::Document::new().add(shape.as_svg()).to_string();
+        let read = LineShape::from_svg("Hexagon", &contents, 0.01).unwrap();
+        assert_eq!(read.items.len(), 6);
+        assert_abs_diff_eq!(read.area(), shape.area(), epsilon = 1e-6);
+    }
+}
@@ -8,24 +8,33 @@
 // most of the imports throughout the rest of the crate can just be from the top level and nicely
 // grouped together.
 
-pub mod basis;
 pub mod cell;
+pub mod ewald;
+pub mod from_svg;
+pub mod minimise;
+pub mod neighbours;
 pub mod ops_macros;
 pub mod optimisation;
+pub mod parameters;
 pub mod shape;
 pub mod site;
 pub mod state;
+pub mod tempering;
 pub mod to_svg;
 pub mod traits;
 pub mod transform;
 pub mod wallpaper;
 
-pub use crate::basis::*;
 pub use crate::cell::*;
+pub use crate::ewald::Ewald;
+pub use crate::minimise::*;
+pub use crate::neighbours::NeighbourList;
 pub use crate::optimisation::*;
+pub use crate::parameters::Parameters;
 pub use crate::shape::*;
 pub use crate::site::*;
 pub use crate::state::*;
-pub use crate::traits::{FromSymmetry, Intersect, Shape};
+pub use crate::tempering::*;
+pub use crate::traits::{FromSymmetry, Intersect, Separation, Shape};
 pub use crate::transform::Transform2;
 pub use crate::wallpaper::WallpaperGroup;
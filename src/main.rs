@@ -7,19 +7,24 @@
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::path;
 use std::path::PathBuf;
 
 use anyhow::{anyhow, bail, Error};
+use itertools::Itertools;
 use log::{debug, info, LevelFilter};
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
 use rayon::prelude::*;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 
 use packing::traits::*;
-use packing::wallpaper::{get_wallpaper_group, WallpaperGroups};
+use packing::wallpaper::{get_wallpaper_group, WallpaperGroup, WallpaperGroups};
 use packing::{
-    BuildOptimiser, LJShape2, LineShape, MolecularShape2, PackedState2, PotentialState2,
+    AttractiveWell, BuildMinimiser, BuildOptimiser, BuildTempering, CompositeShape, Ewald,
+    HybridState2, LJShape2, LineShape, MixingRule, MolecularShape2, PackedState2, PairPotential,
+    PatchyCore, PatchyShape2, PotentialState2, RoundedShape, ShapeFile, SizeDistribution,
+    TabulatedPotential, TabulatedShape2, Truncation, XYZMolecule,
 };
 
 arg_enum! {
@@ -27,6 +32,238 @@ arg_enum! {
     enum Force {
         LJ,
         Hard,
+        Mie,
+        WCA,
+        Morse,
+        Yukawa,
+        GaussianCore,
+        SquareWell,
+        SoftHarmonic,
+    }
+}
+
+impl Force {
+    /// The interaction between particles, which is None for hard shapes
+    fn pair_potential(&self, options: &PairOptions) -> Option<PairPotential> {
+        match self {
+            Force::Hard => None,
+            Force::LJ => Some(PairPotential::LennardJones),
+            Force::Mie => Some(PairPotential::Mie {
+                repulsive: options.repulsive,
+                attractive: options.attractive,
+            }),
+            Force::WCA => Some(PairPotential::WCA),
+            Force::Morse => Some(PairPotential::Morse {
+                alpha: options.alpha,
+            }),
+            Force::Yukawa => Some(PairPotential::Yukawa {
+                kappa: options.kappa,
+            }),
+            Force::GaussianCore => Some(PairPotential::GaussianCore),
+            Force::SquareWell => Some(PairPotential::SquareWell {
+                width: options.width,
+            }),
+            Force::SoftHarmonic => Some(PairPotential::SoftHarmonic),
+        }
+    }
+}
+
+/// The parameters describing the shape of the pair potentials
+#[derive(Debug, StructOpt)]
+struct PairOptions {
+    /// The repulsive exponent of the Mie potential
+    #[structopt(long, default_value = "12")]
+    repulsive: f64,
+
+    /// The attractive exponent of the Mie potential
+    #[structopt(long, default_value = "6")]
+    attractive: f64,
+
+    /// The stiffness of the Morse potential, in units of 1/sigma
+    #[structopt(long, default_value = "6")]
+    alpha: f64,
+
+    /// The inverse screening length of the Yukawa potential, in units of 1/sigma
+    #[structopt(long, default_value = "1")]
+    kappa: f64,
+
+    /// The extent of the square well potential, in units of sigma
+    #[structopt(long, default_value = "1.5")]
+    width: f64,
+
+    /// How the parameters of different particles are combined
+    ///
+    /// A shape file can also specify the parameters for each pair of species in a table, which
+    /// is used when this is not given.
+    #[structopt(long, possible_values = &Mixing::variants(), case_insensitive = true)]
+    mixing: Option<Mixing>,
+
+    /// How the potential of particles with a cutoff is shifted to zero at the cutoff
+    #[structopt(
+        long,
+        possible_values = &TruncationScheme::variants(),
+        case_insensitive = true,
+        default_value = "Shifted"
+    )]
+    truncation: TruncationScheme,
+
+    /// Correct the energy for the interactions beyond the cutoff
+    ///
+    /// This assumes the particles beyond the cutoff have a uniform density, allowing the
+    /// energies of structures using different cutoffs to be compared.
+    #[structopt(long)]
+    tail_correction: bool,
+}
+
+impl PairOptions {
+    /// Whether any of the options which modify the pairs of particles are chosen
+    fn modifies_pairs(&self) -> bool {
+        self.mixing.is_some()
+            || matches!(self.truncation, TruncationScheme::ShiftedForce)
+            || self.tail_correction
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum Mixing {
+        LorentzBerthelot,
+        Geometric,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum TruncationScheme {
+        Shifted,
+        ShiftedForce,
+    }
+}
+
+/// The attraction between hard shapes, which are scored using a hybrid of energy and density
+#[derive(Debug, StructOpt)]
+struct HybridOptions {
+    /// Add an attractive well between hard shapes which aren't overlapping
+    ///
+    /// Only shapes made from circles or lines support the attractive well, since the well
+    /// depends on the separation between the shapes.
+    #[structopt(long, possible_values = &Well::variants(), case_insensitive = true)]
+    well: Option<Well>,
+
+    /// The range of the attractive well, as the separation between the shapes
+    #[structopt(long, default_value = "0.5")]
+    well_range: f64,
+
+    /// The depth of the attractive well
+    #[structopt(long, default_value = "1")]
+    well_depth: f64,
+
+    /// The contribution of the packing fraction to the score of the attractive shapes
+    #[structopt(long, default_value = "0")]
+    density_weight: f64,
+}
+
+impl HybridOptions {
+    fn attractive_well(&self) -> Option<AttractiveWell> {
+        match self.well {
+            None => None,
+            Some(Well::SquareWell) => Some(AttractiveWell::SquareWell {
+                width: self.well_range,
+                depth: self.well_depth,
+            }),
+            // Cut off the potential at 2.5 sigma beyond contact, like the usual LJ cutoff
+            Some(Well::LJ) => Some(AttractiveWell::LennardJones {
+                sigma: self.well_range / 2.5,
+                epsilon: self.well_depth,
+                cutoff: self.well_range,
+            }),
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum Well {
+        SquareWell,
+        LJ,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy)]
+    enum Polish {
+        Quench,
+        FIRE,
+    }
+}
+
+/// The final stage of the optimisation, which moves the state into the nearest minimum
+#[derive(Debug, StructOpt)]
+struct PolishOptions {
+    /// The method used to polish the final state
+    ///
+    /// The quench is a Monte Carlo optimisation at zero temperature, while FIRE follows the
+    /// gradient of the energy. FIRE is only available for LJ and tabulated potentials without
+    /// partial charges.
+    #[structopt(
+        long,
+        possible_values = &Polish::variants(),
+        case_insensitive = true,
+        default_value = "Quench"
+    )]
+    polish: Polish,
+
+    #[structopt(flatten)]
+    minimiser: BuildMinimiser,
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum ShapeKind {
+        Molecular,
+        Line,
+        Rounded,
+        Composite,
+        LJ,
+    }
+}
+
+/// The Monte Carlo optimisation of each of the replications
+#[derive(Debug, StructOpt)]
+struct OptimiserOptions {
+    #[structopt(flatten)]
+    annealing: BuildOptimiser,
+
+    /// Optimise using replica exchange rather than a single annealed chain
+    ///
+    /// Each replication runs many replicas at a ladder of temperatures, which swap their
+    /// configurations. The size of the moves is the same as for the annealing.
+    #[structopt(long)]
+    tempering: bool,
+
+    #[structopt(flatten)]
+    replica_exchange: BuildTempering,
+}
+
+impl OptimiserOptions {
+    /// Optimise the `state` using either tempering or annealing with the given `seed`
+    fn optimise_state<S: State>(&self, seed: u64, state: S) -> S {
+        if self.tempering {
+            let (state, swaps) = self
+                .replica_exchange
+                .clone()
+                .seed(seed)
+                .build(&self.annealing)
+                .optimise_state(state);
+            info!("Replication {}: {}", seed, swaps);
+            state
+        } else {
+            self.annealing
+                .clone()
+                .seed(seed)
+                .build()
+                .optimise_state(state)
+        }
     }
 }
 
@@ -48,23 +285,51 @@ struct Args {
     wallpaper: WallpaperGroups,
 
     /// The potential which is being optimised
-    #[structopt(short, long, possible_values = &Force::variants(), default_value = "Hard")]
-    potential: Force,
+    ///
+    /// This defaults to Hard, except for shapes which define their own potential, like LJ shape
+    /// files, where the potential of each site is kept unless this is given.
+    #[structopt(short, long, possible_values = &Force::variants())]
+    potential: Option<Force>,
 
     /// Where to save the best packed structure
     #[structopt(long, parse(from_os_str))]
     outfile: PathBuf,
 
-    /// An initial configuration which is the starting point for optimisation
-    #[structopt(long, parse(from_os_str))]
-    start_config: Option<PathBuf>,
-
     /// The number of independent starting configurations to optimise
     #[structopt(long, default_value = "100")]
     replications: u64,
 
+    /// Pack a racemic mixture of the shape and its mirror image
+    ///
+    /// This is only possible for hard shapes in wallpaper groups without mirror or glide
+    /// operations.
+    #[structopt(long)]
+    racemic: bool,
+
+    /// The number of independent copies of the shape on the general position
+    ///
+    /// For a co-crystal this multiplies the stoichiometry of each component.
+    #[structopt(long, default_value = "1")]
+    z_prime: usize,
+
+    /// The external pressure applied to the unit cell of a soft potential
+    ///
+    /// This minimises the enthalpy rather than the energy, favouring denser structures at
+    /// higher pressures.
+    #[structopt(long, default_value = "0")]
+    pressure: f64,
+
+    #[structopt(flatten)]
+    pair: PairOptions,
+
+    #[structopt(flatten)]
+    hybrid: HybridOptions,
+
+    #[structopt(flatten)]
+    optimisation: OptimiserOptions,
+
     #[structopt(flatten)]
-    optimisation: BuildOptimiser,
+    polish: PolishOptions,
 }
 
 #[derive(Debug, StructOpt)]
@@ -74,6 +339,12 @@ enum Shapes {
         /// The number of equally spaced sides
         #[structopt(long, default_value = "4")]
         sides: usize,
+        /// The number of LJ sites per unit length along each side, used with the LJ potential
+        #[structopt(long, default_value = "4")]
+        density: f64,
+        /// The size of each of the LJ sites along the sides, used with the LJ potential
+        #[structopt(long, default_value = "0.25")]
+        sigma: f64,
     },
     #[structopt(name = "trimer")]
     Trimer {
@@ -89,19 +360,399 @@ enum Shapes {
     },
     #[structopt(name = "circle")]
     Circle {},
+    /// Circles of multiple sizes, with each circle occupying a separate site
+    ///
+    /// With the LJ potential the diameter of each circle is used for sigma.
+    #[structopt(name = "disks")]
+    Disks {
+        /// The radius of each circle in the unit cell, separated by commas
+        #[structopt(long, use_delimiter = true, required_unless = "count")]
+        radii: Vec<f64>,
+        /// Draw this many radii from a normal distribution with a mean of 1
+        #[structopt(long, conflicts_with = "radii")]
+        count: Option<usize>,
+        /// The standard deviation of the radii drawn from the normal distribution
+        #[structopt(long, default_value = "0.1")]
+        polydispersity: f64,
+        /// The seed of the random numbers used to draw the radii
+        ///
+        /// The same seed draws the same radii, which are also saved alongside the results.
+        #[structopt(long, default_value = "0")]
+        size_seed: u64,
+    },
+    #[structopt(name = "spherocylinder")]
+    Spherocylinder {
+        /// The length of the line segment between the two rounded ends
+        #[structopt(short, long, default_value = "1.")]
+        length: f64,
+        /// The radius of the rounded ends
+        #[structopt(short, long, default_value = "0.5")]
+        radius: f64,
+    },
+    #[structopt(name = "rounded-polygon")]
+    RoundedPolygon {
+        /// The number of equally spaced sides
+        #[structopt(long, default_value = "4")]
+        sides: usize,
+        /// The radius of the rounding applied to the polygon
+        #[structopt(short, long, default_value = "0.2")]
+        radius: f64,
+    },
+    #[structopt(name = "file")]
+    File {
+        /// A json or toml file containing the definition of the shape
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// The type of shape defined in the file
+        #[structopt(long, possible_values = &ShapeKind::variants(), case_insensitive = true)]
+        kind: ShapeKind,
+    },
+    /// A co-crystal of multiple shapes, each defined in a json or toml file
+    #[structopt(name = "cocrystal")]
+    Cocrystal {
+        /// The files containing the definition of each shape
+        #[structopt(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+        /// The number of general positions occupied by each shape, separated by commas
+        ///
+        /// By default each shape occupies a single general position.
+        #[structopt(long, use_delimiter = true)]
+        stoichiometry: Vec<usize>,
+        /// The type of shape defined in the files
+        #[structopt(long, possible_values = &ShapeKind::variants(), case_insensitive = true)]
+        kind: ShapeKind,
+    },
+    /// A planar molecule read from an XYZ file
+    ///
+    /// Lengths are in Å and, with a potential, energies are in kcal/mol, so the temperature is
+    /// also in kcal/mol.
+    #[structopt(name = "xyz")]
+    Xyz {
+        /// An XYZ file containing the coordinates of a planar molecule
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Particles interacting through a pair potential read from a table
+    ///
+    /// The form of the interactions comes from the table, so no potential is chosen with
+    /// --potential.
+    #[structopt(name = "tabulated")]
+    Tabulated {
+        /// A text file with columns of distance and energy
+        #[structopt(parse(from_os_str))]
+        table: PathBuf,
+        /// The distance beyond which the potential is zero, defaulting to the end of the table
+        #[structopt(long)]
+        cutoff: Option<f64>,
+        /// A file describing a LJ shape, with the tabulated potential used at each site
+        #[structopt(long, parse(from_os_str))]
+        shape: Option<PathBuf>,
+    },
+    /// A disk with attractive patches giving directional interactions
+    ///
+    /// The core of the disk is hard with the Hard potential, or interacts through the LJ
+    /// potential with the LJ potential.
+    #[structopt(name = "patchy")]
+    Patchy {
+        /// The number of equally spaced patches
+        #[structopt(long, default_value = "3")]
+        patches: usize,
+        /// The angle from the center to the edge of each patch in degrees
+        #[structopt(long, default_value = "20")]
+        half_width: f64,
+        /// The largest distance between bonded particles, in units of the core diameter
+        #[structopt(long, default_value = "1.2")]
+        range: f64,
+        /// The energy of a bond between two patches
+        #[structopt(long, default_value = "1")]
+        strength: f64,
+    },
+    #[structopt(name = "svg")]
+    Svg {
+        /// An SVG file containing the outline of the shape
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// The type of shape to create, either molecular from the circles or line from the outline
+        #[structopt(long, possible_values = &ShapeKind::variants(), case_insensitive = true)]
+        kind: ShapeKind,
+        /// The largest distance between a curve and the lines approximating it
+        #[structopt(long, default_value = "0.01")]
+        tolerance: f64,
+    },
+}
+
+/// Whether the shapes define the potential of each site, rather than taking it from the options
+fn defines_potential(shape: &Shapes) -> bool {
+    match shape {
+        Shapes::File { kind, .. } | Shapes::Cocrystal { kind, .. } => {
+            matches!(kind, ShapeKind::LJ)
+        }
+        Shapes::Tabulated { .. } => true,
+        _ => false,
+    }
+}
+
+/// The options which are shared by every shape, describing how a state is built and optimised
+struct RunOptions<'a> {
+    outfile: PathBuf,
+    replications: u64,
+    group: WallpaperGroup<'a>,
+    racemic: bool,
+    pressure: f64,
+    /// The potential replacing the potential of each LJ site, which is None to keep them
+    site_potential: Option<PairPotential>,
+    pair: &'a PairOptions,
+    hybrid: &'a HybridOptions,
+    optimisation: &'a OptimiserOptions,
+    polish: &'a PolishOptions,
+}
+
+impl RunOptions<'_> {
+    /// Create a state of hard shapes, as a racemic mixture when requested
+    fn packed_state<S>(&self, components: Vec<(S, usize)>) -> Result<PackedState2<S>, Error>
+    where
+        S: Shape + Intersect,
+    {
+        let state = PackedState2::from_stoichiometry(components, &self.group)?;
+        if self.racemic {
+            state.racemic()
+        } else {
+            Ok(state)
+        }
+    }
+
+    /// Create a state with a soft potential, which can't be a racemic mixture
+    fn potential_state<S>(&self, components: Vec<(S, usize)>) -> Result<PotentialState2<S>, Error>
+    where
+        S: Shape + Potential,
+    {
+        if self.racemic {
+            bail!("A racemic mixture can only be packed with hard shapes which don't interact")
+        }
+        PotentialState2::from_stoichiometry(components, &self.group)?.with_pressure(self.pressure)
+    }
+
+    /// Ensure there is no attractive well, for shapes which don't support one
+    fn check_no_well(&self) -> Result<(), Error> {
+        if self.hybrid.well.is_some() {
+            bail!("An attractive well is only supported for hard shapes made of circles or lines")
+        }
+        Ok(())
+    }
+
+    /// Ensure none of the options modifying the pairs of LJ sites are chosen
+    fn check_unmodified_pairs(&self, name: &str) -> Result<(), Error> {
+        if self.pair.modifies_pairs() {
+            bail!(
+                "{} don't support --mixing, --truncation or --tail-correction",
+                name
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Shapes which can be packed and optimised from the command line
+///
+/// Each shape chooses the state it is packed in and how that state is optimised, so every source
+/// of shapes only has to create the components of the state.
+///
+trait RunShape: Shape + Sized {
+    /// Create a state from the `components`, each being a shape and the number of general
+    /// positions it occupies, then optimise the state and save the best result.
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error>;
+}
+
+/// Hard shapes which support an attractive well, since the separation between them is known
+fn run_separable<S>(components: Vec<(S, usize)>, options: &RunOptions) -> Result<(), Error>
+where
+    S: Shape + Intersect + Separation,
+{
+    let state = options.packed_state(components)?;
+    match options.hybrid.attractive_well() {
+        Some(well) => analyse_state(
+            HybridState2::new(state, well)?.with_density_weight(options.hybrid.density_weight)?,
+            options,
+        ),
+        None => analyse_state(state, options),
+    }
+}
+
+impl RunShape for MolecularShape2 {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        run_separable(components, options)
+    }
+}
+
+impl RunShape for LineShape {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        run_separable(components, options)
+    }
+}
+
+impl RunShape for RoundedShape {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        options.check_no_well()?;
+        analyse_state(options.packed_state(components)?, options)
+    }
+}
+
+impl RunShape for CompositeShape {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        options.check_no_well()?;
+        analyse_state(options.packed_state(components)?, options)
+    }
+}
+
+impl RunShape for LJShape2 {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        options.check_no_well()?;
+        let mixing = match options.pair.mixing {
+            Some(Mixing::LorentzBerthelot) => Some(MixingRule::LorentzBerthelot),
+            Some(Mixing::Geometric) => Some(MixingRule::Geometric),
+            None => None,
+        };
+        let truncation = match options.pair.truncation {
+            TruncationScheme::Shifted => Truncation::Shifted,
+            TruncationScheme::ShiftedForce => Truncation::ShiftedForce,
+        };
+        let components = components
+            .into_iter()
+            .map(|(shape, count)| {
+                let shape = match options.site_potential {
+                    Some(p) => shape.with_potential(p),
+                    None => shape,
+                }
+                .with_truncation(truncation);
+                match &mixing {
+                    Some(m) => (shape.with_mixing(m.clone()), count),
+                    None => (shape, count),
+                }
+            })
+            .collect();
+        let mut state = options.potential_state(components)?;
+        if options.pair.tail_correction {
+            state = state.with_tail_correction();
+        }
+        // Charges have long ranged interactions which are only captured by an Ewald sum
+        if state.has_charges() {
+            info!("Including Coulomb interactions between the partial charges");
+            state = state.with_ewald(Ewald::default())?;
+        }
+        analyse_potential(state, options)
+    }
+}
+
+impl RunShape for TabulatedShape2 {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        options.check_no_well()?;
+        options.check_unmodified_pairs("Tabulated potentials")?;
+        analyse_potential(options.potential_state(components)?, options)
+    }
+}
+
+impl RunShape for PatchyShape2 {
+    fn run(components: Vec<(Self, usize)>, options: &RunOptions) -> Result<(), Error> {
+        options.check_no_well()?;
+        options.check_unmodified_pairs("Patchy particles")?;
+        analyse_state(options.potential_state(components)?, options)
+    }
+}
+
+/// Read the shape of each component of a co-crystal along with the number of general positions
+fn read_components<S: ShapeFile>(
+    paths: &[PathBuf],
+    stoichiometry: &[usize],
+    z_prime: usize,
+) -> Result<Vec<(S, usize)>, Error> {
+    let counts = match stoichiometry.len() {
+        0 => vec![1; paths.len()],
+        n if n == paths.len() => stoichiometry.to_vec(),
+        n => bail!(
+            "The stoichiometry has {} values, however there are {} shapes",
+            n,
+            paths.len()
+        ),
+    };
+    paths
+        .iter()
+        .zip(counts)
+        .map(|(path, count)| Ok((S::from_file(path)?, count * z_prime)))
+        .collect()
 }
 
-fn analyse_state(
-    outfile: path::PathBuf,
-    start_configs: u64,
-    state: impl State,
-    optimiser: &BuildOptimiser,
+/// Read the components of a co-crystal and optimise them, with each file holding a `kind` of shape
+fn run_files(
+    kind: ShapeKind,
+    paths: &[PathBuf],
+    stoichiometry: &[usize],
+    z_prime: usize,
+    options: &RunOptions,
 ) -> Result<(), Error> {
-    let final_state = (0..start_configs)
+    match kind {
+        ShapeKind::Molecular => RunShape::run(
+            read_components::<MolecularShape2>(paths, stoichiometry, z_prime)?,
+            options,
+        ),
+        ShapeKind::Line => RunShape::run(
+            read_components::<LineShape>(paths, stoichiometry, z_prime)?,
+            options,
+        ),
+        ShapeKind::Rounded => RunShape::run(
+            read_components::<RoundedShape>(paths, stoichiometry, z_prime)?,
+            options,
+        ),
+        ShapeKind::Composite => RunShape::run(
+            read_components::<CompositeShape>(paths, stoichiometry, z_prime)?,
+            options,
+        ),
+        ShapeKind::LJ => RunShape::run(
+            read_components::<LJShape2>(paths, stoichiometry, z_prime)?,
+            options,
+        ),
+    }
+}
+
+/// Optimise a state with a soft potential, polishing the final state using the chosen method
+fn analyse_potential<S: ScoreGradient>(state: S, options: &RunOptions) -> Result<(), Error> {
+    match options.polish.polish {
+        Polish::Quench => analyse_state(state, options),
+        Polish::FIRE => {
+            // Check the gradient is available before spending time on the optimisation
+            state.score_gradient()?;
+            let minimiser = options.polish.minimiser.build();
+            optimise_and_save(state, options, |_, opt_state| {
+                minimiser.minimise_state(opt_state)
+            })
+        }
+    }
+}
+
+fn analyse_state(state: impl State, options: &RunOptions) -> Result<(), Error> {
+    optimise_and_save(state, options, |index, opt_state| {
+        Ok(options
+            .optimisation
+            .annealing
+            .clone()
+            .kt_start(0.)
+            .seed(index)
+            .build()
+            .optimise_state(opt_state))
+    })
+}
+
+fn optimise_and_save<S, F>(state: S, options: &RunOptions, polish: F) -> Result<(), Error>
+where
+    S: State,
+    F: Fn(u64, S) -> Result<S, Error> + Sync + Send,
+{
+    let optimiser = options.optimisation;
+    let final_state = (0..options.replications)
         .into_par_iter()
         // Create collection of quickly optimised initial states
         .map(|index| {
             let result = optimiser
+                .annealing
                 .clone()
                 .steps(1000)
                 .kt_start(0.)
@@ -112,23 +763,11 @@ fn analyse_state(
             (index, result)
         })
         // Perform Monte carlo optimisation
-        .map(|(index, opt_state)| {
-            let result = optimiser
-                .clone()
-                .seed(index)
-                .build()
-                .optimise_state(opt_state);
-            (index, result)
-        })
+        .map(|(index, opt_state)| (index, optimiser.optimise_state(index, opt_state)))
         // Final optimsation to help find the minimum
-        .map(|(index, opt_state)| {
-            optimiser
-                .clone()
-                .kt_start(0.)
-                .seed(index)
-                .build()
-                .optimise_state(opt_state)
-        })
+        .map(|(index, opt_state)| polish(index, opt_state))
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
         .max()
         .ok_or_else(|| anyhow!("Error in running optimisation."))?;
 
@@ -141,7 +780,8 @@ fn analyse_state(
 
     let serialised = serde_json::to_string(&final_state)?;
 
-    File::create(outfile.with_extension("json"))?.write_all(&serialised.as_bytes())?;
+    let outfile = &options.outfile;
+    File::create(outfile.with_extension("json"))?.write_all(serialised.as_bytes())?;
     svg::save(outfile.with_extension("svg"), &final_state.as_svg())?;
 
     Ok(())
@@ -159,21 +799,50 @@ fn main(args: Args) -> Result<(), Error> {
 
     debug!("Logging Level: {}", log_level);
 
-    let wg = get_wallpaper_group(args.wallpaper)?;
+    let force = match (&args.potential, defines_potential(&args.shape)) {
+        (Some(force), _) => force,
+        (None, true) => &Force::LJ,
+        (None, false) => &Force::Hard,
+    };
+    let pair = force.pair_potential(&args.pair);
+    if let Some(potential) = pair {
+        potential.validate()?;
+    }
+    if args.pressure != 0. && pair.is_none() && !matches!(args.shape, Shapes::Patchy { .. }) {
+        bail!("A pressure can only be applied to shapes with a soft potential")
+    }
+    if matches!(args.polish.polish, Polish::FIRE)
+        && (pair.is_none() || matches!(args.shape, Shapes::Patchy { .. }))
+    {
+        bail!("Polishing with FIRE requires the gradient of a soft potential")
+    }
+
+    let options = RunOptions {
+        outfile: args.outfile,
+        replications: args.replications,
+        group: get_wallpaper_group(args.wallpaper)?,
+        racemic: args.racemic,
+        pressure: args.pressure,
+        // The potential of each site is only replaced when a potential is explicitly chosen
+        site_potential: args.potential.as_ref().and(pair),
+        pair: &args.pair,
+        hybrid: &args.hybrid,
+        optimisation: &args.optimisation,
+        polish: &args.polish,
+    };
+    let z_prime = args.z_prime;
 
-    match (args.shape, args.potential) {
+    match (args.shape, pair) {
         (
             Shapes::Trimer {
                 distance,
                 angle,
                 radius,
             },
-            Force::LJ,
-        ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PotentialState2::from_group(LJShape2::from_trimer(radius, angle, distance), &wg)?,
-            &args.optimisation,
+            Some(_),
+        ) => RunShape::run(
+            vec![(LJShape2::from_trimer(radius, angle, distance), z_prime)],
+            &options,
         ),
         (
             Shapes::Trimer {
@@ -181,33 +850,206 @@ fn main(args: Args) -> Result<(), Error> {
                 angle,
                 radius,
             },
-            Force::Hard,
-        ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(MolecularShape2::from_trimer(radius, angle, distance), &wg)?,
-            &args.optimisation,
+            None,
+        ) => RunShape::run(
+            vec![(
+                MolecularShape2::from_trimer(radius, angle, distance),
+                z_prime,
+            )],
+            &options,
         ),
-        (Shapes::Circle {}, Force::LJ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PotentialState2::from_group(LJShape2::circle(), &wg)?,
-            &args.optimisation,
+        (Shapes::Circle {}, Some(_)) => {
+            RunShape::run(vec![(LJShape2::circle(), z_prime)], &options)
+        }
+        (Shapes::Circle {}, None) => {
+            RunShape::run(vec![(MolecularShape2::circle(), z_prime)], &options)
+        }
+        (
+            Shapes::Disks {
+                radii,
+                count,
+                polydispersity,
+                size_seed,
+            },
+            potential,
+        ) => {
+            let distribution = match count {
+                Some(count) => SizeDistribution::Normal {
+                    count,
+                    mean: 1.,
+                    std: polydispersity,
+                },
+                None => SizeDistribution::Fixed(radii),
+            };
+            if z_prime != 1 {
+                bail!("Polydisperse disks don't support a z_prime other than 1");
+            }
+            let radii = distribution.sizes(&mut Pcg64Mcg::seed_from_u64(size_seed))?;
+            info!("Using circles with radii {:?}", radii);
+            // Save the radii in the format of --radii, so the same circles can be packed again
+            writeln!(
+                File::create(options.outfile.with_extension("radii"))?,
+                "{}",
+                radii.iter().format(",")
+            )?;
+            match potential {
+                None => RunShape::run(
+                    radii
+                        .iter()
+                        .map(|&r| (MolecularShape2::circle_with_radius(r), 1))
+                        .collect(),
+                    &options,
+                ),
+                Some(_) => RunShape::run(
+                    radii
+                        .iter()
+                        .map(|&r| (LJShape2::circle_with_sigma(2. * r), 1))
+                        .collect(),
+                    &options,
+                ),
+            }
+        }
+        (Shapes::Polygon { sides, .. }, None) => {
+            RunShape::run(vec![(LineShape::polygon(sides)?, z_prime)], &options)
+        }
+        (
+            Shapes::Polygon {
+                sides,
+                density,
+                sigma,
+            },
+            Some(_),
+        ) => RunShape::run(
+            vec![(LJShape2::polygon(sides, density, sigma)?, z_prime)],
+            &options,
         ),
-        (Shapes::Circle {}, Force::Hard) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(MolecularShape2::circle(), &wg)?,
-            &args.optimisation,
+        (Shapes::Spherocylinder { length, radius }, None) => RunShape::run(
+            vec![(RoundedShape::spherocylinder(length, radius)?, z_prime)],
+            &options,
         ),
-        (Shapes::Polygon { sides }, Force::Hard) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(LineShape::polygon(sides)?, &wg)?,
-            &args.optimisation,
+        (Shapes::RoundedPolygon { sides, radius }, None) => RunShape::run(
+            vec![(RoundedShape::rounded_polygon(sides, radius)?, z_prime)],
+            &options,
         ),
-        (Shapes::Polygon { .. }, Force::LJ) => {
-            bail!("Polygon with a LJ potential is not yet implemented")
+        (Shapes::Spherocylinder { .. }, Some(_)) | (Shapes::RoundedPolygon { .. }, Some(_)) => {
+            bail!(
+                "Rounded shapes with the {} potential are not yet implemented",
+                force
+            )
         }
+        (Shapes::File { path, kind }, potential) => {
+            if matches!(kind, ShapeKind::LJ) != potential.is_some() {
+                bail!(
+                    "A {} shape can't be used with the {} potential",
+                    kind,
+                    force
+                )
+            }
+            run_files(kind, &[path], &[], z_prime, &options)
+        }
+        (
+            Shapes::Tabulated {
+                table,
+                cutoff,
+                shape,
+            },
+            _,
+        ) => {
+            if let Some(force) = &args.potential {
+                bail!(
+                    "A tabulated potential can't be used with the {} potential, the potential \
+                     comes from the table",
+                    force
+                )
+            }
+            let potential = TabulatedPotential::from_file(&table, cutoff)?;
+            let shape = match shape {
+                Some(path) => {
+                    TabulatedShape2::from_lj_shape(&LJShape2::from_file(&path)?, potential)
+                }
+                None => TabulatedShape2::circle(potential),
+            };
+            RunShape::run(vec![(shape, z_prime)], &options)
+        }
+        (
+            Shapes::Patchy {
+                patches,
+                half_width,
+                range,
+                strength,
+            },
+            pair @ None,
+        )
+        | (
+            Shapes::Patchy {
+                patches,
+                half_width,
+                range,
+                strength,
+            },
+            pair @ Some(PairPotential::LennardJones),
+        ) => {
+            let core = match pair {
+                None => PatchyCore::Hard,
+                Some(_) => PatchyCore::LennardJones { epsilon: 1. },
+            };
+            let shape =
+                PatchyShape2::regular(core, patches, half_width.to_radians(), range, strength);
+            RunShape::run(vec![(shape, z_prime)], &options)
+        }
+        (Shapes::Patchy { .. }, Some(_)) => bail!(
+            "Patchy particles can only be used with the Hard or LJ potentials, not {}",
+            force
+        ),
+        (Shapes::Xyz { path }, None) => RunShape::run(
+            vec![(
+                XYZMolecule::from_file(&path)?.to_molecular_shape()?,
+                z_prime,
+            )],
+            &options,
+        ),
+        (Shapes::Xyz { path }, Some(_)) => RunShape::run(
+            vec![(XYZMolecule::from_file(&path)?.to_lj_shape()?, z_prime)],
+            &options,
+        ),
+        (
+            Shapes::Cocrystal {
+                paths,
+                stoichiometry,
+                kind,
+            },
+            potential,
+        ) => {
+            if matches!(kind, ShapeKind::LJ) != potential.is_some() {
+                bail!(
+                    "A {} shape can't be used with the {} potential",
+                    kind,
+                    force
+                )
+            }
+            run_files(kind, &paths, &stoichiometry, z_prime, &options)
+        }
+        (
+            Shapes::Svg {
+                path,
+                kind,
+                tolerance,
+            },
+            potential,
+        ) => match (kind, potential) {
+            (ShapeKind::Molecular, None) => RunShape::run(
+                vec![(MolecularShape2::from_svg_file(&path, tolerance)?, z_prime)],
+                &options,
+            ),
+            (ShapeKind::Line, None) => RunShape::run(
+                vec![(LineShape::from_svg_file(&path, tolerance)?, z_prime)],
+                &options,
+            ),
+            (kind, _) => bail!(
+                "A {} shape can't be read from an SVG file with the {} potential",
+                kind,
+                force
+            ),
+        },
     }
 }
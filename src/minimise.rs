@@ -0,0 +1,320 @@
+//
+// minimise.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use anyhow::{bail, Error};
+use log::{debug, warn};
+use structopt::StructOpt;
+
+use crate::traits::*;
+
+#[derive(StructOpt, Debug, Clone, Copy)]
+pub struct BuildMinimiser {
+    /// The maximum number of steps of the gradient based minimisation.
+    #[structopt(long, default_value = "10000")]
+    max_steps: u64,
+
+    /// The initial time step of the minimisation, which is adapted as the minimisation
+    /// progresses.
+    #[structopt(long, default_value = "0.001")]
+    timestep: f64,
+
+    /// The largest time step the minimisation is able to take.
+    #[structopt(long, default_value = "0.01")]
+    max_timestep: f64,
+
+    /// The minimisation has converged when the largest component of the gradient is smaller than
+    /// this value.
+    #[structopt(long, default_value = "1e-6")]
+    tolerance: f64,
+}
+
+impl Default for BuildMinimiser {
+    fn default() -> Self {
+        Self {
+            max_steps: 10000,
+            timestep: 0.001,
+            max_timestep: 0.01,
+            tolerance: 1e-6,
+        }
+    }
+}
+
+impl BuildMinimiser {
+    pub fn max_steps(&mut self, max_steps: u64) -> &mut Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn timestep(&mut self, timestep: f64) -> &mut Self {
+        self.timestep = timestep;
+        self
+    }
+
+    pub fn max_timestep(&mut self, max_timestep: f64) -> &mut Self {
+        self.max_timestep = max_timestep;
+        self
+    }
+
+    pub fn tolerance(&mut self, tolerance: f64) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn build(&self) -> FIREMinimiser {
+        FIREMinimiser {
+            steps: self.max_steps,
+            timestep: self.timestep,
+            max_timestep: f64::max(self.timestep, self.max_timestep),
+            tolerance: self.tolerance,
+        }
+    }
+}
+
+/// A deterministic local minimiser using the Fast Inertial Relaxation Engine (FIRE)
+///
+/// This follows the gradient of the score like a damped particle rolling downhill, with the
+/// velocity steered towards the direction of the gradient. It converges much faster than the
+/// Monte Carlo optimisation once the state is close to a minimum, making it well suited to
+/// polishing the final state. Each parameter is scaled by the range of values it can take, so
+/// the cell lengths and the fractional coordinates move at comparable rates.
+///
+pub struct FIREMinimiser {
+    steps: u64,
+    timestep: f64,
+    max_timestep: f64,
+    tolerance: f64,
+}
+
+impl FIREMinimiser {
+    // The standard parameters of the algorithm from Bitzek et al. (2006)
+    const DELAY_STEPS: u64 = 5;
+    const TIMESTEP_INCREASE: f64 = 1.1;
+    const TIMESTEP_DECREASE: f64 = 0.5;
+    const ALPHA_START: f64 = 0.1;
+    const ALPHA_DECREASE: f64 = 0.99;
+
+    /// The gradient along each parameter, with components pushing beyond a bound removed
+    fn projected_force(gradient: &[f64], values: &[f64], bounds: &[(f64, f64)]) -> Vec<f64> {
+        gradient
+            .iter()
+            .zip(values)
+            .zip(bounds)
+            .map(|((&g, &value), &(min, max))| {
+                let scaled = g * (max - min);
+                if (value <= min && scaled < 0.) || (value >= max && scaled > 0.) {
+                    0.
+                } else {
+                    scaled
+                }
+            })
+            .collect()
+    }
+
+    /// The largest component of the gradient of the score which isn't pushing beyond a bound
+    ///
+    /// The minimisation has converged when this is smaller than the tolerance.
+    ///
+    pub fn max_force<S: ScoreGradient>(state: &S) -> Result<f64, Error> {
+        let parameters = state.parameters();
+        let bounds: Vec<(f64, f64)> = (0..parameters.len())
+            .map(|i| parameters.bounds(i))
+            .collect();
+        let force = Self::projected_force(&state.score_gradient()?, parameters.values(), &bounds);
+        Ok(Self::largest(&force))
+    }
+
+    /// The largest magnitude of the components of the `force`
+    fn largest(force: &[f64]) -> f64 {
+        force.iter().fold(0., |acc: f64, f| acc.max(f.abs()))
+    }
+
+    /// Maximise the score of the state by following the gradient to the nearest maximum
+    ///
+    /// Every update of a parameter is clamped to the bounds of that parameter, with the velocity
+    /// along the parameter removed when it reaches a bound. This returns an error when the state
+    /// is unable to compute the gradient of the score. When the maximum number of steps is
+    /// reached before converging, a warning is logged with the largest remaining component of
+    /// the gradient, and the final state is returned.
+    ///
+    pub fn minimise_state<S: ScoreGradient>(&self, mut state: S) -> Result<S, Error> {
+        if state.score().is_none() {
+            bail!("Invalid configuration passed to the minimiser")
+        }
+        let mut parameters = state.parameters();
+        let bounds: Vec<(f64, f64)> = (0..parameters.len())
+            .map(|i| parameters.bounds(i))
+            .collect();
+        let mut values = parameters.values().to_vec();
+        let mut force = Self::projected_force(&state.score_gradient()?, &values, &bounds);
+
+        let mut velocity = vec![0.; parameters.len()];
+        let mut timestep = self.timestep;
+        let mut alpha = Self::ALPHA_START;
+        let mut steps_downhill = 0;
+
+        for step in 0..self.steps {
+            if Self::largest(&force) < self.tolerance {
+                debug!("Minimisation converged after {} steps", step);
+                break;
+            }
+
+            let power: f64 = force.iter().zip(&velocity).map(|(f, v)| f * v).sum();
+            if power >= 0. {
+                // Steer the velocity towards the direction of the force
+                let force_norm = force.iter().map(|f| f * f).sum::<f64>().sqrt();
+                let velocity_norm = velocity.iter().map(|v| v * v).sum::<f64>().sqrt();
+                for (v, f) in velocity.iter_mut().zip(&force) {
+                    *v = (1. - alpha) * *v + alpha * velocity_norm * f / force_norm;
+                }
+                steps_downhill += 1;
+                if steps_downhill > Self::DELAY_STEPS {
+                    timestep = f64::min(timestep * Self::TIMESTEP_INCREASE, self.max_timestep);
+                    alpha *= Self::ALPHA_DECREASE;
+                }
+            } else {
+                // We have gone past the minimum, so stop and start again more carefully
+                velocity.iter_mut().for_each(|v| *v = 0.);
+                timestep *= Self::TIMESTEP_DECREASE;
+                alpha = Self::ALPHA_START;
+                steps_downhill = 0;
+            }
+
+            let previous = values.clone();
+            for (i, &(min, max)) in bounds.iter().enumerate() {
+                velocity[i] += timestep * force[i];
+                parameters.set(i, values[i] + timestep * velocity[i] * (max - min));
+                values[i] = parameters.get(i);
+                // Hitting a bound removes any motion along that parameter
+                if values[i] <= min || values[i] >= max {
+                    velocity[i] = 0.;
+                }
+            }
+            state.set_parameters(&values);
+
+            let gradient = match state.score() {
+                Some(score) if score.is_finite() => state.score_gradient()?,
+                _ => Vec::new(),
+            };
+            if gradient.iter().all(|g| g.is_finite()) && !gradient.is_empty() {
+                force = Self::projected_force(&gradient, &values, &bounds);
+            } else {
+                // The step was too large, so undo it and try again with a smaller step
+                for (i, &value) in previous.iter().enumerate() {
+                    parameters.set(i, value);
+                }
+                state.set_parameters(&previous);
+                values = previous;
+                velocity.iter_mut().for_each(|v| *v = 0.);
+                timestep *= Self::TIMESTEP_DECREASE;
+                steps_downhill = 0;
+            }
+        }
+        if Self::largest(&force) >= self.tolerance {
+            warn!(
+                "Minimisation didn't converge within {} steps, the largest component of the \
+                 gradient is {:e} with a tolerance of {:e}",
+                self.steps,
+                Self::largest(&force),
+                self.tolerance
+            );
+        }
+        debug!("Score after minimisation: {:?}", state.score());
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{get_wallpaper_group, WallpaperGroups};
+    use crate::{Ewald, LJShape2, PotentialState2};
+
+    fn trimer_state() -> PotentialState2<LJShape2> {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let mut state =
+            PotentialState2::from_group(LJShape2::from_trimer(0.63, 120., 1.), &group).unwrap();
+        let mut parameters = state.parameters();
+        for (index, &value) in [7., 0.9, 1.3, 0.1, 0.2, 0.7].iter().enumerate() {
+            parameters.set(index, value);
+        }
+        state.set_parameters(parameters.values());
+        state
+    }
+
+    #[test]
+    fn score_improves() {
+        let state = trimer_state();
+        let init_score = state.score().unwrap();
+        let final_state = BuildMinimiser::default()
+            .build()
+            .minimise_state(state)
+            .unwrap();
+        assert!(final_state.score().unwrap() > init_score);
+    }
+
+    #[test]
+    fn respects_bounds() {
+        let state = trimer_state();
+        let final_state = BuildMinimiser::default()
+            .build()
+            .minimise_state(state.clone())
+            .unwrap();
+        let parameters = state.parameters();
+        for (index, &value) in final_state.parameters().values().iter().enumerate() {
+            let (min, max) = parameters.bounds(index);
+            assert!(min <= value && value <= max);
+        }
+    }
+
+    #[test]
+    fn converges_to_stationary_point() {
+        let state = trimer_state();
+        let first = BuildMinimiser::default()
+            .tolerance(1e-8)
+            .build()
+            .minimise_state(state)
+            .unwrap();
+        assert!(FIREMinimiser::max_force(&first).unwrap() < 1e-8);
+        // The gradient vanishes along all the parameters which aren't held at a bound
+        let parameters = first.parameters();
+        let bounds: Vec<_> = (0..parameters.len())
+            .map(|i| parameters.bounds(i))
+            .collect();
+        let force = FIREMinimiser::projected_force(
+            &first.score_gradient().unwrap(),
+            parameters.values(),
+            &bounds,
+        );
+        let norm = force.iter().map(|f| f * f).sum::<f64>().sqrt();
+        assert!(norm < 1e-6, "Projected gradient norm {} too large", norm);
+        let score = first.score().unwrap();
+        // Minimising a second time shouldn't find anything better
+        let second = BuildMinimiser::default()
+            .build()
+            .minimise_state(first)
+            .unwrap();
+        assert_abs_diff_eq!(second.score().unwrap(), score, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn max_steps_reached() {
+        let state = trimer_state();
+        let minimiser = BuildMinimiser::default().max_steps(2).build();
+        let final_state = minimiser.minimise_state(state).unwrap();
+        assert!(FIREMinimiser::max_force(&final_state).unwrap() > 1e-6);
+    }
+
+    #[test]
+    fn requires_gradient() {
+        let state = trimer_state().with_ewald(Ewald::default()).unwrap();
+        assert!(BuildMinimiser::default()
+            .build()
+            .minimise_state(state)
+            .is_err());
+    }
+}
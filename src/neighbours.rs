@@ -0,0 +1,284 @@
+//
+// neighbours.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use itertools::iproduct;
+use nalgebra::{Point2, Vector2};
+
+use crate::Cell2;
+
+/// The most periodic images searched along each cell vector
+///
+/// A cell which is very narrow compared to the range has a huge number of images within the
+/// range, so the search is limited to keep finding the neighbours fast. These cells are far
+/// from a reasonable packing, so missing the most distant images doesn't change the outcome.
+///
+const MAX_SEARCH_IMAGES: i64 = 50;
+
+/// A spatial binning of the shapes within a unit cell, used to find the nearby pairs
+///
+/// The unit cell is divided into bins along each of the cell vectors, with each bin at least
+/// as wide as the `range` of the interactions where that is possible. Finding the neighbours of
+/// a shape only needs to search the bins surrounding it, including the bins of the periodic
+/// images of the cell. When the cell is narrower than the range, the search extends over as
+/// many periodic images as needed to find every neighbour within the range, up to
+/// `MAX_SEARCH_IMAGES` images along each cell vector.
+///
+#[derive(Clone, Debug)]
+pub struct NeighbourList {
+    /// The cartesian position of each of the shapes, wrapped into the unit cell
+    positions: Vec<Point2<f64>>,
+    /// The periodic image of the unit cell each of the given positions was wrapped from
+    offsets: Vec<(i64, i64)>,
+    /// The bin along each of the cell vectors for each of the shapes
+    bin_index: Vec<(i64, i64)>,
+    /// The shapes within each bin, indexed by `a + b * num_bins.0`
+    bins: Vec<Vec<usize>>,
+    num_bins: (i64, i64),
+    /// The number of bins either side of a shape to search along each cell vector
+    search: (i64, i64),
+    /// The cell vectors, for finding the position of periodic images
+    vectors: (Vector2<f64>, Vector2<f64>),
+    range: f64,
+}
+
+impl NeighbourList {
+    /// Bin the `positions`, which are in fractional coordinates, within the `cell`
+    ///
+    /// The positions are able to take any value, with those outside the unit cell wrapped back
+    /// into the cell for the binning. The periodic images of the neighbours are relative to the
+    /// positions as they are given.
+    ///
+    pub fn new(cell: &Cell2, positions: &[Point2<f64>], range: f64) -> Self {
+        let vector_a = cell.to_cartesian_point(Point2::new(1., 0.)).coords;
+        let vector_b = cell.to_cartesian_point(Point2::new(0., 1.)).coords;
+        let area = cell.area();
+        // The perpendicular distance across the cell between the sides along each cell vector
+        let width_a = area / vector_b.norm();
+        let width_b = area / vector_a.norm();
+
+        // Limit the number of bins to the number of shapes, since additional bins will be empty
+        let max_bins = positions.len().max(1) as f64;
+        let num_bins = |width: f64| f64::floor(width / range).max(1.).min(max_bins) as i64;
+        let num_bins = (num_bins(width_a), num_bins(width_b));
+        let search = |width: f64, bins: i64| {
+            f64::ceil(range * bins as f64 / width).min((MAX_SEARCH_IMAGES * bins) as f64) as i64
+        };
+        let search = (search(width_a, num_bins.0), search(width_b, num_bins.1));
+
+        let mut list = Self {
+            positions: Vec::with_capacity(positions.len()),
+            offsets: Vec::with_capacity(positions.len()),
+            bin_index: Vec::with_capacity(positions.len()),
+            bins: vec![vec![]; (num_bins.0 * num_bins.1) as usize],
+            num_bins,
+            search,
+            vectors: (vector_a, vector_b),
+            range,
+        };
+        for (index, &position) in positions.iter().enumerate() {
+            let (offset, wrapped, bin) = list.locate(cell, position);
+            list.positions.push(wrapped);
+            list.offsets.push(offset);
+            list.bin_index.push(bin);
+            let current = list.bin(bin);
+            list.bins[current].push(index);
+        }
+        list
+    }
+
+    /// Move the shape at `index` to `position`, which is in fractional coordinates
+    ///
+    /// This gives the same neighbours as creating the list with the new position, provided the
+    /// `cell` is the same one the list was created with.
+    ///
+    pub fn update(&mut self, cell: &Cell2, index: usize, position: Point2<f64>) {
+        let (offset, wrapped, bin) = self.locate(cell, position);
+        let previous = self.bin(self.bin_index[index]);
+        if let Some(i) = self.bins[previous].iter().position(|&i| i == index) {
+            self.bins[previous].swap_remove(i);
+        }
+        let current = self.bin(bin);
+        self.bins[current].push(index);
+        self.positions[index] = wrapped;
+        self.offsets[index] = offset;
+        self.bin_index[index] = bin;
+    }
+
+    /// The periodic image, wrapped cartesian position and bin of a fractional `position`
+    fn locate(&self, cell: &Cell2, position: Point2<f64>) -> ((i64, i64), Point2<f64>, (i64, i64)) {
+        let offset = (position.x.floor() as i64, position.y.floor() as i64);
+        let wrapped = Point2::new(position.x - offset.0 as f64, position.y - offset.1 as f64);
+        let bin = (
+            ((wrapped.x * self.num_bins.0 as f64) as i64).min(self.num_bins.0 - 1),
+            ((wrapped.y * self.num_bins.1 as f64) as i64).min(self.num_bins.1 - 1),
+        );
+        (offset, cell.to_cartesian_point(wrapped), bin)
+    }
+
+    /// The index within `bins` of the bin `(a, b)`
+    fn bin(&self, (a, b): (i64, i64)) -> usize {
+        (a + b * self.num_bins.0) as usize
+    }
+
+    /// The number of shapes within the list
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The shapes which are closer than the range to the shape at `index`
+    ///
+    /// Each neighbour is the index of the shape along with the periodic image of that shape,
+    /// in units of the cell vectors. The shape itself is excluded, although periodic images of
+    /// the shape are included.
+    ///
+    pub fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, (i64, i64))> + '_ {
+        let (a, b) = self.bin_index[index];
+        let position = self.positions[index];
+        let range_squared = self.range.powi(2);
+        iproduct!(
+            -self.search.0..=self.search.0,
+            -self.search.1..=self.search.1
+        )
+        .flat_map(move |(da, db)| {
+            // Each offset is a unique combination of a bin and a periodic image
+            let (bin_a, bin_b) = (a + da, b + db);
+            let image = (
+                bin_a.div_euclid(self.num_bins.0),
+                bin_b.div_euclid(self.num_bins.1),
+            );
+            let bin = bin_a.rem_euclid(self.num_bins.0)
+                + bin_b.rem_euclid(self.num_bins.1) * self.num_bins.0;
+            self.bins[bin as usize]
+                .iter()
+                .map(move |&other| (other, image))
+        })
+        .filter(move |&(other, image)| {
+            if other == index && image == (0, 0) {
+                return false;
+            }
+            let translation = self.vectors.0 * image.0 as f64 + self.vectors.1 * image.1 as f64;
+            (self.positions[other] + translation - position).norm_squared() < range_squared
+        })
+        .map(move |(other, image)| {
+            // Convert the image to be relative to the positions before they were wrapped
+            let (offset, other_offset) = (self.offsets[index], self.offsets[other]);
+            (
+                other,
+                (
+                    image.0 + offset.0 - other_offset.0,
+                    image.1 + offset.1 - other_offset.1,
+                ),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::CrystalFamily;
+
+    /// All the neighbours from a brute force search of the periodic images
+    fn brute_force(
+        cell: &Cell2,
+        positions: &[Point2<f64>],
+        index: usize,
+        range: f64,
+    ) -> Vec<(usize, (i64, i64))> {
+        let mut neighbours = vec![];
+        for (other, p) in positions.iter().enumerate() {
+            for (x, y) in iproduct!(-20..=20, -20..=20) {
+                if other == index && x == 0 && y == 0 {
+                    continue;
+                }
+                let image = cell.to_cartesian_point(Point2::new(p.x + x as f64, p.y + y as f64));
+                if (image - cell.to_cartesian_point(positions[index])).norm() < range {
+                    neighbours.push((other, (x, y)));
+                }
+            }
+        }
+        neighbours.sort();
+        neighbours
+    }
+
+    fn positions() -> Vec<Point2<f64>> {
+        vec![
+            Point2::new(0.1, 0.2),
+            Point2::new(0.45, -0.3),
+            Point2::new(-0.2, 0.05),
+            Point2::new(0.9, 0.7),
+            Point2::new(0.3, 0.35),
+        ]
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut cell = Cell2::from_family(CrystalFamily::Monoclinic, 8.);
+        cell.set_parameters(&[8., 0.7, 1.1]);
+        for &range in &[0.5, 2., 3.5, 7., 15.] {
+            let list = NeighbourList::new(&cell, &positions(), range);
+            for index in 0..list.len() {
+                let mut neighbours: Vec<_> = list.neighbours(index).collect();
+                neighbours.sort();
+                assert_eq!(
+                    neighbours,
+                    brute_force(&cell, &positions(), index, range),
+                    "Mismatch for range {}",
+                    range
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn single_shape() {
+        let cell = Cell2::from_family(CrystalFamily::Tetragonal, 2.);
+        let list = NeighbourList::new(&cell, &[Point2::new(0., 0.)], 3.);
+        let mut neighbours: Vec<_> = list.neighbours(0).collect();
+        neighbours.sort();
+        // The four nearest and four diagonal images are within the range
+        assert_eq!(neighbours.len(), 8);
+        assert!(neighbours.iter().all(|&(index, _)| index == 0));
+    }
+
+    #[test]
+    fn narrow_cell() {
+        // Thousands of images are within the range along the narrow side of the cell
+        let mut cell = Cell2::from_family(CrystalFamily::Monoclinic, 8.);
+        cell.set_parameters(&[8., 0.001, PI / 2.]);
+        let list = NeighbourList::new(&cell, &[Point2::new(0., 0.)], 3.);
+        let mut neighbours: Vec<_> = list.neighbours(0).collect();
+        neighbours.sort();
+        let expected: Vec<_> = (-MAX_SEARCH_IMAGES..=MAX_SEARCH_IMAGES)
+            .filter(|&y| y != 0)
+            .map(|y| (0, (0, y)))
+            .collect();
+        assert_eq!(neighbours, expected);
+    }
+
+    #[test]
+    fn update_matches_new() {
+        let mut cell = Cell2::from_family(CrystalFamily::Monoclinic, 8.);
+        cell.set_parameters(&[8., 0.7, 1.1]);
+        let mut positions = positions();
+        let mut list = NeighbourList::new(&cell, &positions, 3.5);
+        for (index, position) in [(1, (0.6, 1.2)), (3, (-0.1, 0.4)), (1, (0.45, -0.3))].iter() {
+            positions[*index] = Point2::new(position.0, position.1);
+            list.update(&cell, *index, positions[*index]);
+            for index in 0..list.len() {
+                let mut neighbours: Vec<_> = list.neighbours(index).collect();
+                neighbours.sort();
+                assert_eq!(neighbours, brute_force(&cell, &positions, index, 3.5));
+            }
+        }
+    }
+}
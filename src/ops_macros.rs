@@ -12,6 +12,8 @@
 /// This converts a set of values and types to an impl block for a specific operation. In doing
 /// this it is possible to implement all the operations at once with the binop_impl_all macro.
 ///
+#[doc(hidden)]
+#[macro_export]
 macro_rules! _binop_impl(
     ($Op: ident, $op: ident;
      $lhs: ident: $Lhs: ty, $rhs: ident: $Rhs: ty, Output = $Output: ty;
@@ -41,21 +43,22 @@ macro_rules! _binop_impl(
 /// # Example
 ///
 /// ```
-/// # use crate::ops_macros::binop_impl_all;
+/// use std::ops::Mul;
+///
+/// use packing::binop_impl_all;
+///
 /// struct Wrapper(f64);
 ///
 /// binop_impl_all!(
 ///     Mul, mul;
 ///     self: Wrapper, rhs: f64, Output = f64;
-///     [ref ref] => self * rhs
-/// )
-///
+///     [ref ref] => self.0 * rhs;
+/// );
 ///
 /// let w = Wrapper(8.);
-/// w * 2.
-/// # assert!(w * 2. == 16.)
-///
+/// assert_eq!(w * 2., 16.);
 /// ```
+#[macro_export]
 macro_rules! binop_impl_all(
     // The trait we implementing, along with the function required to implement the trait
     ($Op: ident, $op: ident;
@@ -67,22 +70,22 @@ macro_rules! binop_impl_all(
      // form ref * ref, there is only a single implementation required.
      [ref ref] => $action_ref_ref: expr;) => {
         // This is the block where the transformation actually occurs.
-        _binop_impl!(
+        $crate::_binop_impl!(
             $Op, $op;
             $lhs: $Lhs, $rhs: $Rhs, Output = $Output;
             &$lhs * &$rhs; );
 
-        _binop_impl!(
+        $crate::_binop_impl!(
             $Op, $op;
             $lhs: &'a $Lhs, $rhs: $Rhs, Output = $Output;
             $lhs * &$rhs; 'a);
 
-        _binop_impl!(
+        $crate::_binop_impl!(
             $Op, $op;
             $lhs: $Lhs, $rhs: &'b $Rhs, Output = $Output;
             &$lhs * $rhs; 'b);
 
-        _binop_impl!(
+        $crate::_binop_impl!(
             $Op, $op;
             $lhs: &'a $Lhs, $rhs: &'b $Rhs, Output = $Output;
             $action_ref_ref; 'a, 'b);
@@ -11,6 +11,7 @@ use rand_pcg::Pcg64Mcg;
 use structopt::StructOpt;
 
 use crate::traits::*;
+use crate::Parameters;
 
 #[derive(StructOpt, Debug, Clone, Copy)]
 pub struct BuildOptimiser {
@@ -177,7 +178,27 @@ impl MCOptimiser {
         }
     }
 
-    pub fn optimise_state(&self, state: impl State) -> impl State {
+    pub fn optimise_state<S: State>(&self, state: S) -> S {
+        let mut parameters = state.parameters();
+        let mut step_ratio = 1.;
+        self.optimise_chain(state, &mut parameters, &mut step_ratio)
+    }
+
+    /// Optimise the state, keeping the bounds of the moves and their size between calls
+    ///
+    /// The values of the `parameters` are taken from the `state`, while the bounds are kept,
+    /// so a chain of moves continued from a different configuration still moves within the
+    /// original bounds. The `step_ratio` scales the size of the moves, and is updated as the
+    /// moves are accepted or rejected.
+    ///
+    pub(crate) fn optimise_chain<S: State>(
+        &self,
+        mut state: S,
+        parameters: &mut Parameters,
+        step_ratio: &mut f64,
+    ) -> S {
+        parameters.set_values(state.parameters().values());
+        state.set_parameters(parameters.values());
         let mut score_current = match state.score() {
             Some(score) => score,
             _ => panic!("Invalid configuration passed to function, exiting."),
@@ -188,40 +209,52 @@ impl MCOptimiser {
 
         let mut kt: f64 = self.kt_start;
 
-        let mut basis = state.generate_basis();
-        let basis_distribution = Uniform::new(0, basis.len() as usize);
+        let parameter_distribution = Uniform::new(0, parameters.len());
 
-        let mut step_ratio = 1.;
         let mut convergence_count = 0;
 
         for loop_counter in 1..=(self.steps / self.inner_steps) {
+            // Remove any accumulated error from updating the score with the changes
+            score_current = state.score().expect("The state has become invalid");
             let score_start = score_current;
             let mut loop_rejections: u64 = 0;
             for _ in 0..self.inner_steps {
-                // Choose a basis at random to modify
-                // This is needed later if we need to undo the change
-                let basis_index: usize = basis_distribution.sample(&mut rng);
-
-                // Make a random modification to the selected basis
-                basis
-                    .get_mut(basis_index)
-                    // There was some error in accessing the basis,
-                    // This should never occur in normal operation so panic and exit
-                    .expect("Trying to access basis which doesn't exist")
-                    .set_sampled(&mut rng, self.max_step_size * step_ratio);
+                // Choose a parameter at random to modify
+                let index: usize = parameter_distribution.sample(&mut rng);
+
+                // Where only part of the score depends on the parameter, the change in the score
+                // is found from the change of that part, rather than the complete score.
+                let contribution = state.parameter_score_before(index).flatten();
+
+                // Make a random modification to the selected parameter, keeping the previous
+                // value in case we need to undo the change
+                let previous =
+                    parameters.set_sampled(index, &mut rng, self.max_step_size * *step_ratio);
+                state.set_parameters(parameters.values());
+
+                let score_new = match contribution {
+                    Some(before) => match state
+                        .parameter_score(index)
+                        .expect("The contribution of the parameter is no longer separable")
+                    {
+                        Some(after) if before.is_finite() && after.is_finite() => {
+                            Some(score_current - before + after)
+                        }
+                        // An infinite contribution from overlapping shapes can't be subtracted
+                        // from the score, so the complete score is required.
+                        Some(_) => state.score(),
+                        None => None,
+                    },
+                    None => state.score(),
+                };
 
                 // Check if modification was good
-                score_current = match self.accept_score(state.score(), score_current, kt, &mut rng)
-                {
+                score_current = match self.accept_score(score_new, score_current, kt, &mut rng) {
                     Some(score) => score,
                     // Score was rejected so we have to undo the change
                     None => {
-                        basis
-                            .get(basis_index)
-                            // There was some error in accessing the basis,
-                            // This should never occur in normal operation so panic and exit
-                            .expect("Trying to access basis which doesn't exist.")
-                            .reset_value();
+                        parameters.set(index, previous);
+                        state.set_parameters(parameters.values());
                         // Increment counter of rejections
                         loop_rejections += 1;
                         score_current
@@ -255,8 +288,8 @@ impl MCOptimiser {
             // Taking shinking the cell as an example, 50% of steps will  increase the cell, so
             // we want 50% of the steps which can improve the performance to be accepted.
             // There is a limit to the usefulness though and 1e-4 has been good.
-            if step_ratio > 1e-4 {
-                step_ratio *= self.inner_steps as f64 / (loop_rejections as f64 + 1.);
+            if *step_ratio > 1e-4 {
+                *step_ratio *= self.inner_steps as f64 / (loop_rejections as f64 + 1.);
             }
         }
         debug!(
@@ -275,9 +308,15 @@ impl MCOptimiser {
 
 #[cfg(test)]
 mod test {
-    use super::*;
+    use std::cmp::Ordering;
+
+    use anyhow::Error;
     use approx::abs_diff_eq;
     use quickcheck_macros::quickcheck;
+    use serde::Serialize;
+    use svg::Document;
+
+    use super::*;
 
     static OPT: MCOptimiser = MCOptimiser {
         kt_start: 0.,
@@ -312,4 +351,75 @@ mod test {
             false
         }
     }
+
+    /// A single value with the best score at 0.5, which is invalid below zero like overlapping
+    /// shapes, and where the score is found entirely from the contribution of the parameter
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    struct Well {
+        x: f64,
+    }
+
+    impl Eq for Well {}
+
+    impl PartialOrd for Well {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Well {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.score().partial_cmp(&other.score()).unwrap()
+        }
+    }
+
+    impl ToSVG for Well {
+        type Value = Document;
+
+        fn as_svg(&self) -> Self::Value {
+            Document::new()
+        }
+    }
+
+    impl State for Well {
+        fn score(&self) -> Option<f64> {
+            if self.x < 0. {
+                Some(f64::NEG_INFINITY)
+            } else {
+                Some(-10. * (self.x - 0.5).abs())
+            }
+        }
+
+        fn parameters(&self) -> Parameters {
+            let mut parameters = Parameters::new();
+            parameters.push(self.x, -1., 1.);
+            parameters
+        }
+
+        fn set_parameters(&mut self, values: &[f64]) {
+            self.x = values[0];
+        }
+
+        fn total_shapes(&self) -> usize {
+            1
+        }
+
+        fn as_positions(&self) -> Result<String, Error> {
+            Ok(self.x.to_string())
+        }
+
+        fn parameter_score(&self, _index: usize) -> Option<Option<f64>> {
+            Some(self.score())
+        }
+    }
+
+    #[test]
+    fn escape_infinite_contribution() {
+        let state = BuildOptimiser::default()
+            .seed(0)
+            .build()
+            .optimise_state(Well { x: -0.001 });
+        assert!(state.score().unwrap().is_finite());
+        assert!(abs_diff_eq!(state.x, 0.5, epsilon = 0.05));
+    }
 }
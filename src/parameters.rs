@@ -0,0 +1,186 @@
+//
+// parameters.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The values which are able to be modified to change a state, along with their bounds
+///
+/// Each of the values is a degree of freedom of the state, like the length of the unit cell or
+/// the position of a site, in a flat vector. The state is rebuilt from the values using
+/// `State::set_parameters`, so modifying the values only changes a copy, and the values
+/// before a modification can be restored by keeping the previous value.
+///
+/// The bounds of each value are fixed when the parameters are created. Setting a value outside
+/// the bounds clamps it to the nearest bound.
+///
+/// ```
+/// use packing::Parameters;
+/// let mut parameters = Parameters::new();
+/// parameters.push(1., 0., 2.);
+///
+/// parameters.set(0, 1.5);
+/// assert_eq!(parameters.values(), &[1.5]);
+///
+/// // Values beyond the bounds are clamped
+/// parameters.set(0, 3.);
+/// assert_eq!(parameters.get(0), 2.);
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Parameters {
+    values: Vec<f64>,
+    bounds: Vec<(f64, f64)>,
+}
+
+impl Parameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value which can take values between `min` and `max`
+    pub fn push(&mut self, value: f64, min: f64, max: f64) {
+        self.values.push(value);
+        self.bounds.push((min, max));
+    }
+
+    /// Add all the values of `other` after the values of these parameters
+    pub fn append(&mut self, mut other: Parameters) {
+        self.values.append(&mut other.values);
+        self.bounds.append(&mut other.bounds);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn get(&self, index: usize) -> f64 {
+        self.values[index]
+    }
+
+    /// The smallest and largest values the parameter at `index` is able to take
+    pub fn bounds(&self, index: usize) -> (f64, f64) {
+        self.bounds[index]
+    }
+
+    /// Update the value at `index`, returning the previous value
+    ///
+    /// The new value is clamped to the bounds of the parameter.
+    ///
+    pub fn set(&mut self, index: usize, value: f64) -> f64 {
+        let (min, max) = self.bounds[index];
+        let value = match value {
+            x if x < min => min,
+            x if x > max => max,
+            x => x,
+        };
+        std::mem::replace(&mut self.values[index], value)
+    }
+
+    /// Update every value from `values`, which are in the same order as the parameters
+    ///
+    /// Each of the values is clamped to the bounds of the parameter, like `set`.
+    ///
+    pub fn set_values(&mut self, values: &[f64]) {
+        assert_eq!(values.len(), self.len(), "The number of values has changed");
+        for (index, &value) in values.iter().enumerate() {
+            self.set(index, value);
+        }
+    }
+
+    /// A random value near the current value at `index`
+    ///
+    /// The value is within `step_size` as a fraction of the range of the parameter, so the
+    /// parameters with a larger range take larger steps.
+    ///
+    pub fn sample<R: Rng + ?Sized>(&self, index: usize, rng: &mut R, step_size: f64) -> f64 {
+        let (min, max) = self.bounds[index];
+        self.values[index] + step_size * (max - min) * rng.gen_range(-0.5, 0.5)
+    }
+
+    /// Update the value at `index` to a random nearby value, returning the previous value
+    pub fn set_sampled<R: Rng + ?Sized>(
+        &mut self,
+        index: usize,
+        rng: &mut R,
+        step_size: f64,
+    ) -> f64 {
+        let value = self.sample(index, rng, step_size);
+        self.set(index, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+    use rand::thread_rng;
+
+    use super::*;
+
+    fn single(value: f64) -> Parameters {
+        let mut parameters = Parameters::new();
+        parameters.push(value, 0., 1.);
+        parameters
+    }
+
+    #[test]
+    fn set_value() {
+        let mut parameters = single(1.);
+        let previous = parameters.set(0, 0.5);
+        assert_abs_diff_eq!(previous, 1.);
+        assert_abs_diff_eq!(parameters.get(0), 0.5);
+    }
+
+    #[test]
+    fn set_value_limits() {
+        let mut parameters = single(1.);
+
+        // Over maximum value
+        parameters.set(0, 1.1);
+        assert_abs_diff_eq!(parameters.get(0), 1.);
+
+        // Less than minimum value
+        parameters.set(0, -0.1);
+        assert_abs_diff_eq!(parameters.get(0), 0.);
+    }
+
+    #[test]
+    fn reset_value() {
+        let mut parameters = single(1.);
+        let previous = parameters.set(0, 0.5);
+        parameters.set(0, previous);
+        assert_abs_diff_eq!(parameters.get(0), 1.);
+    }
+
+    #[test]
+    fn sample() {
+        let parameters = single(1.);
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let val = parameters.sample(0, &mut rng, 1.);
+            // Range of values which should be present
+            assert!((0.5..=1.5).contains(&val));
+        }
+    }
+
+    #[test]
+    fn append() {
+        let mut parameters = single(1.);
+        let mut other = Parameters::new();
+        other.push(2., 1., 3.);
+        parameters.append(other);
+        assert_eq!(parameters.values(), &[1., 2.]);
+        assert_eq!(parameters.bounds(1), (1., 3.));
+    }
+}
@@ -9,7 +9,7 @@ use std::fmt;
 use nalgebra::Point2;
 use serde::{Deserialize, Serialize};
 
-use crate::traits::Intersect;
+use crate::traits::{Intersect, Separation};
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Atom2 {
@@ -30,6 +30,17 @@ impl Intersect for Atom2 {
     }
 }
 
+impl Separation for Atom2 {
+    /// The distance between the surfaces of the two circles
+    ///
+    /// The penetration depth of two overlapping circles is the amount the distance between
+    /// their centres is less than the sum of their radii.
+    ///
+    fn separation(&self, other: &Self) -> f64 {
+        (self.position - other.position).norm() - self.radius - other.radius
+    }
+}
+
 impl fmt::Display for Atom2 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -71,6 +82,14 @@ mod test {
         assert!(a0.intersects(&a1));
     }
 
+    #[test]
+    fn separation() {
+        let a0 = Atom2::new(0., 0., 1.);
+        assert_abs_diff_eq!(a0.separation(&Atom2::new(3., 0., 0.5)), 1.5);
+        assert_abs_diff_eq!(a0.separation(&Atom2::new(0., 1.5, 1.)), -0.5);
+        assert_abs_diff_eq!(a0.separation(&Atom2::new(0., 2., 1.)), 0.);
+    }
+
     #[test]
     fn intersection_test() {
         let a0 = Atom2::new(0., 0., 1.);
@@ -89,7 +108,7 @@ mod test {
     fn intersection_calculation_test() {
         let a0 = Atom2::new(0., 0., f64::sqrt(2.) / 2.);
         let a1 = Atom2::new(1., 1., f64::sqrt(2.) / 2.);
-        let a2 = Atom2::new(1., 1., f64::sqrt(2.) / 2. - 2. * std::f64::EPSILON);
+        let a2 = Atom2::new(1., 1., f64::sqrt(2.) / 2. - 2. * f64::EPSILON);
         println!("Radii: {}", a0.radius * a0.radius + a1.radius * a1.radius);
         assert!(a0.intersects(&a1));
         assert!(a1.intersects(&a2));
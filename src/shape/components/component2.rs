@@ -0,0 +1,427 @@
+//
+// component2.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::iter;
+
+use nalgebra::{distance, Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{Atom2, Line2};
+use crate::traits::{Intersect, Shape};
+use crate::LineShape;
+
+/// A component of a shape which can take one of a number of geometric forms
+///
+/// This allows for the construction of shapes which combine different types of components, like a
+/// polygonal ring with circular substituents.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Component2 {
+    /// A circular disk
+    Disk(Atom2),
+    /// A line segment, which has no area
+    Segment(Line2),
+    /// A closed polygon, defined by the lines around the outside
+    Polygon(LineShape),
+}
+
+impl Intersect for Component2 {
+    /// Check whether two components intersect
+    ///
+    /// Components intersect when their boundaries overlap, or when one of the components is
+    /// completely contained within the area of the other.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Component2::Disk(a1), Component2::Disk(a2)) => a1.intersects(a2),
+            (Component2::Disk(a), Component2::Segment(l))
+            | (Component2::Segment(l), Component2::Disk(a)) => {
+                l.distance_to_point(&a.position) < a.radius
+            }
+            (Component2::Disk(a), Component2::Polygon(p))
+            | (Component2::Polygon(p), Component2::Disk(a)) => {
+                p.contains_point(&a.position)
+                    || p.iter()
+                        .any(|l| l.distance_to_point(&a.position) < a.radius)
+            }
+            (Component2::Segment(l1), Component2::Segment(l2)) => l1.intersects(l2),
+            (Component2::Segment(l), Component2::Polygon(p))
+            | (Component2::Polygon(p), Component2::Segment(l)) => {
+                p.contains_point(&l.start) || p.iter().any(|e| e.intersects(l))
+            }
+            (Component2::Polygon(p1), Component2::Polygon(p2)) => {
+                p1.intersects(p2)
+                    || p1.contains_point(&p2.items[0].start)
+                    || p2.contains_point(&p1.items[0].start)
+            }
+        }
+    }
+
+    fn area(&self) -> f64 {
+        match self {
+            Component2::Disk(a) => a.area(),
+            Component2::Segment(_) => 0.,
+            Component2::Polygon(p) => polygon_area(p),
+        }
+    }
+}
+
+impl fmt::Display for Component2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Component2::Disk(a) => write!(f, "{}", a),
+            Component2::Segment(l) => write!(f, "{}", l),
+            Component2::Polygon(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+impl Component2 {
+    /// The distance from the origin to the furthest point of the component
+    pub fn enclosing_radius(&self) -> f64 {
+        match self {
+            Component2::Disk(a) => distance(&Point2::origin(), &a.position) + a.radius,
+            Component2::Segment(l) => f64::max(
+                distance(&Point2::origin(), &l.start),
+                distance(&Point2::origin(), &l.end),
+            ),
+            Component2::Polygon(p) => p.enclosing_radius(),
+        }
+    }
+}
+
+/// The signed area enclosed by a polygon
+///
+/// This uses the shoelace formula, which is valid for any simple polygon. The result is positive
+/// when the points are ordered anti-clockwise.
+///
+fn signed_area(points: &[Point2<f64>]) -> f64 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(p1, p2)| p1.x * p2.y - p2.x * p1.y)
+        .sum::<f64>()
+        / 2.
+}
+
+fn polygon_area(polygon: &LineShape) -> f64 {
+    let points: Vec<_> = polygon.iter().map(|l| l.start).collect();
+    signed_area(&points).abs()
+}
+
+/// The distance within which a point is considered to be on the boundary of a component
+const BOUNDARY_TOLERANCE: f64 = 1e-9;
+
+/// The points of a polygon ordered anti-clockwise, so the area is on the left of each edge
+fn anticlockwise_points(polygon: &LineShape) -> Vec<Point2<f64>> {
+    let mut points: Vec<_> = polygon.iter().map(|l| l.start).collect();
+    if signed_area(&points) < 0. {
+        points.reverse();
+    }
+    points
+}
+
+/// The fractions along the segment from `p` to `q` where it crosses the circle
+fn segment_circle_crossings(
+    p: Point2<f64>,
+    q: Point2<f64>,
+    centre: Point2<f64>,
+    radius: f64,
+) -> Vec<f64> {
+    let d = q - p;
+    let f = p - centre;
+    let (qa, qb, qc) = (
+        d.norm_squared(),
+        2. * f.dot(&d),
+        f.norm_squared() - radius.powi(2),
+    );
+    let discriminant = qb * qb - 4. * qa * qc;
+    if qa == 0. || discriminant < 0. {
+        return vec![];
+    }
+    let sqrt_disc = discriminant.sqrt();
+    [(-qb - sqrt_disc) / (2. * qa), (-qb + sqrt_disc) / (2. * qa)]
+        .iter()
+        .cloned()
+        .filter(|t| 0. < *t && *t < 1.)
+        .collect()
+}
+
+/// The fraction along the segment from `p` to `q` where it crosses the segment from `a` to `b`
+fn segment_crossing(p: Point2<f64>, q: Point2<f64>, a: Point2<f64>, b: Point2<f64>) -> Option<f64> {
+    let (d, e) = (q - p, b - a);
+    let denominator = d.perp(&e);
+    if denominator == 0. {
+        return None;
+    }
+    let t = (a - p).perp(&e) / denominator;
+    let u = (a - p).perp(&d) / denominator;
+    if 0. < t && t < 1. && (0. ..=1.).contains(&u) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+impl Component2 {
+    /// Whether the point is on the boundary of the component
+    fn on_boundary(&self, point: &Point2<f64>) -> bool {
+        match self {
+            Component2::Disk(a) => {
+                (distance(&a.position, point) - a.radius).abs() < BOUNDARY_TOLERANCE
+            }
+            Component2::Segment(_) => false,
+            Component2::Polygon(p) => p
+                .iter()
+                .any(|l| l.distance_to_point(point) < BOUNDARY_TOLERANCE),
+        }
+    }
+
+    /// Whether the point is within the area of the component
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        match self {
+            Component2::Disk(a) => distance(&a.position, point) < a.radius,
+            Component2::Segment(_) => false,
+            Component2::Polygon(p) => p.contains_point(point),
+        }
+    }
+
+    /// The fractions along the segment from `p` to `q` where it crosses the boundary
+    fn segment_crossings(&self, p: Point2<f64>, q: Point2<f64>) -> Vec<f64> {
+        match self {
+            Component2::Disk(a) => segment_circle_crossings(p, q, a.position, a.radius),
+            Component2::Segment(_) => vec![],
+            Component2::Polygon(polygon) => polygon
+                .iter()
+                .filter_map(|l| segment_crossing(p, q, l.start, l.end))
+                .collect(),
+        }
+    }
+
+    /// The angles around the circle where it crosses the boundary
+    fn circle_crossings(&self, centre: Point2<f64>, radius: f64) -> Vec<f64> {
+        let angle = |point: Point2<f64>| f64::atan2(point.y - centre.y, point.x - centre.x);
+        match self {
+            Component2::Disk(a) => {
+                let d = distance(&centre, &a.position);
+                if d == 0. || d >= radius + a.radius || d <= (radius - a.radius).abs() {
+                    return vec![];
+                }
+                // The angle from the line between the centres to the crossings
+                let x = (d.powi(2) + radius.powi(2) - a.radius.powi(2)) / (2. * d);
+                let offset = f64::acos((x / radius).clamp(-1., 1.));
+                let direction = angle(a.position);
+                vec![direction - offset, direction + offset]
+            }
+            Component2::Segment(_) => vec![],
+            Component2::Polygon(polygon) => polygon
+                .iter()
+                .flat_map(|l| {
+                    segment_circle_crossings(l.start, l.end, centre, radius)
+                        .into_iter()
+                        .map(move |t| angle(l.start + (l.end - l.start) * t))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether a point on the boundary of the component at `index` is covered by another component
+///
+/// Where the boundaries of two components coincide, the boundary is only included from the
+/// component with the smaller index, so it is counted once.
+///
+fn boundary_covered(components: &[Component2], index: usize, point: &Point2<f64>) -> bool {
+    components.iter().enumerate().any(|(j, c)| {
+        if j == index {
+            false
+        } else if c.on_boundary(point) {
+            j < index
+        } else {
+            c.contains(point)
+        }
+    })
+}
+
+/// The total area covered by a collection of components
+///
+/// The area is found by integrating around the boundary of the union, using Green's theorem.
+/// The boundary of each component is split at every point it crosses the boundary of another
+/// component, with the pieces which are covered by another component removed. This is exact
+/// for any combination of overlapping disks and polygons, including concave polygons. Line
+/// segments have no area, so they don't contribute.
+///
+pub fn union_area(components: &[Component2]) -> f64 {
+    let mut area = 0.;
+    for (index, component) in components.iter().enumerate() {
+        let others = components
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != index)
+            .map(|(_, c)| c);
+        match component {
+            Component2::Disk(a) => {
+                let (centre, radius) = (a.position, a.radius);
+                let mut angles: Vec<f64> = others
+                    .flat_map(|c| c.circle_crossings(centre, radius))
+                    .map(|angle| angle.rem_euclid(2. * PI))
+                    .collect();
+                angles.sort_by(|a, b| a.total_cmp(b));
+                if angles.is_empty() {
+                    angles.push(0.);
+                }
+                let wrapped = angles[0] + 2. * PI;
+                let pieces = angles
+                    .iter()
+                    .zip(angles.iter().skip(1).chain(iter::once(&wrapped)));
+                for (&start, &end) in pieces {
+                    let middle = (start + end) / 2.;
+                    let point = centre + Vector2::new(middle.cos(), middle.sin()) * radius;
+                    if !boundary_covered(components, index, &point) {
+                        area += (radius.powi(2) * (end - start)
+                            + radius * centre.x * (end.sin() - start.sin())
+                            - radius * centre.y * (end.cos() - start.cos()))
+                            / 2.;
+                    }
+                }
+            }
+            Component2::Segment(_) => (),
+            Component2::Polygon(polygon) => {
+                let points = anticlockwise_points(polygon);
+                let others: Vec<_> = others.collect();
+                for (&p, &q) in points.iter().zip(points.iter().cycle().skip(1)) {
+                    let mut fractions: Vec<f64> = others
+                        .iter()
+                        .flat_map(|c| c.segment_crossings(p, q))
+                        .collect();
+                    fractions.push(0.);
+                    fractions.push(1.);
+                    fractions.sort_by(|a, b| a.total_cmp(b));
+                    for (&t1, &t2) in fractions.iter().zip(fractions.iter().skip(1)) {
+                        let (start, end) = (p + (q - p) * t1, p + (q - p) * t2);
+                        let middle = start + (end - start) / 2.;
+                        if !boundary_covered(components, index, &middle) {
+                            area += (start.x * end.y - end.x * start.y) / 2.;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    area
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn square() -> Component2 {
+        Component2::Polygon(LineShape::polygon(4).unwrap())
+    }
+
+    #[test]
+    fn disk_segment_intersection() {
+        let disk = Component2::Disk(Atom2::new(0., 0., 1.));
+        let close = Component2::Segment(Line2::new((-1., 0.5), (1., 0.5)));
+        let far = Component2::Segment(Line2::new((-1., 1.5), (1., 1.5)));
+        assert!(disk.intersects(&close));
+        assert!(close.intersects(&disk));
+        assert!(!disk.intersects(&far));
+    }
+
+    #[test]
+    fn disk_polygon_intersection() {
+        let square = square();
+        // Completely contained within the polygon
+        assert!(square.intersects(&Component2::Disk(Atom2::new(0., 0., 0.1))));
+        // Overlapping an edge
+        assert!(square.intersects(&Component2::Disk(Atom2::new(1., 1., 0.8))));
+        assert!(!square.intersects(&Component2::Disk(Atom2::new(1., 1., 0.6))));
+    }
+
+    #[test]
+    fn polygon_containment() {
+        let square = square();
+        let mut small = LineShape::polygon(4).unwrap();
+        small.items = small
+            .items
+            .iter()
+            .map(|l| Line2 {
+                start: l.start * 0.1,
+                end: l.end * 0.1,
+            })
+            .collect();
+        assert!(square.intersects(&Component2::Polygon(small)));
+    }
+
+    #[test]
+    fn union_area_three_disks() {
+        // Three unit disks centred on the vertices of an equilateral triangle with sides of 1
+        let height = f64::sqrt(3.) / 2.;
+        let components = vec![
+            Component2::Disk(Atom2::new(0., 0., 1.)),
+            Component2::Disk(Atom2::new(1., 0., 1.)),
+            Component2::Disk(Atom2::new(0.5, height, 1.)),
+        ];
+        // Each disk passes through the other two centres, so the three disks overlap in a
+        // Reuleaux triangle and the union has an area of 3 pi / 2 + sqrt(3)
+        assert_abs_diff_eq!(
+            union_area(&components),
+            1.5 * PI + f64::sqrt(3.),
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn union_area_concave_ring() {
+        // An L shaped polygon made from three unit squares, with a disk in the notch
+        let l_shape = LineShape {
+            name: String::from("L"),
+            items: vec![
+                Line2::new((0., 0.), (2., 0.)),
+                Line2::new((2., 0.), (2., 1.)),
+                Line2::new((2., 1.), (1., 1.)),
+                Line2::new((1., 1.), (1., 2.)),
+                Line2::new((1., 2.), (0., 2.)),
+                Line2::new((0., 2.), (0., 0.)),
+            ],
+        };
+        // The disk fills a quarter circle of the notch, and three quarters are outside
+        let components = vec![
+            Component2::Polygon(l_shape),
+            Component2::Disk(Atom2::new(1., 1., 0.5)),
+        ];
+        assert_abs_diff_eq!(
+            union_area(&components),
+            3. + 0.25 * PI * 0.25,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn union_area_identical() {
+        let components = vec![
+            square(),
+            square(),
+            Component2::Disk(Atom2::new(0., 0., 0.1)),
+        ];
+        assert_abs_diff_eq!(union_area(&components), 2., epsilon = 1e-10);
+    }
+
+    #[test]
+    fn union_area_pairs() {
+        let components = vec![
+            Component2::Disk(Atom2::new(0., 0., 1.)),
+            Component2::Disk(Atom2::new(3., 0., 1.)),
+            Component2::Segment(Line2::new((0., 0.), (3., 0.))),
+        ];
+        assert_abs_diff_eq!(union_area(&components), 2. * PI);
+    }
+}
@@ -0,0 +1,32 @@
+//
+// component2_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+use std::ops::Mul;
+
+use super::Component2;
+use crate::traits::Shape;
+use crate::Transform2;
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Component2, Output = Component2;
+    [ref ref] => {
+        match rhs {
+            Component2::Disk(a) => Component2::Disk(self * a),
+            Component2::Segment(l) => Component2::Segment(self * l),
+            Component2::Polygon(p) => Component2::Polygon(p.transform(self)),
+        }
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Component2, rhs: Transform2, Output = Component2;
+    [ref ref] => {
+        rhs * self
+    };
+);
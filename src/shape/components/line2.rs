@@ -11,7 +11,7 @@ use approx::AbsDiffEq;
 use nalgebra::Point2;
 use serde::{Deserialize, Serialize};
 
-use crate::traits::Intersect;
+use crate::traits::{Intersect, Separation};
 
 #[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Line2 {
@@ -47,7 +47,7 @@ impl Intersect for Line2 {
         let ua = ua_t / u_b;
         let ub = ub_t / u_b;
         // Should the points ua, ub both lie on the interval [0, 1] the lines intersect.
-        if 0. <= ua && ua <= 1. && 0. <= ub && ub <= 1. {
+        if (0. ..=1.).contains(&ua) && (0. ..=1.).contains(&ub) {
             return true;
         }
         false
@@ -59,12 +59,31 @@ impl Intersect for Line2 {
     }
 }
 
+impl Separation for Line2 {
+    /// The signed distance between two line segments
+    ///
+    /// When the segments cross, the smallest translation which separates them moves one of the
+    /// endpoints onto the other segment, so the penetration depth is the smallest distance from
+    /// an endpoint to the other segment.
+    ///
+    fn separation(&self, other: &Self) -> f64 {
+        if !self.intersects(other) {
+            return self.distance(other);
+        }
+        -self
+            .distance_to_point(&other.start)
+            .min(self.distance_to_point(&other.end))
+            .min(other.distance_to_point(&self.start))
+            .min(other.distance_to_point(&self.end))
+    }
+}
+
 #[cfg(test)]
 impl AbsDiffEq for Line2 {
     type Epsilon = f64;
 
     fn default_epsilon() -> Self::Epsilon {
-        std::f64::EPSILON
+        f64::EPSILON
     }
 
     fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
@@ -99,10 +118,66 @@ impl Line2 {
     pub fn dy(&self) -> f64 {
         self.end.y - self.start.y
     }
+
+    /// The length of the line segment
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).norm()
+    }
+
+    /// The point on the line segment which is closest to `point`
+    ///
+    /// This projects the point onto the infinite line, clamping the result to the endpoints of
+    /// the segment.
+    ///
+    pub fn closest_point(&self, point: &Point2<f64>) -> Point2<f64> {
+        let direction = self.end - self.start;
+        let length_squared = direction.norm_squared();
+        // A line with no length is just a point
+        if length_squared == 0. {
+            return self.start;
+        }
+        let t = (point - self.start).dot(&direction) / length_squared;
+        self.start + direction * t.clamp(0., 1.)
+    }
+
+    /// The shortest distance from a point to any point on the line segment
+    pub fn distance_to_point(&self, point: &Point2<f64>) -> f64 {
+        nalgebra::distance(&self.closest_point(point), point)
+    }
+
+    /// The shortest distance between two line segments
+    ///
+    /// When the segments cross the distance is 0, otherwise the closest approach of two segments
+    /// always involves at least one of the endpoints, so we only need to check each endpoint
+    /// against the other segment.
+    ///
+    pub fn distance(&self, other: &Self) -> f64 {
+        if self.intersects(other) {
+            return 0.;
+        }
+        self.distance_to_point(&other.start)
+            .min(self.distance_to_point(&other.end))
+            .min(other.distance_to_point(&self.start))
+            .min(other.distance_to_point(&self.end))
+    }
+
+    /// Whether a ray cast from `point` in the +x direction crosses this line
+    ///
+    /// Counting the crossings of the ray over all the edges of a closed polygon gives the
+    /// even-odd rule for determining whether the point is inside the polygon.
+    ///
+    pub fn crosses_ray(&self, point: &Point2<f64>) -> bool {
+        if (self.start.y > point.y) == (self.end.y > point.y) {
+            return false;
+        }
+        let x_crossing = self.start.x + (point.y - self.start.y) * self.dx() / self.dy();
+        point.x < x_crossing
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use approx::assert_abs_diff_eq;
     use itertools::iproduct;
 
     use super::*;
@@ -166,6 +241,54 @@ mod test {
     // |                   |                   |
     // +-------------------|-------------------+
     //
+    #[test]
+    fn length() {
+        let line = Line2::new((0., 0.), (3., 4.));
+        assert_abs_diff_eq!(line.length(), 5.);
+    }
+
+    #[test]
+    fn distance_to_point() {
+        let line = Line2::new((-1., 0.), (1., 0.));
+        // Perpendicular to the middle of the line
+        assert_abs_diff_eq!(line.distance_to_point(&Point2::new(0., 2.)), 2.);
+        // Beyond the end of the line the distance is to the endpoint
+        assert_abs_diff_eq!(line.distance_to_point(&Point2::new(4., 4.)), 5.);
+        assert_abs_diff_eq!(line.distance_to_point(&Point2::new(0.5, 0.)), 0.);
+    }
+
+    #[test]
+    fn distance() {
+        let line1 = Line2::new((-1., 0.), (1., 0.));
+        let line2 = Line2::new((0., 1.), (0., 3.));
+        assert_abs_diff_eq!(line1.distance(&line2), 1.);
+        assert_abs_diff_eq!(line2.distance(&line1), 1.);
+
+        // Crossing lines have no separation
+        let line3 = Line2::new((0., -1.), (0., 1.));
+        assert_abs_diff_eq!(line1.distance(&line3), 0.);
+
+        // Parallel lines
+        let line4 = Line2::new((-1., 0.5), (1., 0.5));
+        assert_abs_diff_eq!(line1.distance(&line4), 0.5);
+    }
+
+    #[test]
+    fn separation() {
+        let line1 = Line2::new((-1., 0.), (1., 0.));
+        assert_abs_diff_eq!(line1.separation(&Line2::new((0., 1.), (0., 3.))), 1.);
+        assert_abs_diff_eq!(line1.separation(&Line2::new((0.5, -1.), (0.5, 0.2))), -0.2);
+        assert_abs_diff_eq!(line1.separation(&Line2::new((0.8, -1.), (0.8, 1.))), -0.2);
+    }
+
+    #[test]
+    fn crosses_ray() {
+        let line = Line2::new((1., -1.), (1., 1.));
+        assert!(line.crosses_ray(&Point2::new(0., 0.)));
+        assert!(!line.crosses_ray(&Point2::new(2., 0.)));
+        assert!(!line.crosses_ray(&Point2::new(0., 2.)));
+    }
+
     #[test]
     fn intersects() {
         let line1 = Line2::new((-1., 0.), (0., -1.));
@@ -9,11 +9,13 @@ use std::fmt;
 use nalgebra::Point2;
 use serde::{Deserialize, Serialize};
 
+use super::{MixingRule, PairParameters, PairPotential, Truncation};
 use crate::traits::Potential;
 
 /// A particle which is influences by the Lennard Jones potential
 ///
-/// This defines interactions between particles usign the 12-6 Lennard Jones Potential.
+/// This defines interactions between particles using the 12-6 Lennard Jones Potential by
+/// default, with other functional forms able to be chosen through the `potential`.
 ///
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct LJ2 {
@@ -26,6 +28,22 @@ pub struct LJ2 {
     /// The cutoff for the potential. When this is Some, it indicates the use of the Shifted
     /// Lennard Jones potential.
     pub cutoff: Option<f64>,
+    /// How the potential is shifted to zero at the cutoff
+    #[serde(default)]
+    pub truncation: Truncation,
+    /// The functional form of the interaction with other particles
+    ///
+    /// Particles with different forms only interact through an entry of a `MixingRule::Table`
+    /// giving the form of the interaction.
+    ///
+    #[serde(default)]
+    pub potential: PairPotential,
+    /// The type of particle, used to look up interactions in a `MixingRule::Table`
+    #[serde(default)]
+    pub species: usize,
+    /// The partial charge of the particle
+    #[serde(default)]
+    pub charge: f64,
 }
 
 impl Default for LJ2 {
@@ -35,28 +53,25 @@ impl Default for LJ2 {
             sigma: 1.,
             epsilon: 1.,
             cutoff: None,
+            truncation: Truncation::default(),
+            potential: PairPotential::default(),
+            species: 0,
+            charge: 0.,
         }
     }
 }
 
 impl Potential for LJ2 {
+    /// The energy of the interaction between two particles
+    ///
+    /// Particles with different parameters are combined using the Lorentz-Berthelot mixing
+    /// rules, with $\sigma$ being the arithmetic mean and $\epsilon$ the geometric mean of the
+    /// two particles. The potential is truncated when either particle has a cutoff, using the
+    /// larger of the two when both do. Both particles need the same functional form of the
+    /// potential.
+    ///
     fn energy(&self, other: &Self) -> f64 {
-        let sigma_squared = self.sigma.powi(2);
-        let r_squared = (self.position - other.position).norm_squared();
-        let sigma2_r2_cubed = (sigma_squared / r_squared).powi(3);
-
-        match self.cutoff {
-            Some(x) => {
-                if r_squared < x * x {
-                    let shift =
-                        4. * self.epsilon * ((self.sigma / x).powi(12) - (self.sigma / x).powi(6));
-                    4. * self.epsilon * (sigma2_r2_cubed.powi(2) - sigma2_r2_cubed) - shift
-                } else {
-                    0.
-                }
-            }
-            None => 4. * self.epsilon * (sigma2_r2_cubed.powi(2) - sigma2_r2_cubed),
-        }
+        self.mixed_energy(other, &MixingRule::LorentzBerthelot)
     }
 }
 
@@ -78,12 +93,91 @@ impl LJ2 {
             ..Default::default()
         }
     }
+
+    /// The energy of the interaction between two particles, combined using the `mixing` rule
+    ///
+    /// # Panics
+    ///
+    /// When the two particles have a different functional form for the potential which isn't
+    /// given by the `mixing` rule. Shapes are checked for this when they are created.
+    ///
+    pub fn mixed_energy(&self, other: &Self, mixing: &MixingRule) -> f64 {
+        let PairParameters {
+            sigma,
+            epsilon,
+            cutoff,
+        } = mixing.parameters(self, other);
+        let r_squared = (self.position - other.position).norm_squared();
+        self.pair_potential(other, mixing).truncated_energy(
+            r_squared,
+            sigma,
+            epsilon,
+            cutoff,
+            self.pair_truncation(other),
+        )
+    }
+
+    /// The derivative of `mixed_energy` with respect to the distance between the particles
+    pub fn mixed_derivative(&self, other: &Self, mixing: &MixingRule) -> f64 {
+        let PairParameters {
+            sigma,
+            epsilon,
+            cutoff,
+        } = mixing.parameters(self, other);
+        let r_squared = (self.position - other.position).norm_squared();
+        self.pair_potential(other, mixing).truncated_derivative(
+            r_squared,
+            sigma,
+            epsilon,
+            cutoff,
+            self.pair_truncation(other),
+        )
+    }
+
+    /// The energy missing beyond the cutoff, per unit density of the `other` particles
+    ///
+    /// This is zero when the potential isn't truncated, since the complete potential is
+    /// already included in the energy.
+    ///
+    pub fn mixed_tail(&self, other: &Self, mixing: &MixingRule) -> f64 {
+        let PairParameters {
+            sigma,
+            epsilon,
+            cutoff,
+        } = mixing.parameters(self, other);
+        let cutoff = match cutoff {
+            Some(x) => x,
+            None => return 0.,
+        };
+        self.pair_potential(other, mixing)
+            .tail(cutoff, sigma, epsilon)
+    }
+
+    /// The functional form of the interaction with `other`
+    fn pair_potential(&self, other: &Self, mixing: &MixingRule) -> PairPotential {
+        mixing.potential(self, other).unwrap_or_else(|| {
+            panic!(
+                "No potential for the interaction between {:?} and {:?}",
+                self.potential, other.potential
+            )
+        })
+    }
+
+    /// The shifted force truncation is used when either of the particles uses it
+    fn pair_truncation(&self, other: &Self) -> Truncation {
+        match (self.truncation, other.truncation) {
+            (Truncation::Shifted, Truncation::Shifted) => Truncation::Shifted,
+            _ => Truncation::ShiftedForce,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use approx::assert_abs_diff_eq;
 
+    use crate::shape::PairEntry;
+
     use super::*;
 
     #[test]
@@ -183,4 +277,168 @@ mod test {
             assert!(a.energy(&b) < 0.);
         }
     }
+
+    #[test]
+    fn mixed_derivative() {
+        let a = LJ2 {
+            cutoff: Some(2.5),
+            potential: PairPotential::Morse { alpha: 3. },
+            ..LJ2::new(0., 0., 1.)
+        };
+        let b = LJ2 {
+            cutoff: Some(3.),
+            ..a.clone()
+        };
+        let b = LJ2 {
+            position: Point2::new(1.1, 0.2),
+            sigma: 1.2,
+            ..b
+        };
+        let h = 1e-6;
+        let shifted = |dx: f64| LJ2 {
+            position: b.position + (b.position - a.position).normalize() * dx,
+            ..b.clone()
+        };
+        let mixing = MixingRule::LorentzBerthelot;
+        let numerical = (a.mixed_energy(&shifted(h), &mixing)
+            - a.mixed_energy(&shifted(-h), &mixing))
+            / (2. * h);
+        assert_abs_diff_eq!(a.mixed_derivative(&b, &mixing), numerical, epsilon = 1e-6);
+        assert_abs_diff_eq!(a.mixed_derivative(&shifted(2.), &mixing), 0.);
+    }
+
+    #[test]
+    fn shifted_force_cutoff() {
+        let a = LJ2 {
+            cutoff: Some(2.5),
+            truncation: Truncation::ShiftedForce,
+            ..LJ2::new(0., 0., 1.)
+        };
+        let b = LJ2 {
+            cutoff: Some(2.5),
+            ..LJ2::new(2.5 - 1e-9, 0., 1.)
+        };
+        let mixing = MixingRule::LorentzBerthelot;
+        assert_abs_diff_eq!(a.mixed_energy(&b, &mixing), 0., epsilon = 1e-9);
+        assert_abs_diff_eq!(a.mixed_derivative(&b, &mixing), 0., epsilon = 1e-9);
+        // The energy shifted potential still has a force at the cutoff
+        let c = LJ2 {
+            truncation: Truncation::Shifted,
+            ..a.clone()
+        };
+        assert!(c.mixed_derivative(&b, &mixing) > 1e-3);
+    }
+
+    #[test]
+    fn mixed_tail() {
+        let a = LJ2 {
+            cutoff: Some(2.5),
+            ..LJ2::new(0., 0., 1.)
+        };
+        let mixing = MixingRule::LorentzBerthelot;
+        assert_abs_diff_eq!(
+            a.mixed_tail(&a, &mixing),
+            PairPotential::LennardJones.tail(2.5, 1., 1.)
+        );
+        assert!(a.mixed_tail(&a, &mixing) < 0.);
+        // Without a cutoff there is nothing missing
+        let b = LJ2::new(0., 0., 1.);
+        assert_abs_diff_eq!(b.mixed_tail(&b, &mixing), 0.);
+    }
+
+    #[test]
+    fn energy_symmetric() {
+        let a = LJ2 {
+            cutoff: Some(2.5),
+            potential: PairPotential::Morse { alpha: 4. },
+            ..LJ2::new(0., 0., 1.)
+        };
+        let b = LJ2 {
+            position: Point2::new(1.3, 0.4),
+            sigma: 0.6,
+            epsilon: 2.,
+            cutoff: Some(3.),
+            truncation: Truncation::ShiftedForce,
+            potential: PairPotential::Morse { alpha: 4. },
+            species: 1,
+            charge: 0.,
+        };
+        for mixing in &[MixingRule::LorentzBerthelot, MixingRule::Geometric] {
+            assert_abs_diff_eq!(a.mixed_energy(&b, mixing), b.mixed_energy(&a, mixing));
+        }
+        assert_abs_diff_eq!(a.energy(&b), b.energy(&a));
+    }
+
+    #[test]
+    fn pair_table_potential() {
+        let a = LJ2::new(0., 0., 1.);
+        let morse = PairPotential::Morse { alpha: 4. };
+        let b = LJ2 {
+            potential: morse,
+            species: 1,
+            ..LJ2::new(1.1, 0., 1.)
+        };
+        let parameters = MixingRule::LorentzBerthelot.parameters(&a, &b);
+        let table = MixingRule::Table(vec![PairEntry {
+            species: (0, 1),
+            parameters,
+            potential: Some(morse),
+        }]);
+        let energy = morse.truncated_energy(1.21, 1., 1., None, Truncation::Shifted);
+        assert_abs_diff_eq!(a.mixed_energy(&b, &table), energy);
+        assert_abs_diff_eq!(b.mixed_energy(&a, &table), energy);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mismatched_potential() {
+        let a = LJ2::new(0., 0., 1.);
+        let b = LJ2 {
+            potential: PairPotential::Morse { alpha: 4. },
+            ..LJ2::new(1.1, 0., 1.)
+        };
+        a.energy(&b);
+    }
+
+    #[test]
+    fn pair_table_cutoff() {
+        let a = LJ2::new(0., 0., 1.);
+        let b = LJ2 {
+            species: 1,
+            ..LJ2::new(2., 0., 1.)
+        };
+        let table = MixingRule::Table(vec![PairEntry {
+            species: (1, 0),
+            parameters: PairParameters {
+                sigma: 1.,
+                epsilon: 1.,
+                cutoff: Some(1.5),
+            },
+            potential: None,
+        }]);
+        // The particles have no cutoff of their own, with the cutoff from the table
+        assert!(a.mixed_energy(&b, &MixingRule::LorentzBerthelot) < 0.);
+        assert_abs_diff_eq!(a.mixed_energy(&b, &table), 0.);
+        assert_abs_diff_eq!(b.mixed_energy(&a, &table), 0.);
+    }
+
+    #[test]
+    fn mixed_parameters() {
+        let a = LJ2::new(0., 0., 1.);
+        let b = LJ2 {
+            position: Point2::new(1.5, 0.),
+            sigma: 2.,
+            epsilon: 4.,
+            ..Default::default()
+        };
+        assert_abs_diff_eq!(a.energy(&b), b.energy(&a));
+        // The mixed sigma is 1.5, where the potential is zero
+        assert_abs_diff_eq!(a.energy(&b), 0., epsilon = 1e-12);
+        // The minimum of the potential has the depth of the mixed epsilon
+        let c = LJ2 {
+            position: Point2::new(1.5 * 2_f64.powf(1. / 6.), 0.),
+            ..b
+        };
+        assert_abs_diff_eq!(a.energy(&c), -2., epsilon = 1e-12);
+    }
 }
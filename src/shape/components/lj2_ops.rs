@@ -18,7 +18,11 @@ binop_impl_all!(
             position: self * rhs.position,
             sigma: rhs.sigma,
             epsilon: rhs.epsilon,
-            cutoff: rhs.cutoff
+            cutoff: rhs.cutoff,
+            truncation: rhs.truncation,
+            potential: rhs.potential,
+            species: rhs.species,
+            charge: rhs.charge
         }
     };
 );
@@ -31,7 +35,11 @@ binop_impl_all!(
             position: rhs * self.position,
             sigma: self.sigma,
             epsilon: self.epsilon,
-            cutoff: self.cutoff
+            cutoff: self.cutoff,
+            truncation: self.truncation,
+            potential: self.potential,
+            species: self.species,
+            charge: self.charge
         }
     };
 );
@@ -0,0 +1,210 @@
+//
+// mixing.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use serde::{Deserialize, Serialize};
+
+use super::{PairPotential, LJ2};
+
+/// The parameters of the interaction between a pair of particles
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PairParameters {
+    /// The characteristic distance of the interaction
+    pub sigma: f64,
+    /// The energy scale of the interaction
+    pub epsilon: f64,
+    /// The distance at which the potential is truncated, with None having no truncation
+    pub cutoff: Option<f64>,
+}
+
+/// The interaction parameters between the particles of one species and another
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PairEntry {
+    pub species: (usize, usize),
+    pub parameters: PairParameters,
+    /// The functional form of the interaction, required when the two species use different
+    /// forms, and otherwise taken from the particles when missing.
+    #[serde(default)]
+    pub potential: Option<PairPotential>,
+}
+
+/// How the parameters of two different particles are combined for their interaction
+///
+/// For each of the rules the potential is truncated when either particle has a cutoff, taking the
+/// larger cutoff when both particles have one. All the
+/// rules give the same parameters regardless of the order of the particles.
+///
+/// Particles only interact through a single functional form of the potential. Two particles
+/// with different forms have no meaningful combination, so they can only interact when they are
+/// covered by an entry of a `MixingRule::Table` which gives the form of their interaction.
+///
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum MixingRule {
+    /// The arithmetic mean of sigma and the geometric mean of epsilon
+    #[default]
+    LorentzBerthelot,
+    /// The geometric mean of both sigma and epsilon
+    Geometric,
+    /// Parameters given explicitly for each pair of species
+    ///
+    /// Any pair of species which is not within the table uses the Lorentz-Berthelot rules.
+    Table(Vec<PairEntry>),
+}
+
+impl MixingRule {
+    /// The parameters of the interaction between the particles `a` and `b`
+    pub fn parameters(&self, a: &LJ2, b: &LJ2) -> PairParameters {
+        let cutoff = match (a.cutoff, b.cutoff) {
+            (Some(x), Some(y)) => Some(f64::max(x, y)),
+            (x, y) => x.or(y),
+        };
+        match self {
+            MixingRule::LorentzBerthelot => PairParameters {
+                sigma: (a.sigma + b.sigma) / 2.,
+                epsilon: (a.epsilon * b.epsilon).sqrt(),
+                cutoff,
+            },
+            MixingRule::Geometric => PairParameters {
+                sigma: (a.sigma * b.sigma).sqrt(),
+                epsilon: (a.epsilon * b.epsilon).sqrt(),
+                cutoff,
+            },
+            MixingRule::Table(_) => self
+                .entry(a, b)
+                .map(|e| e.parameters)
+                .unwrap_or_else(|| MixingRule::LorentzBerthelot.parameters(a, b)),
+        }
+    }
+
+    /// The functional form of the interaction between the particles `a` and `b`
+    ///
+    /// This is None when the particles have different forms without a table entry to choose the
+    /// form of their interaction.
+    ///
+    pub fn potential(&self, a: &LJ2, b: &LJ2) -> Option<PairPotential> {
+        match self.entry(a, b).and_then(|e| e.potential) {
+            Some(potential) => Some(potential),
+            None if a.potential == b.potential => Some(a.potential),
+            None => None,
+        }
+    }
+
+    /// The entry of the table for the species `a` and `b`
+    fn entry(&self, a: &LJ2, b: &LJ2) -> Option<&PairEntry> {
+        match self {
+            MixingRule::Table(entries) => entries.iter().find(|e| {
+                e.species == (a.species, b.species) || e.species == (b.species, a.species)
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn particles() -> (LJ2, LJ2) {
+        let a = LJ2 {
+            sigma: 1.,
+            epsilon: 1.,
+            cutoff: Some(2.5),
+            ..Default::default()
+        };
+        let b = LJ2 {
+            sigma: 4.,
+            epsilon: 4.,
+            cutoff: Some(3.),
+            species: 1,
+            ..Default::default()
+        };
+        (a, b)
+    }
+
+    #[test]
+    fn lorentz_berthelot() {
+        let (a, b) = particles();
+        let params = MixingRule::LorentzBerthelot.parameters(&a, &b);
+        assert_abs_diff_eq!(params.sigma, 2.5);
+        assert_abs_diff_eq!(params.epsilon, 2.);
+        assert_eq!(params.cutoff, Some(3.));
+        assert_eq!(params, MixingRule::LorentzBerthelot.parameters(&b, &a));
+    }
+
+    #[test]
+    fn geometric() {
+        let (a, b) = particles();
+        let params = MixingRule::Geometric.parameters(&a, &b);
+        assert_abs_diff_eq!(params.sigma, 2.);
+        assert_abs_diff_eq!(params.epsilon, 2.);
+        assert_eq!(params, MixingRule::Geometric.parameters(&b, &a));
+    }
+
+    #[test]
+    fn cutoff_from_either() {
+        let (a, b) = particles();
+        let b = LJ2 { cutoff: None, ..b };
+        assert_eq!(MixingRule::Geometric.parameters(&a, &b).cutoff, Some(2.5));
+        assert_eq!(MixingRule::Geometric.parameters(&b, &a).cutoff, Some(2.5));
+        let a = LJ2 { cutoff: None, ..a };
+        assert_eq!(MixingRule::Geometric.parameters(&a, &b).cutoff, None);
+    }
+
+    #[test]
+    fn table() {
+        let (a, b) = particles();
+        let parameters = PairParameters {
+            sigma: 1.2,
+            epsilon: 0.5,
+            cutoff: Some(5.),
+        };
+        let rule = MixingRule::Table(vec![PairEntry {
+            species: (0, 1),
+            parameters,
+            potential: None,
+        }]);
+        assert_eq!(rule.parameters(&a, &b), parameters);
+        assert_eq!(rule.parameters(&b, &a), parameters);
+        // Pairs missing from the table fall back to the Lorentz-Berthelot rules
+        assert_eq!(
+            rule.parameters(&a, &a),
+            MixingRule::LorentzBerthelot.parameters(&a, &a)
+        );
+    }
+
+    #[test]
+    fn potential_forms() {
+        let (a, b) = particles();
+        let morse = PairPotential::Morse { alpha: 3. };
+        let b = LJ2 {
+            potential: morse,
+            ..b
+        };
+        assert_eq!(
+            MixingRule::LorentzBerthelot.potential(&a, &a),
+            Some(a.potential)
+        );
+        // Different forms can't be combined without a table entry
+        assert_eq!(MixingRule::LorentzBerthelot.potential(&a, &b), None);
+        let parameters = MixingRule::LorentzBerthelot.parameters(&a, &b);
+        let rule = MixingRule::Table(vec![PairEntry {
+            species: (1, 0),
+            parameters,
+            potential: Some(morse),
+        }]);
+        assert_eq!(rule.potential(&a, &b), Some(morse));
+        assert_eq!(rule.potential(&b, &a), Some(morse));
+        assert_eq!(rule.potential(&b, &b), Some(morse));
+        // An entry without a form still requires the particles to share one
+        let rule = MixingRule::Table(vec![PairEntry {
+            species: (1, 0),
+            parameters,
+            potential: None,
+        }]);
+        assert_eq!(rule.potential(&a, &b), None);
+    }
+}
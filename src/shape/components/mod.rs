@@ -6,11 +6,24 @@
 
 pub mod atom2;
 pub mod atom2_ops;
+pub mod component2;
+pub mod component2_ops;
 pub mod line2;
 pub mod line2_ops;
 pub mod lj2;
 pub mod lj2_ops;
+pub mod mixing;
+pub mod pair_potential;
+pub mod patchy2;
+pub mod patchy2_ops;
+pub mod tabulated2;
+pub mod tabulated2_ops;
 
 pub use atom2::Atom2;
+pub use component2::{union_area, Component2};
 pub use line2::Line2;
 pub use lj2::LJ2;
+pub use mixing::{MixingRule, PairEntry, PairParameters};
+pub use pair_potential::{PairPotential, Truncation};
+pub use patchy2::{Patch, Patchy2, PatchyCore};
+pub use tabulated2::{Tabulated2, TabulatedPotential};
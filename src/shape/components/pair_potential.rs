@@ -0,0 +1,614 @@
+//
+// pair_potential.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+/// The functional form of the interaction between two particles
+///
+/// Each of the potentials is expressed in terms of a characteristic distance $\sigma$ and an
+/// energy $\epsilon$, which are taken from the interacting particles. Any additional parameters
+/// describing the shape of the potential are dimensionless, being in units of $\sigma$ where
+/// they describe a distance.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum PairPotential {
+    /// The 12-6 Lennard Jones potential, $4\epsilon[(\sigma/r)^{12} - (\sigma/r)^6]$
+    #[default]
+    LennardJones,
+    /// The generalised Lennard Jones potential with a `repulsive` exponent $n$ and an
+    /// `attractive` exponent $m$, normalised so the minimum has a depth of $\epsilon$
+    Mie { repulsive: f64, attractive: f64 },
+    /// The Weeks-Chandler-Andersen potential, the repulsive part of the Lennard Jones potential,
+    /// truncated at the minimum and shifted up by $\epsilon$
+    WCA,
+    /// The Morse potential with the minimum at $\sigma$ and a stiffness `alpha`,
+    /// $\epsilon[e^{-2\alpha(r/\sigma - 1)} - 2e^{-\alpha(r/\sigma - 1)}]$
+    Morse { alpha: f64 },
+    /// The Yukawa or screened Coulomb potential with an inverse screening length `kappa`,
+    /// $\epsilon (\sigma/r) e^{-\kappa(r/\sigma - 1)}$
+    Yukawa { kappa: f64 },
+    /// The Gaussian core potential, $\epsilon e^{-(r/\sigma)^2}$
+    GaussianCore,
+    /// A hard core of diameter $\sigma$ surrounded by an attractive well of depth $\epsilon$
+    /// extending to `width` $\sigma$
+    ///
+    /// The energy of overlapping cores is infinite, so a state containing an overlap has a score
+    /// of negative infinity, which is worse than that of any state without overlaps.
+    SquareWell { width: f64 },
+    /// A harmonic repulsion between overlapping particles, $\epsilon(1 - r/\sigma)^2$ for
+    /// $r < \sigma$
+    SoftHarmonic,
+}
+
+/// How a potential is modified so the energy goes to zero at the cutoff
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Truncation {
+    /// The energy is shifted by a constant, $U(r) - U(r_c)$, leaving a discontinuity in the
+    /// force at the cutoff
+    #[default]
+    Shifted,
+    /// Both the energy and the force go to zero at the cutoff,
+    /// $U(r) - U(r_c) - (r - r_c) U'(r_c)$
+    ShiftedForce,
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Truncation::Shifted => write!(f, "Shifted"),
+            Truncation::ShiftedForce => write!(f, "Shifted force"),
+        }
+    }
+}
+
+impl fmt::Display for PairPotential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairPotential::LennardJones => write!(f, "Lennard Jones"),
+            PairPotential::Mie {
+                repulsive,
+                attractive,
+            } => write!(f, "Mie {}-{}", repulsive, attractive),
+            PairPotential::WCA => write!(f, "WCA"),
+            PairPotential::Morse { alpha } => write!(f, "Morse alpha={}", alpha),
+            PairPotential::Yukawa { kappa } => write!(f, "Yukawa kappa={}", kappa),
+            PairPotential::GaussianCore => write!(f, "Gaussian core"),
+            PairPotential::SquareWell { width } => write!(f, "Square well width={}", width),
+            PairPotential::SoftHarmonic => write!(f, "Soft harmonic"),
+        }
+    }
+}
+
+/// Ensure a parameter of a potential is finite and greater than `minimum`
+fn check_greater(value: f64, minimum: f64, name: &str) -> Result<(), Error> {
+    if value.is_finite() && value > minimum {
+        Ok(())
+    } else {
+        bail!(
+            "The {} has to be greater than {}, found {}",
+            name,
+            minimum,
+            value
+        )
+    }
+}
+
+impl PairPotential {
+    /// Check the parameters describe a physically meaningful potential
+    ///
+    /// The Mie potential requires the repulsive exponent to be larger than the attractive
+    /// exponent, otherwise the normalisation is undefined. The stiffness of the Morse potential
+    /// and the screening of the Yukawa potential have to be positive, and the well of the
+    /// square well potential has to extend beyond the hard core.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        match *self {
+            PairPotential::Mie {
+                repulsive,
+                attractive,
+            } => {
+                check_greater(attractive, 0., "attractive exponent")?;
+                check_greater(repulsive, attractive, "repulsive exponent")
+            }
+            PairPotential::Morse { alpha } => check_greater(alpha, 0., "alpha"),
+            PairPotential::Yukawa { kappa } => check_greater(kappa, 0., "kappa"),
+            PairPotential::SquareWell { width } => check_greater(width, 1., "width"),
+            _ => Ok(()),
+        }
+    }
+
+    /// The energy of two particles separated by a squared distance `r_squared`
+    ///
+    /// This is the complete potential, without any truncation. The square of the distance is
+    /// used since it avoids taking a square root for the Lennard Jones potential.
+    ///
+    pub fn energy(&self, r_squared: f64, sigma: f64, epsilon: f64) -> f64 {
+        match *self {
+            PairPotential::LennardJones => {
+                let sigma2_r2_cubed = (sigma.powi(2) / r_squared).powi(3);
+                4. * epsilon * (sigma2_r2_cubed.powi(2) - sigma2_r2_cubed)
+            }
+            PairPotential::Mie {
+                repulsive: n,
+                attractive: m,
+            } => {
+                let prefactor = n / (n - m) * (n / m).powf(m / (n - m));
+                let sigma_r = sigma / r_squared.sqrt();
+                prefactor * epsilon * (sigma_r.powf(n) - sigma_r.powf(m))
+            }
+            PairPotential::WCA => {
+                if r_squared < 2_f64.powf(1. / 3.) * sigma.powi(2) {
+                    PairPotential::LennardJones.energy(r_squared, sigma, epsilon) + epsilon
+                } else {
+                    0.
+                }
+            }
+            PairPotential::Morse { alpha } => {
+                let decay = f64::exp(-alpha * (r_squared.sqrt() / sigma - 1.));
+                epsilon * (decay.powi(2) - 2. * decay)
+            }
+            PairPotential::Yukawa { kappa } => {
+                let r = r_squared.sqrt();
+                epsilon * sigma / r * f64::exp(-kappa * (r / sigma - 1.))
+            }
+            PairPotential::GaussianCore => epsilon * f64::exp(-r_squared / sigma.powi(2)),
+            PairPotential::SquareWell { width } => {
+                if r_squared < sigma.powi(2) {
+                    f64::INFINITY
+                } else if r_squared < (width * sigma).powi(2) {
+                    -epsilon
+                } else {
+                    0.
+                }
+            }
+            PairPotential::SoftHarmonic => {
+                if r_squared < sigma.powi(2) {
+                    epsilon * (1. - r_squared.sqrt() / sigma).powi(2)
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+
+    /// The derivative of the energy with respect to the distance between the particles
+    ///
+    /// The square well is discontinuous, so the derivative is taken as zero everywhere.
+    ///
+    pub fn derivative(&self, r_squared: f64, sigma: f64, epsilon: f64) -> f64 {
+        let r = r_squared.sqrt();
+        match *self {
+            PairPotential::LennardJones => {
+                let sigma2_r2_cubed = (sigma.powi(2) / r_squared).powi(3);
+                24. * epsilon / r * (sigma2_r2_cubed - 2. * sigma2_r2_cubed.powi(2))
+            }
+            PairPotential::Mie {
+                repulsive: n,
+                attractive: m,
+            } => {
+                let prefactor = n / (n - m) * (n / m).powf(m / (n - m));
+                let sigma_r = sigma / r;
+                prefactor * epsilon * (m * sigma_r.powf(m) - n * sigma_r.powf(n)) / r
+            }
+            PairPotential::WCA => {
+                if r_squared < 2_f64.powf(1. / 3.) * sigma.powi(2) {
+                    PairPotential::LennardJones.derivative(r_squared, sigma, epsilon)
+                } else {
+                    0.
+                }
+            }
+            PairPotential::Morse { alpha } => {
+                let decay = f64::exp(-alpha * (r / sigma - 1.));
+                2. * epsilon * alpha / sigma * (decay - decay.powi(2))
+            }
+            PairPotential::Yukawa { kappa } => {
+                -self.energy(r_squared, sigma, epsilon) * (1. / r + kappa / sigma)
+            }
+            PairPotential::GaussianCore => {
+                -2. * r / sigma.powi(2) * self.energy(r_squared, sigma, epsilon)
+            }
+            PairPotential::SquareWell { .. } => 0.,
+            PairPotential::SoftHarmonic => {
+                if r < sigma {
+                    -2. * epsilon / sigma * (1. - r / sigma)
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+
+    /// The diameter of the hard core, within which the energy is infinite
+    fn hard_core(&self, sigma: f64) -> Option<f64> {
+        match self {
+            PairPotential::SquareWell { .. } => Some(sigma),
+            _ => None,
+        }
+    }
+
+    /// The energy of two particles, truncated at the `cutoff` using the `truncation` scheme
+    ///
+    /// Beyond the cutoff the energy is zero, while there is no truncation when the cutoff is
+    /// None. A hard core is never truncated, so overlapping cores have an infinite energy even
+    /// when the cutoff lies within the core.
+    ///
+    pub fn truncated_energy(
+        &self,
+        r_squared: f64,
+        sigma: f64,
+        epsilon: f64,
+        cutoff: Option<f64>,
+        truncation: Truncation,
+    ) -> f64 {
+        // Beyond the core the energy at the cutoff is finite, so the shift is well defined
+        if let Some(core) = self.hard_core(sigma) {
+            if r_squared < core.powi(2) {
+                return f64::INFINITY;
+            }
+        }
+        match cutoff {
+            Some(x) if r_squared < x * x => {
+                let shifted =
+                    self.energy(r_squared, sigma, epsilon) - self.energy(x * x, sigma, epsilon);
+                match truncation {
+                    Truncation::Shifted => shifted,
+                    Truncation::ShiftedForce => {
+                        shifted - (r_squared.sqrt() - x) * self.derivative(x * x, sigma, epsilon)
+                    }
+                }
+            }
+            Some(_) => 0.,
+            None => self.energy(r_squared, sigma, epsilon),
+        }
+    }
+
+    /// The derivative of `truncated_energy` with respect to the distance between the particles
+    pub fn truncated_derivative(
+        &self,
+        r_squared: f64,
+        sigma: f64,
+        epsilon: f64,
+        cutoff: Option<f64>,
+        truncation: Truncation,
+    ) -> f64 {
+        match cutoff {
+            Some(x) if r_squared < x * x => match truncation {
+                Truncation::Shifted => self.derivative(r_squared, sigma, epsilon),
+                Truncation::ShiftedForce => {
+                    self.derivative(r_squared, sigma, epsilon)
+                        - self.derivative(x * x, sigma, epsilon)
+                }
+            },
+            Some(_) => 0.,
+            None => self.derivative(r_squared, sigma, epsilon),
+        }
+    }
+
+    /// The integral of the energy over the plane beyond the `cutoff`
+    ///
+    /// This is $\int_{r_c}^\infty 2 \pi r U(r) dr$, the energy a particle is missing from the
+    /// interactions with a uniform density of other particles beyond the cutoff, per unit of
+    /// density. The integral diverges for potentials decaying as $r^{-2}$ or slower, giving
+    /// an infinite value.
+    ///
+    pub fn tail(&self, cutoff: f64, sigma: f64, epsilon: f64) -> f64 {
+        use std::f64::consts::PI;
+
+        // The integral of the power law $(\sigma / r)^n$ multiplied by $2 \pi r$
+        let power_tail = |n: f64| {
+            if n > 2. {
+                2. * PI * sigma.powf(n) * cutoff.powf(2. - n) / (n - 2.)
+            } else {
+                f64::INFINITY
+            }
+        };
+        // The integral of $e^{-k r}$ multiplied by $2 \pi r$
+        let exponential_tail =
+            |k: f64| 2. * PI * f64::exp(-k * cutoff) * (cutoff / k + 1. / k.powi(2));
+
+        match *self {
+            PairPotential::LennardJones => 4. * epsilon * (power_tail(12.) - power_tail(6.)),
+            PairPotential::Mie {
+                repulsive: n,
+                attractive: m,
+            } => {
+                let prefactor = n / (n - m) * (n / m).powf(m / (n - m));
+                prefactor * epsilon * (power_tail(n) - power_tail(m))
+            }
+            PairPotential::WCA => {
+                let r_min = 2_f64.powf(1. / 6.) * sigma;
+                if cutoff < r_min {
+                    PairPotential::LennardJones.tail(cutoff, sigma, epsilon)
+                        - PairPotential::LennardJones.tail(r_min, sigma, epsilon)
+                        + PI * epsilon * (r_min.powi(2) - cutoff.powi(2))
+                } else {
+                    0.
+                }
+            }
+            PairPotential::Morse { alpha } => {
+                epsilon
+                    * (f64::exp(2. * alpha) * exponential_tail(2. * alpha / sigma)
+                        - 2. * f64::exp(alpha) * exponential_tail(alpha / sigma))
+            }
+            PairPotential::Yukawa { kappa } => {
+                if kappa > 0. {
+                    2. * PI * epsilon * sigma.powi(2) / kappa
+                        * f64::exp(-kappa * (cutoff / sigma - 1.))
+                } else {
+                    f64::INFINITY
+                }
+            }
+            PairPotential::GaussianCore => {
+                PI * epsilon * sigma.powi(2) * f64::exp(-(cutoff / sigma).powi(2))
+            }
+            PairPotential::SquareWell { width } => {
+                let outer = width * sigma;
+                if cutoff < outer {
+                    -PI * epsilon * (outer.powi(2) - cutoff.max(sigma).powi(2))
+                } else {
+                    0.
+                }
+            }
+            PairPotential::SoftHarmonic => {
+                // The antiderivative of $x(1 - x)^2$ with $x = r / \sigma$
+                let antiderivative = |x: f64| x.powi(2) / 2. - 2. * x.powi(3) / 3. + x.powi(4) / 4.;
+                let x = cutoff / sigma;
+                if x < 1. {
+                    2. * PI * epsilon * sigma.powi(2) * (antiderivative(1.) - antiderivative(x))
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn energy(potential: PairPotential, r: f64) -> f64 {
+        potential.energy(r * r, 1., 1.)
+    }
+
+    #[test]
+    fn validate() {
+        let mie = |repulsive, attractive| PairPotential::Mie {
+            repulsive,
+            attractive,
+        };
+        assert!(mie(12., 6.).validate().is_ok());
+        assert!(mie(6., 6.).validate().is_err());
+        assert!(mie(6., 12.).validate().is_err());
+        assert!(mie(12., 0.).validate().is_err());
+        assert!(mie(f64::NAN, 6.).validate().is_err());
+        assert!(PairPotential::Morse { alpha: 0. }.validate().is_err());
+        assert!(PairPotential::Yukawa { kappa: -1. }.validate().is_err());
+        assert!(PairPotential::SquareWell { width: 1. }.validate().is_err());
+        assert!(PairPotential::SquareWell { width: 1.5 }.validate().is_ok());
+        assert!(PairPotential::LennardJones.validate().is_ok());
+    }
+
+    #[test]
+    fn square_well_cutoff_within_core() {
+        let potential = PairPotential::SquareWell { width: 1.5 };
+        for &truncation in &[Truncation::Shifted, Truncation::ShiftedForce] {
+            let energy = |r: f64| potential.truncated_energy(r * r, 1., 1., Some(0.8), truncation);
+            assert_eq!(energy(0.5), f64::INFINITY);
+            assert_eq!(energy(0.9), f64::INFINITY);
+            assert_eq!(energy(1.2), 0.);
+        }
+    }
+
+    #[test]
+    fn square_well_cutoff_within_well() {
+        let potential = PairPotential::SquareWell { width: 1.5 };
+        let energy =
+            |r: f64| potential.truncated_energy(r * r, 1., 1., Some(1.2), Truncation::Shifted);
+        assert_eq!(energy(0.9), f64::INFINITY);
+        assert_abs_diff_eq!(energy(1.1), 0.);
+        assert_abs_diff_eq!(energy(1.3), 0.);
+    }
+
+    #[test]
+    fn lennard_jones_minimum() {
+        let potential = PairPotential::LennardJones;
+        assert_abs_diff_eq!(energy(potential, 1.), 0.);
+        assert_abs_diff_eq!(energy(potential, 2_f64.powf(1. / 6.)), -1., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn mie_12_6_is_lennard_jones() {
+        let mie = PairPotential::Mie {
+            repulsive: 12.,
+            attractive: 6.,
+        };
+        for i in 1..300 {
+            let r = 0.9 + i as f64 / 100.;
+            assert_abs_diff_eq!(
+                energy(mie, r),
+                energy(PairPotential::LennardJones, r),
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn mie_minimum() {
+        let (n, m) = (14., 7.);
+        let mie = PairPotential::Mie {
+            repulsive: n,
+            attractive: m,
+        };
+        let r_min = (n / m).powf(1. / (n - m));
+        assert_abs_diff_eq!(energy(mie, r_min), -1., epsilon = 1e-12);
+        assert_abs_diff_eq!(energy(mie, 1.), 0., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn wca_purely_repulsive() {
+        let potential = PairPotential::WCA;
+        assert_abs_diff_eq!(energy(potential, 2_f64.powf(1. / 6.)), 0., epsilon = 1e-12);
+        assert_abs_diff_eq!(energy(potential, 1.5), 0.);
+        assert_abs_diff_eq!(energy(potential, 1.), 1., epsilon = 1e-12);
+        for i in 1..200 {
+            assert!(energy(potential, 0.5 + i as f64 / 100.) >= 0.);
+        }
+    }
+
+    #[test]
+    fn morse_minimum() {
+        let potential = PairPotential::Morse { alpha: 6. };
+        assert_abs_diff_eq!(energy(potential, 1.), -1.);
+        assert!(energy(potential, 0.99) > -1.);
+        assert!(energy(potential, 1.01) > -1.);
+        assert_abs_diff_eq!(energy(potential, 10.), 0., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn yukawa_contact() {
+        let potential = PairPotential::Yukawa { kappa: 2. };
+        assert_abs_diff_eq!(energy(potential, 1.), 1.);
+        assert_abs_diff_eq!(energy(potential, 2.), 0.5 * f64::exp(-2.));
+    }
+
+    #[test]
+    fn gaussian_core_finite() {
+        let potential = PairPotential::GaussianCore;
+        assert_abs_diff_eq!(energy(potential, 0.), 1.);
+        assert_abs_diff_eq!(energy(potential, 1.), f64::exp(-1.));
+    }
+
+    #[test]
+    fn square_well_regions() {
+        let potential = PairPotential::SquareWell { width: 1.5 };
+        assert!(energy(potential, 0.99).is_infinite());
+        assert_abs_diff_eq!(energy(potential, 1.2), -1.);
+        assert_abs_diff_eq!(energy(potential, 1.6), 0.);
+    }
+
+    #[test]
+    fn soft_harmonic_overlap() {
+        let potential = PairPotential::SoftHarmonic;
+        assert_abs_diff_eq!(energy(potential, 0.), 1.);
+        assert_abs_diff_eq!(energy(potential, 0.5), 0.25);
+        assert_abs_diff_eq!(energy(potential, 1.), 0.);
+        assert_abs_diff_eq!(energy(potential, 2.), 0.);
+    }
+
+    #[test]
+    fn derivative_finite_difference() {
+        let potentials = [
+            PairPotential::LennardJones,
+            PairPotential::Mie {
+                repulsive: 14.,
+                attractive: 7.,
+            },
+            PairPotential::WCA,
+            PairPotential::Morse { alpha: 6. },
+            PairPotential::Yukawa { kappa: 2. },
+            PairPotential::GaussianCore,
+            PairPotential::SoftHarmonic,
+        ];
+        let h = 1e-6;
+        for potential in potentials.iter() {
+            for i in 0..50 {
+                let r = 0.85 + i as f64 / 27.;
+                let numerical = (energy(*potential, r + h) - energy(*potential, r - h)) / (2. * h);
+                assert_abs_diff_eq!(
+                    potential.derivative(r * r, 1., 1.),
+                    numerical,
+                    epsilon = 1e-5 * numerical.abs().max(1.)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shifted_force_continuous() {
+        let potential = PairPotential::LennardJones;
+        let cutoff = Some(2.5);
+        let energy =
+            |r: f64| potential.truncated_energy(r * r, 1., 1., cutoff, Truncation::ShiftedForce);
+        let derivative = |r: f64| {
+            potential.truncated_derivative(r * r, 1., 1., cutoff, Truncation::ShiftedForce)
+        };
+        assert_abs_diff_eq!(energy(2.5 - 1e-9), 0., epsilon = 1e-9);
+        assert_abs_diff_eq!(derivative(2.5 - 1e-9), 0., epsilon = 1e-9);
+        assert_abs_diff_eq!(energy(3.), 0.);
+        // The shifted force potential is still attractive near the minimum
+        assert!(energy(2_f64.powf(1. / 6.)) < 0.);
+
+        let h = 1e-6;
+        for i in 0..50 {
+            let r = 0.95 + i as f64 / 33.;
+            let numerical = (energy(r + h) - energy(r - h)) / (2. * h);
+            assert_abs_diff_eq!(
+                derivative(r),
+                numerical,
+                epsilon = 1e-5 * numerical.abs().max(1.)
+            );
+        }
+    }
+
+    #[test]
+    fn tail_numerical_integral() {
+        let cases = [
+            (PairPotential::LennardJones, 2.5),
+            (
+                PairPotential::Mie {
+                    repulsive: 14.,
+                    attractive: 7.,
+                },
+                2.,
+            ),
+            (PairPotential::WCA, 1.),
+            (PairPotential::Morse { alpha: 3. }, 1.5),
+            (PairPotential::Yukawa { kappa: 2. }, 2.),
+            (PairPotential::GaussianCore, 1.),
+            (PairPotential::SquareWell { width: 1.5 }, 1.2),
+            (PairPotential::SoftHarmonic, 0.6),
+        ];
+        for (potential, cutoff) in cases.iter() {
+            // Integrate using the midpoint rule out to a distance where the energy is negligible
+            let dr = 1e-4;
+            let numerical: f64 = (0..500_000)
+                .map(|i| cutoff + (i as f64 + 0.5) * dr)
+                .map(|r| 2. * std::f64::consts::PI * r * energy(*potential, r) * dr)
+                .sum();
+            assert_abs_diff_eq!(
+                potential.tail(*cutoff, 1., 1.),
+                numerical,
+                epsilon = 1e-5 * numerical.abs().max(1.)
+            );
+        }
+    }
+
+    #[test]
+    fn tail_diverges() {
+        let potential = PairPotential::Mie {
+            repulsive: 6.,
+            attractive: 2.,
+        };
+        assert!(potential.tail(2.5, 1., 1.).is_infinite());
+    }
+
+    #[test]
+    fn serialise_roundtrip() {
+        let potential = PairPotential::Mie {
+            repulsive: 10.,
+            attractive: 5.,
+        };
+        let serialised = serde_json::to_string(&potential).unwrap();
+        let deserialised: PairPotential = serde_json::from_str(&serialised).unwrap();
+        assert_eq!(potential, deserialised);
+    }
+}
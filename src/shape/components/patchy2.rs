@@ -0,0 +1,257 @@
+//
+// patchy2.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::PairPotential;
+use crate::traits::Potential;
+
+/// An attractive site on the surface of a patchy particle
+///
+/// Two patches bind when the particles are within the range of the patches and the vector
+/// joining the particles lies within the angular width of each of the patches, the
+/// Kern-Frenkel model of directional interactions.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Patch {
+    /// The unit vector from the center of the particle through the middle of the patch
+    pub direction: Vector2<f64>,
+    /// The angle in radians from the direction of the patch to the edge
+    pub half_width: f64,
+    /// The furthest distance between particles for which the patches bind, in units of the
+    /// core diameter
+    pub range: f64,
+    /// The energy of a bond between two patches
+    pub strength: f64,
+}
+
+impl Patch {
+    /// Create a patch pointing at an `angle` in radians from the x axis
+    pub fn new(angle: f64, half_width: f64, range: f64, strength: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            direction: Vector2::new(cos, sin),
+            half_width,
+            range,
+            strength,
+        }
+    }
+
+    /// The angle in radians of the center of the patch from the x axis
+    pub fn angle(&self) -> f64 {
+        self.direction.y.atan2(self.direction.x)
+    }
+
+    /// Whether the vector `bond` from the center of the particle passes through the patch
+    fn contains(&self, bond: &Vector2<f64>) -> bool {
+        self.direction.dot(bond) >= self.half_width.cos() * bond.norm()
+    }
+}
+
+/// The isotropic interaction between the cores of two patchy particles
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PatchyCore {
+    /// The cores are unable to overlap
+    Hard,
+    /// The cores interact through the Lennard Jones potential with a well depth `epsilon`
+    LennardJones { epsilon: f64 },
+}
+
+/// A disk with attractive patches on the surface
+///
+/// The orientation of the particle is described by the directions of the patches, which are
+/// rotated along with the particle by a transform.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Patchy2 {
+    /// The position of the center of the particle
+    pub position: Point2<f64>,
+    /// The diameter of the core of the particle
+    pub sigma: f64,
+    pub core: PatchyCore,
+    pub patches: Vec<Patch>,
+}
+
+impl Potential for Patchy2 {
+    /// The energy of the interaction between two particles
+    ///
+    /// This is the sum of the interaction of the cores and the bonds between each pair of
+    /// patches. The diameter of the cores and the range of the patches are the mean of the two
+    /// particles, while the strength of a bond is the geometric mean of the two patches.
+    ///
+    fn energy(&self, other: &Self) -> f64 {
+        let sigma = (self.sigma + other.sigma) / 2.;
+        let bond = other.position - self.position;
+        let r_squared = bond.norm_squared();
+
+        let core = match (self.core, other.core) {
+            (PatchyCore::Hard, _) | (_, PatchyCore::Hard) => {
+                if r_squared < sigma.powi(2) {
+                    return f64::INFINITY;
+                }
+                0.
+            }
+            (
+                PatchyCore::LennardJones { epsilon: e1 },
+                PatchyCore::LennardJones { epsilon: e2 },
+            ) => PairPotential::LennardJones.energy(r_squared, sigma, (e1 * e2).sqrt()),
+        };
+
+        let mut patches = 0.;
+        for p1 in self.patches.iter().filter(|p| p.contains(&bond)) {
+            for p2 in other.patches.iter().filter(|p| p.contains(&-bond)) {
+                let range = (p1.range + p2.range) / 2. * sigma;
+                if r_squared < range.powi(2) {
+                    patches -= (p1.strength * p2.strength).sqrt();
+                }
+            }
+        }
+        core + patches
+    }
+
+    /// The furthest distance at which either the cores or the patches interact
+    ///
+    /// The Lennard Jones core isn't truncated, so only particles with a hard core have a
+    /// finite range.
+    ///
+    fn interaction_range(&self, other: &Self) -> Option<f64> {
+        if let (PatchyCore::LennardJones { .. }, PatchyCore::LennardJones { .. }) =
+            (self.core, other.core)
+        {
+            return None;
+        }
+        let sigma = (self.sigma + other.sigma) / 2.;
+        let longest = |patches: &[Patch]| patches.iter().map(|p| p.range).fold(0., f64::max);
+        let range = (longest(&self.patches) + longest(&other.patches)) / 2.;
+        Some(sigma * range.max(1.))
+    }
+}
+
+impl fmt::Display for Patchy2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Patchy2 {{ {}, {}, {}, [",
+            self.position.x, self.position.y, self.sigma
+        )?;
+        for patch in self.patches.iter() {
+            write!(f, "{},", patch.angle())?;
+        }
+        write!(f, "] }}")
+    }
+}
+
+impl Patchy2 {
+    /// Create a particle at the origin with `num_patches` equally spaced identical patches
+    ///
+    /// The first patch points along the x axis.
+    ///
+    pub fn regular(
+        sigma: f64,
+        core: PatchyCore,
+        num_patches: usize,
+        half_width: f64,
+        range: f64,
+        strength: f64,
+    ) -> Self {
+        Self {
+            position: Point2::origin(),
+            sigma,
+            core,
+            patches: (0..num_patches)
+                .map(|i| {
+                    let angle = 2. * PI * i as f64 / num_patches as f64;
+                    Patch::new(angle, half_width, range, strength)
+                })
+                .collect(),
+        }
+    }
+
+    /// The largest distance from the center of the particle to the edge of a patch
+    pub fn radius(&self) -> f64 {
+        self.patches
+            .iter()
+            .map(|p| p.range * self.sigma / 2.)
+            .fold(self.sigma / 2., f64::max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::Transform2;
+
+    fn janus() -> Patchy2 {
+        Patchy2::regular(1., PatchyCore::Hard, 1, PI / 4., 1.5, 1.)
+    }
+
+    #[test]
+    fn facing_patches_bind() {
+        let a = janus();
+        // Rotate the second particle so the patch points back along the x axis
+        let b = janus() * Transform2::new(PI, (1.2, 0.));
+        assert_abs_diff_eq!(a.energy(&b), -1.);
+        assert_abs_diff_eq!(b.energy(&a), -1.);
+    }
+
+    #[test]
+    fn misaligned_patches() {
+        let a = janus();
+        // Both patches pointing in the same direction
+        let b = janus() * Transform2::new(0., (1.2, 0.));
+        assert_abs_diff_eq!(a.energy(&b), 0.);
+        // Within the half width of the patches
+        let b = janus() * Transform2::new(PI - 0.6, (1.2 * 0.1_f64.cos(), 1.2 * 0.1_f64.sin()));
+        assert_abs_diff_eq!(a.energy(&b), -1.);
+        // Outside the half width of the patches
+        let b = janus() * Transform2::new(PI - 0.8, (1.2, 0.));
+        assert_abs_diff_eq!(a.energy(&b), 0.);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let a = janus();
+        let b = janus() * Transform2::new(PI, (1.6, 0.));
+        assert_abs_diff_eq!(a.energy(&b), 0.);
+    }
+
+    #[test]
+    fn hard_core_overlap() {
+        let a = janus();
+        let b = janus() * Transform2::new(PI, (0.9, 0.));
+        assert!(a.energy(&b).is_infinite());
+    }
+
+    #[test]
+    fn lennard_jones_core() {
+        let core = PatchyCore::LennardJones { epsilon: 1. };
+        let a = Patchy2::regular(1., core, 0, 0., 1., 1.);
+        let b = Patchy2::regular(1., core, 0, 0., 1., 1.)
+            * Transform2::new(0., (2_f64.powf(1. / 6.), 0.));
+        assert_abs_diff_eq!(a.energy(&b), -1., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn reflection() {
+        let a = Patchy2::regular(1., PatchyCore::Hard, 1, 0.1, 1.5, 1.)
+            * Transform2::new(PI / 4., (0., 0.));
+        let b = a.clone() * Transform2::reflection(0.);
+        assert_abs_diff_eq!(b.patches[0].angle(), -PI / 4., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn radius() {
+        assert_abs_diff_eq!(janus().radius(), 0.75);
+        let core = Patchy2::regular(2., PatchyCore::Hard, 0, 0., 1., 1.);
+        assert_abs_diff_eq!(core.radius(), 1.);
+    }
+}
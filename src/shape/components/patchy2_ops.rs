@@ -0,0 +1,39 @@
+//
+// patchy2_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+use std::ops::Mul;
+
+use super::{Patch, Patchy2};
+use crate::Transform2;
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Patchy2, Output = Patchy2;
+    [ref ref] => {
+        Patchy2 {
+            position: self * rhs.position,
+            sigma: rhs.sigma,
+            core: rhs.core,
+            patches: rhs
+                .patches
+                .iter()
+                .map(|p| Patch {
+                    direction: self * p.direction,
+                    ..*p
+                })
+                .collect(),
+        }
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Patchy2, rhs: Transform2, Output = Patchy2;
+    [ref ref] => {
+        rhs * self
+    };
+);
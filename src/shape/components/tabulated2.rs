@@ -0,0 +1,355 @@
+//
+// tabulated2.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::path::Path;
+use std::str::FromStr;
+use std::{fmt, fs};
+
+use anyhow::{bail, Context, Error};
+use nalgebra::Point2;
+use serde::{Deserialize, Serialize};
+
+/// A pair potential given as a table of energies at a series of distances
+///
+/// The energy between the tabulated points is found using a natural cubic spline. Below the
+/// smallest distance in the table the potential is extended linearly using the slope of the
+/// spline, and beyond the cutoff the energy is zero. The potential is shifted so the energy is
+/// continuous at the cutoff.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TabulatedPotential {
+    distances: Vec<f64>,
+    energies: Vec<f64>,
+    /// The second derivative of the spline at each of the distances
+    second_derivatives: Vec<f64>,
+    cutoff: f64,
+    shift: f64,
+}
+
+impl TabulatedPotential {
+    /// Create a potential from the `energies` at each of the `distances`
+    ///
+    /// The distances need to be strictly increasing, with at least three points in the table.
+    /// When no `cutoff` is given, the largest distance in the table is used.
+    ///
+    pub fn new(
+        distances: Vec<f64>,
+        energies: Vec<f64>,
+        cutoff: Option<f64>,
+    ) -> Result<Self, Error> {
+        if distances.len() != energies.len() {
+            bail!(
+                "There are {} distances but {} energies",
+                distances.len(),
+                energies.len()
+            )
+        }
+        if distances.len() < 3 {
+            bail!(
+                "A table requires at least 3 points, found {}",
+                distances.len()
+            )
+        }
+        if distances
+            .iter()
+            .chain(energies.iter())
+            .any(|v| !v.is_finite())
+        {
+            bail!("The values in a table need to be finite")
+        }
+        if distances[0] < 0. {
+            bail!("The distances in a table can't be negative")
+        }
+        if distances.windows(2).any(|w| w[1] <= w[0]) {
+            bail!("The distances in a table need to be strictly increasing")
+        }
+        let last = distances[distances.len() - 1];
+        let cutoff = cutoff.unwrap_or(last);
+        if cutoff.is_nan() || cutoff <= distances[0] || cutoff > last {
+            bail!(
+                "The cutoff {} has to be within the table, between {} and {}",
+                cutoff,
+                distances[0],
+                last
+            )
+        }
+        let second_derivatives = Self::spline(&distances, &energies);
+        let mut potential = Self {
+            distances,
+            energies,
+            second_derivatives,
+            cutoff,
+            shift: 0.,
+        };
+        potential.shift = potential.interpolate(cutoff);
+        Ok(potential)
+    }
+
+    /// Read a table from a file with columns of distance and energy
+    pub fn from_file(path: &Path, cutoff: Option<f64>) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        let (distances, energies) = Self::parse_columns(&contents)
+            .with_context(|| format!("Unable to parse table from {}", path.display()))?;
+        Self::new(distances, energies, cutoff)
+    }
+
+    /// Read the distance and energy from the first two columns of each line
+    ///
+    /// Columns are separated by whitespace, with any further columns being ignored. Blank lines
+    /// and those starting with `#` are skipped.
+    ///
+    fn parse_columns(contents: &str) -> Result<(Vec<f64>, Vec<f64>), Error> {
+        let mut distances = vec![];
+        let mut energies = vec![];
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.split_whitespace().map(f64::from_str);
+            match (columns.next(), columns.next()) {
+                (Some(Ok(r)), Some(Ok(u))) => {
+                    distances.push(r);
+                    energies.push(u);
+                }
+                _ => bail!("Invalid values on line {}: {}", number + 1, line),
+            }
+        }
+        Ok((distances, energies))
+    }
+
+    /// The second derivatives of a natural cubic spline through the points
+    ///
+    /// This solves the tridiagonal system of equations using the Thomas algorithm, with the
+    /// second derivative being zero at each end.
+    ///
+    fn spline(x: &[f64], y: &[f64]) -> Vec<f64> {
+        let n = x.len();
+        let mut second = vec![0.; n];
+        let mut u = vec![0.; n];
+        for i in 1..n - 1 {
+            let sig = (x[i] - x[i - 1]) / (x[i + 1] - x[i - 1]);
+            let p = sig * second[i - 1] + 2.;
+            second[i] = (sig - 1.) / p;
+            let slope =
+                (y[i + 1] - y[i]) / (x[i + 1] - x[i]) - (y[i] - y[i - 1]) / (x[i] - x[i - 1]);
+            u[i] = (6. * slope / (x[i + 1] - x[i - 1]) - sig * u[i - 1]) / p;
+        }
+        for i in (0..n - 1).rev() {
+            second[i] = second[i] * second[i + 1] + u[i];
+        }
+        second
+    }
+
+    /// The value of the spline at the distance `r`, without any shift or cutoff
+    fn interpolate(&self, r: f64) -> f64 {
+        let x = &self.distances;
+        let y = &self.energies;
+        let d2 = &self.second_derivatives;
+        if r < x[0] {
+            let h = x[1] - x[0];
+            let slope = (y[1] - y[0]) / h - h * (2. * d2[0] + d2[1]) / 6.;
+            return y[0] + slope * (r - x[0]);
+        }
+        // The index of the upper end of the interval containing r
+        let upper = usize::min(x.partition_point(|&v| v <= r), x.len() - 1);
+        let lower = upper - 1;
+        let h = x[upper] - x[lower];
+        let a = (x[upper] - r) / h;
+        let b = (r - x[lower]) / h;
+        a * y[lower]
+            + b * y[upper]
+            + ((a.powi(3) - a) * d2[lower] + (b.powi(3) - b) * d2[upper]) * h.powi(2) / 6.
+    }
+
+    /// The slope of the spline at the distance `r`
+    fn interpolate_derivative(&self, r: f64) -> f64 {
+        let x = &self.distances;
+        let y = &self.energies;
+        let d2 = &self.second_derivatives;
+        if r < x[0] {
+            let h = x[1] - x[0];
+            return (y[1] - y[0]) / h - h * (2. * d2[0] + d2[1]) / 6.;
+        }
+        let upper = usize::min(x.partition_point(|&v| v <= r), x.len() - 1);
+        let lower = upper - 1;
+        let h = x[upper] - x[lower];
+        let a = (x[upper] - r) / h;
+        let b = (r - x[lower]) / h;
+        (y[upper] - y[lower]) / h
+            + ((1. - 3. * a.powi(2)) * d2[lower] + (3. * b.powi(2) - 1.) * d2[upper]) * h / 6.
+    }
+
+    /// The derivative of the energy with respect to the distance `r`
+    pub fn derivative(&self, r: f64) -> f64 {
+        if r < self.cutoff {
+            self.interpolate_derivative(r)
+        } else {
+            0.
+        }
+    }
+
+    /// The energy of two particles separated by the distance `r`
+    pub fn energy(&self, r: f64) -> f64 {
+        if r < self.cutoff {
+            self.interpolate(r) - self.shift
+        } else {
+            0.
+        }
+    }
+
+    /// The distance beyond which the energy is zero
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
+    /// The smallest distance at which the energy is no longer repulsive
+    ///
+    /// This is the equivalent of $\sigma$ for the Lennard Jones potential, being a measure of the
+    /// size of the particles.
+    ///
+    pub fn contact_distance(&self) -> f64 {
+        self.distances
+            .iter()
+            .zip(self.energies.iter())
+            .find(|(_, &u)| u - self.shift <= 0.)
+            .map_or(self.distances[0], |(&r, _)| r)
+    }
+}
+
+/// A particle interacting with others through a tabulated potential
+///
+/// The tables are stored once within the shape containing the particle, with each particle
+/// referring to its table by index, in the same way as the `species` of an `LJ2` particle.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Tabulated2 {
+    /// The position of a particle within the potential
+    pub position: Point2<f64>,
+    /// The index of the table describing the interactions of the particle
+    pub table: usize,
+    /// The radius of the particle, being half the contact distance of the table
+    pub radius: f64,
+}
+
+impl fmt::Display for Tabulated2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Tabulated2 {{ {}, {}, {} }}",
+            self.position.x, self.position.y, self.table
+        )
+    }
+}
+
+impl Tabulated2 {
+    /// Create a particle using the table at index `table`, which is described by `potential`
+    pub fn new(x: f64, y: f64, table: usize, potential: &TabulatedPotential) -> Self {
+        Self {
+            position: Point2::new(x, y),
+            table,
+            radius: potential.contact_distance() / 2.,
+        }
+    }
+}
+
+impl FromStr for TabulatedPotential {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (distances, energies) = Self::parse_columns(s)?;
+        Self::new(distances, energies, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::shape::PairPotential;
+
+    fn lj_table(cutoff: Option<f64>) -> TabulatedPotential {
+        let distances: Vec<f64> = (0..400).map(|i| 0.9 + i as f64 / 200.).collect();
+        let energies = distances
+            .iter()
+            .map(|r| PairPotential::LennardJones.energy(r * r, 1., 1.))
+            .collect();
+        TabulatedPotential::new(distances, energies, cutoff).unwrap()
+    }
+
+    #[test]
+    fn interpolates_knots() {
+        let table = lj_table(None);
+        for (r, u) in table.distances.iter().zip(table.energies.iter()) {
+            assert_abs_diff_eq!(table.interpolate(*r), u, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn interpolates_between_knots() {
+        let table = lj_table(None);
+        for i in 0..100 {
+            let r = 0.95 + i as f64 / 51.;
+            let expected = PairPotential::LennardJones.energy(r * r, 1., 1.) - table.shift;
+            assert_abs_diff_eq!(table.energy(r), expected, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn linear_is_exact() {
+        let table =
+            TabulatedPotential::new(vec![1., 2., 3., 4.], vec![4., 3., 2., 1.], None).unwrap();
+        assert_abs_diff_eq!(table.energy(2.5), 1.5, epsilon = 1e-12);
+        // Below the table the potential continues with the same slope
+        assert_abs_diff_eq!(table.energy(0.5), 3.5, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn cutoff() {
+        let table = lj_table(Some(2.5));
+        assert_abs_diff_eq!(table.energy(2.5), 0.);
+        assert_abs_diff_eq!(table.energy(3.), 0.);
+        assert_abs_diff_eq!(table.energy(2.5 - 1e-9), 0., epsilon = 1e-6);
+        assert!(table.energy(1.5) < 0.);
+    }
+
+    #[test]
+    fn derivative() {
+        let table = lj_table(Some(2.5));
+        let h = 1e-6;
+        for i in 0..100 {
+            let r = 0.85 + i as f64 / 61.;
+            let numerical = (table.energy(r + h) - table.energy(r - h)) / (2. * h);
+            assert_abs_diff_eq!(table.derivative(r), numerical, epsilon = 1e-5);
+        }
+        assert_abs_diff_eq!(table.derivative(3.), 0.);
+    }
+
+    #[test]
+    fn contact_distance() {
+        let table = lj_table(None);
+        assert_abs_diff_eq!(table.contact_distance(), 1., epsilon = 1e-2);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        assert!(TabulatedPotential::new(vec![1., 2.], vec![1., 0.], None).is_err());
+        assert!(TabulatedPotential::new(vec![1., 3., 2.], vec![1., 0., 0.], None).is_err());
+        assert!(TabulatedPotential::new(vec![1., 2., 3.], vec![1., 0.], None).is_err());
+        assert!(TabulatedPotential::new(vec![1., 2., 3.], vec![1., 0., 0.], Some(4.)).is_err());
+    }
+
+    #[test]
+    fn parse_table() {
+        let contents = "# r U F\n1.0 2.0 0.0\n\n1.5 1.0 0.0\n2.0 0.0 0.0\n";
+        let table: TabulatedPotential = contents.parse().unwrap();
+        assert_eq!(table.distances, vec![1., 1.5, 2.]);
+        assert_eq!(table.energies, vec![2., 1., 0.]);
+        assert!("1.0 a\n".parse::<TabulatedPotential>().is_err());
+    }
+}
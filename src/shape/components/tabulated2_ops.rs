@@ -0,0 +1,35 @@
+//
+// tabulated2_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+use std::ops::Mul;
+
+use super::Tabulated2;
+use crate::Transform2;
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Tabulated2, Output = Tabulated2;
+    [ref ref] => {
+        Tabulated2 {
+            position: self * rhs.position,
+            table: rhs.table,
+            radius: rhs.radius
+        }
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Tabulated2, rhs: Transform2, Output = Tabulated2;
+    [ref ref] => {
+        Tabulated2 {
+            position: rhs * self.position,
+            table: self.table,
+            radius: self.radius
+        }
+    };
+);
@@ -0,0 +1,263 @@
+//
+// composite_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use anyhow::{bail, Context, Error};
+use itertools::{iproduct, Itertools};
+use serde::{Deserialize, Serialize};
+
+use super::shape_file::check_closed;
+use super::{union_area, Atom2, Component2, LineShape};
+use crate::traits::{Intersect, Shape};
+use crate::Transform2;
+
+/// A Shape combining disks, line segments and polygons
+///
+/// This is a shape comprised of a collection of components which can each be of a different
+/// type, allowing for a representation of molecules like aromatic rings with circular
+/// substituents.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompositeShape {
+    pub name: String,
+    pub items: Vec<Component2>,
+}
+
+impl<'a> IntoIterator for &'a CompositeShape {
+    type Item = &'a Component2;
+    type IntoIter = slice::Iter<'a, Component2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl fmt::Display for CompositeShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CompositeShape {{ {} }}", self.items.iter().format(", "))
+    }
+}
+
+impl Intersect for CompositeShape {
+    /// Check whether this shape intersects with another shape
+    ///
+    /// The shapes intersect when any component of `self` intersects with any component of
+    /// `other`, regardless of the type of each component.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.iter(), other.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    /// The area of the union of all the components
+    ///
+    /// Regions covered by more than one component are only counted once, so this is exact for
+    /// any arrangement of overlapping disks and polygons.
+    ///
+    fn area(&self) -> f64 {
+        union_area(&self.items)
+    }
+}
+
+impl Shape for CompositeShape {
+    type Component = Component2;
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.iter()
+            .map(Component2::enclosing_radius)
+            // The f64 type doesn't have complete ordering because of Nan and Inf, so the
+            // standard min/max comparators don't work. Instead we use the f64::max which ignores
+            // the NAN and max values.
+            .fold(f64::MIN, f64::max)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl CompositeShape {
+    /// Create a CompositeShape from a collection of components
+    ///
+    /// Every polygon component has to be a closed outline enclosing some area.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::{Atom2, Component2, CompositeShape, LineShape};
+    /// let shape = CompositeShape::new(
+    ///     "Substituted Square",
+    ///     vec![
+    ///         Component2::Polygon(LineShape::polygon(4).unwrap()),
+    ///         Component2::Disk(Atom2::new(0., 1.2, 0.5)),
+    ///     ],
+    /// )
+    /// .unwrap();
+    /// # assert_eq!(shape.items.len(), 2);
+    /// ```
+    ///
+    pub fn new(name: &str, items: Vec<Component2>) -> Result<CompositeShape, Error> {
+        if items.is_empty() {
+            bail!("At least one component is required to create a shape.")
+        }
+        for component in items.iter() {
+            if let Component2::Polygon(p) = component {
+                check_closed(&p.items)
+                    .with_context(|| format!("Invalid polygon in the shape {}", name))?;
+            }
+        }
+        Ok(CompositeShape {
+            name: String::from(name),
+            items,
+        })
+    }
+
+    /// Create a regular polygon with a disk on some of the vertices
+    ///
+    /// This is a simple model of an aromatic ring, where the polygon is the ring and the disks are
+    /// the substituents. The polygon has the vertices a distance of 1 from the origin, with the
+    /// vertex at index 0 lying on the positive y axis and the remaining vertices continuing
+    /// clockwise.
+    ///
+    /// # Arguments
+    ///
+    /// - `sides` - The number of sides of the polygon
+    /// - `substituents` - The index of each vertex which has a disk attached
+    /// - `radius` - The radius of the disk for each substituent
+    /// - `distance` - The distance of the centre of each disk from the origin
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::CompositeShape;
+    /// // A para substituted benzene ring
+    /// let shape = CompositeShape::substituted_polygon(6, &[0, 3], 0.6, 1.5).unwrap();
+    /// # assert_eq!(shape.items.len(), 3);
+    /// ```
+    ///
+    pub fn substituted_polygon(
+        sides: usize,
+        substituents: &[usize],
+        radius: f64,
+        distance: f64,
+    ) -> Result<CompositeShape, Error> {
+        let polygon = LineShape::polygon(sides)?;
+        let dtheta = 2. * PI / sides as f64;
+        let mut items = vec![Component2::Polygon(polygon)];
+        for &index in substituents {
+            if index >= sides {
+                bail!(
+                    "Substituent index {} is out of range for a polygon with {} sides",
+                    index,
+                    sides
+                )
+            }
+            let angle = index as f64 * dtheta;
+            items.push(Component2::Disk(Atom2::new(
+                distance * f64::sin(angle),
+                distance * f64::cos(angle),
+                radius,
+            )));
+        }
+        CompositeShape::new("Substituted Polygon", items)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::Line2;
+
+    fn create_shape() -> CompositeShape {
+        CompositeShape::substituted_polygon(4, &[0], 0.5, 1.).unwrap()
+    }
+
+    #[test]
+    fn invalid_substituent() {
+        assert!(CompositeShape::substituted_polygon(4, &[4], 0.5, 1.).is_err());
+    }
+
+    #[test]
+    fn invalid_polygon() {
+        let empty = LineShape {
+            name: String::from("Empty"),
+            items: vec![],
+        };
+        assert!(CompositeShape::new("Empty", vec![Component2::Polygon(empty)]).is_err());
+
+        let mut open = LineShape::polygon(4).unwrap();
+        open.items.pop();
+        assert!(CompositeShape::new("Open", vec![Component2::Polygon(open)]).is_err());
+    }
+
+    #[test]
+    fn area() {
+        // The disk is centred on a right angled vertex so a quarter is within the square
+        let shape = create_shape();
+        assert_abs_diff_eq!(shape.area(), 2. + 0.75 * PI * 0.25, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn enclosing_radius() {
+        let shape = create_shape();
+        assert_abs_diff_eq!(shape.enclosing_radius(), 1.5);
+    }
+
+    #[test]
+    fn disk_intersects_polygon() {
+        let shape = create_shape();
+        // Move the shape up so the polygon of the second is just above the disk of the first
+        assert!(shape.intersects(&shape.transform(&Transform2::new(0., (0., 2.4)))));
+        assert!(!shape.intersects(&shape.transform(&Transform2::new(0., (0., 2.6)))));
+    }
+
+    #[test]
+    fn segment_intersects_disk() {
+        let shape = create_shape();
+        let rod = CompositeShape::new(
+            "Rod",
+            vec![Component2::Segment(Line2::new((-1., 0.), (1., 0.)))],
+        )
+        .unwrap();
+        assert!(shape.intersects(&rod.transform(&Transform2::new(0., (0., 1.4)))));
+        assert!(!shape.intersects(&rod.transform(&Transform2::new(0., (0., 1.6)))));
+    }
+
+    #[test]
+    fn contained_intersection() {
+        let shape = create_shape();
+        let small =
+            CompositeShape::new("Small", vec![Component2::Disk(Atom2::new(0., 0., 0.1))]).unwrap();
+        assert!(shape.intersects(&small));
+        assert!(small.intersects(&shape));
+    }
+}
@@ -11,11 +11,11 @@ use std::vec;
 
 use anyhow::{bail, Error};
 use itertools::{iproduct, Itertools};
-use nalgebra::{distance, Point2};
+use nalgebra::{distance, Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
 use super::Line2;
-use crate::traits::{Intersect, Shape};
+use crate::traits::{Intersect, Separation, Shape};
 use crate::Transform2;
 
 /// A Shape constructed from a collection of Lines
@@ -57,18 +57,51 @@ impl Intersect for LineShape {
         iproduct!(self.iter(), other.iter()).any(|(s, o)| s.intersects(o))
     }
 
+    /// The area enclosed by the lines, using the shoelace formula
+    ///
+    /// This is the sum of the signed areas of the triangles made by each line and the origin,
+    /// so it holds for any closed outline, regardless of where the origin is.
+    ///
     fn area(&self) -> f64 {
-        // This is the sine of the angle between each point, this is used for every calculation
-        // so pre-calculate here.
-        let angle_term: f64 = f64::sin(2. * PI / self.items.len() as f64);
-        self.iter()
-            // Calculate the area of the of triangle made by the line and the origin
-            .map(|p| {
-                0.5 * angle_term
-                    * distance(&Point2::origin(), &p.start)
-                    * distance(&Point2::origin(), &p.end)
+        let twice_area: f64 = self
+            .iter()
+            .map(|l| l.start.x * l.end.y - l.end.x * l.start.y)
+            .sum();
+        0.5 * twice_area.abs()
+    }
+}
+
+impl Separation for LineShape {
+    /// The signed distance between the boundaries of two shapes
+    ///
+    /// When the shapes are separated this is the smallest distance between any pair of lines.
+    /// When they overlap, either by the lines crossing or one shape containing the other, the
+    /// penetration depth is found using the separating axis theorem, which is exact for convex
+    /// shapes. For a concave shape this gives the penetration depth of its convex hull.
+    ///
+    fn separation(&self, other: &Self) -> f64 {
+        let contained = match (self.items.first(), other.items.first()) {
+            (Some(s), Some(o)) => self.contains_point(&o.start) || other.contains_point(&s.start),
+            _ => false,
+        };
+        if !contained && !self.intersects(other) {
+            return iproduct!(self.iter(), other.iter())
+                .map(|(s, o)| s.distance(o))
+                .fold(f64::MAX, f64::min);
+        }
+        // The overlap of the shapes along the normal of each line, where the smallest overlap is
+        // the shortest distance to separate the shapes.
+        -self
+            .iter()
+            .chain(other.iter())
+            .filter(|l| l.length() > 0.)
+            .map(|l| {
+                let axis = Vector2::new(-l.dy(), l.dx()) / l.length();
+                let (self_min, self_max) = self.projection(&axis);
+                let (other_min, other_max) = other.projection(&axis);
+                f64::min(self_max - other_min, other_max - self_min)
             })
-            .sum()
+            .fold(f64::MAX, f64::min)
     }
 }
 
@@ -89,7 +122,7 @@ impl Shape for LineShape {
             // The f64 type doesn't have complete ordering because of Nan and Inf, so the
             // standard min/max comparators don't work. Instead we use the f64::max which ignores
             // the NAN and max values.
-            .fold(std::f64::MIN, f64::max)
+            .fold(f64::MIN, f64::max)
     }
 
     fn get_items(&self) -> Vec<Self::Component> {
@@ -106,6 +139,10 @@ impl Shape for LineShape {
             items: self.into_iter().map(|i| i * transform).collect(),
         }
     }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
 }
 
 impl LineShape {
@@ -148,6 +185,23 @@ impl LineShape {
     pub fn polygon(sides: usize) -> Result<LineShape, Error> {
         LineShape::from_radial("Polygon", vec![1.; sides])
     }
+
+    /// Whether a point lies within the area enclosed by the lines
+    ///
+    /// This uses the even-odd rule, counting the number of lines crossed by a ray from the point.
+    ///
+    pub fn contains_point(&self, point: &Point2<f64>) -> bool {
+        self.iter().filter(|l| l.crosses_ray(point)).count() % 2 == 1
+    }
+
+    /// The minimum and maximum of the vertices projected onto `axis`
+    fn projection(&self, axis: &Vector2<f64>) -> (f64, f64) {
+        self.iter()
+            .map(|l| l.start.coords.dot(axis))
+            .fold((f64::MAX, f64::MIN), |(min, max), p| {
+                (f64::min(min, p), f64::max(max, p))
+            })
+    }
 }
 
 #[cfg(test)]
@@ -179,6 +233,14 @@ mod test {
         assert_abs_diff_eq!(shape.enclosing_radius(), 4.);
     }
 
+    #[test]
+    fn contains_point() {
+        let square = create_square();
+        assert!(square.contains_point(&Point2::new(0., 0.)));
+        assert!(square.contains_point(&Point2::new(0.4, 0.4)));
+        assert!(!square.contains_point(&Point2::new(0.6, 0.6)));
+    }
+
     #[test]
     fn intersection() {
         let square = create_square();
@@ -206,4 +268,51 @@ mod test {
         let transform = Transform2::new(0., (2.01, 2.01));
         assert!(!square.intersects(&square.transform(&transform)));
     }
+
+    #[test]
+    fn separation() {
+        // Rotate the square so the sides are aligned with the axes, with a side length of sqrt(2)
+        let square = create_square().transform(&Transform2::new(PI / 4., (0., 0.)));
+        let side = f64::sqrt(2.);
+        let shifted = |x, y| square.transform(&Transform2::new(0., (x, y)));
+        assert_abs_diff_eq!(
+            square.separation(&shifted(2., 0.)),
+            2. - side,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            square.separation(&shifted(1., 0.2)),
+            1. - side,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            square.separation(&shifted(0.1, 0.)),
+            0.1 - side,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn separation_contained() {
+        let square = create_square();
+        let small = LineShape::from_radial("Small", vec![0.1; 4]).unwrap();
+        assert!(!square.intersects(&small));
+        assert!(square.separation(&small) < 0.);
+        assert!(small.separation(&square) < 0.);
+    }
+
+    #[test]
+    fn mirror() {
+        let shape = LineShape::from_radial("Chiral", vec![1., 2., 1.5, 1.]).unwrap();
+        let mirrored = shape.mirror(PI / 2.);
+        assert_eq!(mirrored.name, "Chiral (mirror)");
+        assert_abs_diff_eq!(mirrored.area(), shape.area(), epsilon = 1e-12);
+        assert_abs_diff_eq!(mirrored.items[0].end, Point2::new(-2., 0.), epsilon = 1e-12);
+        // Mirroring twice across the same axis gives the original shape
+        let restored = mirrored.mirror(PI / 2.);
+        assert_eq!(restored.name, "Chiral");
+        for (a, b) in shape.iter().zip(restored.iter()) {
+            assert_abs_diff_eq!(a, b, epsilon = 1e-12);
+        }
+    }
 }
@@ -6,12 +6,13 @@
 
 use std::{fmt, slice, vec};
 
+use anyhow::{bail, Error};
 use itertools::iproduct;
 use nalgebra::{distance, Point2};
 use serde::{Deserialize, Serialize};
 
-use super::{Transform2, LJ2};
-use crate::traits::{Potential, Shape};
+use super::{LineShape, MixingRule, PairPotential, Transform2, Truncation, LJ2};
+use crate::traits::{Potential, PotentialGradient, Shape};
 
 /// A shape defined by a collection of Atoms
 ///
@@ -20,6 +21,9 @@ use crate::traits::{Potential, Shape};
 pub struct LJShape2 {
     pub name: String,
     pub items: Vec<LJ2>,
+    /// How the parameters of the particles are combined when interacting with another shape
+    #[serde(default)]
+    pub mixing: MixingRule,
 }
 
 impl<'a> IntoIterator for &'a LJShape2 {
@@ -34,19 +38,78 @@ impl<'a> IntoIterator for &'a LJShape2 {
 impl Potential for LJShape2 {
     fn energy(&self, other: &Self) -> f64 {
         iproduct!(self.items.iter(), other.items.iter())
-            .map(|(s, o)| s.energy(o))
+            .map(|(s, o)| s.mixed_energy(o, &self.mixing))
             .sum()
     }
+
+    fn charges(&self) -> Vec<(Point2<f64>, f64)> {
+        self.items
+            .iter()
+            .filter(|p| p.charge != 0.)
+            .map(|p| (p.position, p.charge))
+            .collect()
+    }
+
+    fn tail(&self, other: &Self) -> f64 {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| s.mixed_tail(o, &self.mixing))
+            .sum()
+    }
+
+    fn interaction_range(&self, other: &Self) -> Option<f64> {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| {
+                let cutoff = self.mixing.parameters(s, o).cutoff?;
+                Some(cutoff + s.position.coords.norm() + o.position.coords.norm())
+            })
+            .try_fold(0., |range: f64, r| Some(range.max(r?)))
+    }
+
+    /// The parameters of each pair of particles come from the mixing rule of `self`, so shapes
+    /// with different rules would interact differently depending on their order. Each pair of
+    /// particles also needs a single functional form for their interaction, either by sharing
+    /// the same form, or having one given in the table of the mixing rule.
+    ///
+    fn check_compatible(&self, other: &Self) -> Result<(), Error> {
+        if self.mixing != other.mixing {
+            bail!(
+                "The shapes {} and {} use different mixing rules, all shapes need the same rule",
+                self.name,
+                other.name
+            )
+        }
+        for (s, o) in iproduct!(self.items.iter(), other.items.iter()) {
+            if self.mixing.potential(s, o).is_none() {
+                bail!(
+                    "The particles of species {} and {} in the shapes {} and {} have different \
+                     potentials ({:?} and {:?}), which requires a table entry for the pair",
+                    s.species,
+                    o.species,
+                    self.name,
+                    other.name,
+                    s.potential,
+                    o.potential
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PotentialGradient for LJShape2 {
+    fn pair_derivatives(&self, other: &Self) -> Vec<(Point2<f64>, Point2<f64>, f64)> {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| (s.position, o.position, s.mixed_derivative(o, &self.mixing)))
+            .filter(|(_, _, derivative)| *derivative != 0.)
+            .collect()
+    }
 }
 
 impl Shape for LJShape2 {
     type Component = LJ2;
 
     fn score(&self, other: &Self) -> Option<f64> {
-        Some(
-            iproduct!(self.items.iter(), other.items.iter())
-                .fold(0., |sum, (s, o)| sum + s.energy(o)),
-        )
+        Some(self.energy(other))
     }
 
     fn enclosing_radius(&self) -> f64 {
@@ -56,7 +119,7 @@ impl Shape for LJShape2 {
             // The f64 type doesn't have complete ordering because of Nan and Inf, so the
             // standard min/max comparators don't work. Instead we use the f64::max which ignores
             // the NAN and max values.
-            .fold(std::f64::MIN, f64::max)
+            .fold(f64::MIN, f64::max)
     }
 
     fn get_items(&self) -> Vec<Self::Component> {
@@ -71,8 +134,13 @@ impl Shape for LJShape2 {
         Self {
             name: self.name.clone(),
             items: self.into_iter().map(|i| i * transform).collect(),
+            mixing: self.mixing.clone(),
         }
     }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
 }
 
 impl fmt::Display for LJShape2 {
@@ -127,6 +195,7 @@ impl LJShape2 {
                     ..Default::default()
                 })
                 .collect(),
+            mixing: MixingRule::default(),
         }
     }
 
@@ -144,9 +213,113 @@ impl LJShape2 {
     /// ```
     ///
     pub fn circle() -> Self {
+        Self::circle_with_sigma(1.)
+    }
+
+    /// Create an instance of a Circle with the characteristic distance `sigma`
+    ///
+    /// Circles of different sizes can be combined within a single state to study polydisperse
+    /// systems.
+    ///
+    pub fn circle_with_sigma(sigma: f64) -> Self {
         Self {
             name: String::from("circle"),
-            items: vec![LJ2::new(0., 0., 1.)],
+            items: vec![LJ2::new(0., 0., sigma)],
+            mixing: MixingRule::default(),
+        }
+    }
+
+    /// Create a shape from LJ sites spread along the outline of a `LineShape`
+    ///
+    /// Sites are evenly spaced along each line at `density` sites per unit length, with each line
+    /// having at least one site. The sites are placed at the start of each line, so the vertices
+    /// of a closed outline are only occupied once. Each site has the size `sigma`, which is
+    /// independent of the spacing, so a sigma of at least `1 / density` is required for the
+    /// neighbouring sites to touch, giving a continuous edge.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use packing::{LineShape, LJShape2};
+    /// let square = LineShape::polygon(4).unwrap();
+    /// let shape = LJShape2::from_lines(&square, 4., 0.25).unwrap();
+    /// # assert_eq!(shape.items.len(), 24);
+    /// ```
+    ///
+    pub fn from_lines(shape: &LineShape, density: f64, sigma: f64) -> Result<Self, Error> {
+        if density.is_nan() || density <= 0. {
+            bail!(
+                "The density of sites needs to be positive, found {}",
+                density
+            )
+        }
+        if sigma.is_nan() || sigma <= 0. {
+            bail!(
+                "The sigma of the sites needs to be positive, found {}",
+                sigma
+            )
+        }
+        let items = shape
+            .iter()
+            .flat_map(|line| {
+                let sites = usize::max(1, (line.length() * density).round() as usize);
+                (0..sites).map(move |i| {
+                    let fraction = i as f64 / sites as f64;
+                    LJ2 {
+                        position: line.start + (line.end - line.start) * fraction,
+                        sigma,
+                        cutoff: Some(2.5 * sigma),
+                        ..Default::default()
+                    }
+                })
+            })
+            .collect();
+        Ok(Self {
+            name: shape.name.clone(),
+            items,
+            mixing: MixingRule::default(),
+        })
+    }
+
+    /// Create a regular polygon with LJ sites along each edge
+    ///
+    /// This has the same outline as `LineShape::polygon`, with the vertices at a distance of 1
+    /// from the center, with the sites of size `sigma` placed at `density` sites per unit length.
+    ///
+    pub fn polygon(sides: usize, density: f64, sigma: f64) -> Result<Self, Error> {
+        Self::from_lines(&LineShape::polygon(sides)?, density, sigma)
+    }
+
+    /// Combine the parameters of interacting particles using the `mixing` rule
+    ///
+    /// All the shapes within a state have to use the same rule, so the interactions don't depend
+    /// on the order of the shapes.
+    ///
+    pub fn with_mixing(self, mixing: MixingRule) -> Self {
+        Self { mixing, ..self }
+    }
+
+    /// Use the functional form `potential` for the interactions of every particle in the shape
+    pub fn with_potential(self, potential: PairPotential) -> Self {
+        Self {
+            items: self
+                .items
+                .into_iter()
+                .map(|item| LJ2 { potential, ..item })
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Use the `truncation` scheme at the cutoff of every particle in the shape
+    pub fn with_truncation(self, truncation: Truncation) -> Self {
+        Self {
+            items: self
+                .items
+                .into_iter()
+                .map(|item| LJ2 { truncation, ..item })
+                .collect(),
+            ..self
         }
     }
 }
@@ -189,6 +362,70 @@ mod test {
         assert_abs_diff_eq!(shape.items[2].sigma, 4.);
     }
 
+    #[test]
+    fn polygon_sites() {
+        let shape = LJShape2::polygon(4, 4., 0.25).unwrap();
+        // Each side has a length of sqrt(2), giving 6 sites
+        assert_eq!(shape.items.len(), 24);
+        for item in shape.iter() {
+            assert_abs_diff_eq!(item.sigma, 0.25);
+        }
+        // All the sites lie on the outline of the square
+        for item in shape.iter() {
+            assert_abs_diff_eq!(
+                item.position.x.abs() + item.position.y.abs(),
+                1.,
+                epsilon = 1e-12
+            );
+        }
+        assert_abs_diff_eq!(shape.enclosing_radius(), 1.125, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn polygon_sparse_sites() {
+        // A low density still places a site on each side, without changing the size of the sites
+        let shape = LJShape2::polygon(3, 0.01, 0.25).unwrap();
+        assert_eq!(shape.items.len(), 3);
+        for item in shape.iter() {
+            assert_abs_diff_eq!(item.sigma, 0.25);
+            assert_eq!(item.cutoff, Some(0.625));
+        }
+    }
+
+    #[test]
+    fn polygon_invalid_density() {
+        assert!(LJShape2::polygon(4, 0., 0.25).is_err());
+        assert!(LJShape2::polygon(4, -1., 0.25).is_err());
+        assert!(LJShape2::polygon(4, f64::NAN, 0.25).is_err());
+    }
+
+    #[test]
+    fn polygon_invalid_sigma() {
+        assert!(LJShape2::polygon(4, 4., 0.).is_err());
+        assert!(LJShape2::polygon(4, 4., f64::NAN).is_err());
+    }
+
+    #[test]
+    fn with_potential() {
+        let shape = LJShape2::from_trimer(0.7, 120., 1.).with_potential(PairPotential::WCA);
+        for item in shape.iter() {
+            assert_eq!(item.potential, PairPotential::WCA);
+        }
+        // The WCA potential is purely repulsive
+        assert!(shape.energy(&shape.transform(&Transform2::new(0., (2., 0.)))) >= 0.);
+    }
+
+    #[test]
+    fn energy_symmetric() {
+        let trimer = LJShape2::from_trimer(0.637_556, 120., 1.);
+        let circle = LJShape2::circle_with_sigma(1.5).transform(&Transform2::new(0.3, (1.2, 1.)));
+        for mixing in &[MixingRule::LorentzBerthelot, MixingRule::Geometric] {
+            let a = trimer.clone().with_mixing(mixing.clone());
+            let b = circle.clone().with_mixing(mixing.clone());
+            assert_abs_diff_eq!(a.energy(&b), b.energy(&a), epsilon = 1e-12);
+        }
+    }
+
     #[test]
     fn trimer_cutoff() {
         let shape = LJShape2::from_trimer(2., 180., 0.5);
@@ -9,11 +9,37 @@ pub use super::Transform2;
 
 pub mod components;
 
+pub mod composite_shape;
 pub mod line_shape;
 pub mod lj_shape;
 pub mod molecular_shape2;
+pub mod patchy_shape;
+pub mod rounded_shape;
+pub mod shape_file;
+pub mod size_distribution;
+pub mod tabulated_shape;
+pub mod xyz_file;
 
 pub use components::*;
+pub use composite_shape::*;
 pub use line_shape::*;
 pub use lj_shape::*;
 pub use molecular_shape2::*;
+pub use patchy_shape::*;
+pub use rounded_shape::*;
+pub use shape_file::*;
+pub use size_distribution::*;
+pub use tabulated_shape::*;
+pub use xyz_file::*;
+
+/// The name of the mirror image of a shape called `name`
+///
+/// Mirroring a shape twice gives back the original shape, so the tag is removed from a name which
+/// already has it.
+///
+pub(crate) fn mirror_name(name: &str) -> String {
+    match name.strip_suffix(" (mirror)") {
+        Some(original) => String::from(original),
+        None => format!("{} (mirror)", name),
+    }
+}
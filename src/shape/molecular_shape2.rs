@@ -8,11 +8,11 @@ use std::f64::consts::PI;
 use std::{fmt, slice, vec};
 
 use itertools::{iproduct, Itertools};
-use nalgebra::Point2;
+use nalgebra::{Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
 use super::{Atom2, Transform2};
-use crate::traits::{Intersect, Shape};
+use crate::traits::{Intersect, Separation, Shape};
 
 /// A shape defined by a collection of Atoms
 ///
@@ -52,6 +52,52 @@ impl Intersect for MolecularShape2 {
     }
 }
 
+impl Separation for MolecularShape2 {
+    /// The smallest separation between any pair of atoms
+    ///
+    /// When the shapes overlap this is the penetration depth, the length of the shortest
+    /// translation of `other` which removes the overlap of every pair of atoms. Moving `other`
+    /// by a vector $t$ causes the atoms `s` and `o` to overlap when $t$ lies in the disk centred
+    /// on `s - o` with a radius of the sum of their radii. The penetration depth is the distance
+    /// from the origin to the closest point outside all these disks, which is either the point
+    /// on one of the circles closest to the origin, or where two of the circles cross.
+    ///
+    fn separation(&self, other: &Self) -> f64 {
+        let closest = iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| s.separation(o))
+            .fold(f64::MAX, f64::min);
+        if closest >= 0. {
+            return closest;
+        }
+
+        let disks: Vec<(Vector2<f64>, f64)> = iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| (s.position - o.position, s.radius + o.radius))
+            .collect();
+        let nearest = disks.iter().map(|&(centre, radius)| {
+            let distance = centre.norm();
+            if distance > 0. {
+                centre * (1. - radius / distance)
+            } else {
+                Vector2::new(radius, 0.)
+            }
+        });
+        let crossings = disks
+            .iter()
+            .tuple_combinations()
+            .flat_map(|(&(c1, r1), &(c2, r2))| Self::circle_crossings(c1, r1, c2, r2));
+        let depth = nearest
+            .chain(crossings)
+            .filter(|point| {
+                disks
+                    .iter()
+                    .all(|(centre, radius)| (point - centre).norm() >= radius - 1e-10)
+            })
+            .map(|point| point.norm())
+            .fold(f64::MAX, f64::min);
+        -depth
+    }
+}
+
 impl Shape for MolecularShape2 {
     type Component = Atom2;
 
@@ -70,7 +116,7 @@ impl Shape for MolecularShape2 {
             // The f64 type doesn't have complete ordering because of Nan and Inf, so the
             // standard min/max comparators don't work. Instead we use the f64::max which ignores
             // the NAN and max values.
-            .fold(std::f64::MIN, f64::max)
+            .fold(f64::MIN, f64::max)
     }
 
     fn get_items(&self) -> Vec<Self::Component> {
@@ -87,6 +133,10 @@ impl Shape for MolecularShape2 {
             items: self.into_iter().map(|i| i * transform).collect(),
         }
     }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
 }
 
 impl fmt::Display for MolecularShape2 {
@@ -100,6 +150,20 @@ impl fmt::Display for MolecularShape2 {
 }
 
 impl MolecularShape2 {
+    /// The points where two circles cross
+    fn circle_crossings(c1: Vector2<f64>, r1: f64, c2: Vector2<f64>, r2: f64) -> Vec<Vector2<f64>> {
+        let d = (c2 - c1).norm();
+        if d == 0. || d > r1 + r2 || d < (r1 - r2).abs() {
+            return vec![];
+        }
+        let along = (d.powi(2) + r1.powi(2) - r2.powi(2)) / (2. * d);
+        let across = f64::sqrt(f64::max(r1.powi(2) - along.powi(2), 0.));
+        let direction = (c2 - c1) / d;
+        let normal = Vector2::new(-direction.y, direction.x);
+        let middle = c1 + direction * along;
+        vec![middle + normal * across, middle - normal * across]
+    }
+
     fn overlap_area(r: f64, d: f64) -> f64 {
         r.powi(2) * f64::acos(d / r) - d * f64::sqrt(r.powi(2) - d.powi(2))
     }
@@ -165,9 +229,18 @@ impl MolecularShape2 {
     ///
     /// This is the simplest molecular shape, a single circle at the origin with radius of 1.0.
     pub fn circle() -> Self {
+        Self::circle_with_radius(1.)
+    }
+
+    /// Create an instance of a Circle with a radius of `radius`
+    ///
+    /// Circles of different sizes can be combined within a single state to study polydisperse
+    /// packings.
+    ///
+    pub fn circle_with_radius(radius: f64) -> Self {
         Self {
             name: String::from("circle"),
-            items: vec![Atom2::new(0., 0., 1.)],
+            items: vec![Atom2::new(0., 0., radius)],
         }
     }
 }
@@ -175,7 +248,6 @@ impl MolecularShape2 {
 #[cfg(test)]
 mod test {
     use approx::assert_abs_diff_eq;
-    use nalgebra::Point2;
 
     use super::*;
 
@@ -272,4 +344,56 @@ mod test {
         let transform = Transform2::new(0., (2., 2.));
         assert!(!mol.intersects(&mol.transform(&transform)));
     }
+
+    #[test]
+    fn separation() {
+        let mol = MolecularShape2::from_trimer(0.7, 120., 1.);
+        let separation = mol.separation(&mol.transform(&Transform2::new(0., (0., 3.))));
+        // The closest atoms are the central atom of the shifted shape with the smaller atoms
+        let expected = nalgebra::distance(
+            &mol.items[1].position,
+            &(mol.items[0].position + nalgebra::Vector2::new(0., 3.)),
+        ) - 1.7;
+        assert_abs_diff_eq!(separation, expected, epsilon = 1e-12);
+
+        // The central atoms overlap by 1.5, however moving them apart causes the smaller atoms
+        // to overlap, so the shapes have to be moved further.
+        let overlap = mol.separation(&mol.transform(&Transform2::new(0., (0.5, 0.))));
+        assert!(overlap < -1.5);
+    }
+
+    #[test]
+    fn separation_multiple_overlaps() {
+        // A disk between the atoms of a dimer can't escape along the line to the closest atom,
+        // since that moves it into the other atom, so it has to move perpendicular to the dimer.
+        let dimer = MolecularShape2 {
+            name: String::from("Dimer"),
+            items: vec![Atom2::new(-1., 0., 1.), Atom2::new(1., 0., 1.)],
+        };
+        let disk = MolecularShape2 {
+            name: String::from("Disk"),
+            items: vec![Atom2::new(0., 0.2, 1.)],
+        };
+        let depth = f64::sqrt(3.) - 0.2;
+        assert_abs_diff_eq!(dimer.separation(&disk), -depth, epsilon = 1e-12);
+        assert_abs_diff_eq!(disk.separation(&dimer), -depth, epsilon = 1e-12);
+        // Moving the disk by the penetration depth removes the overlap
+        let moved = disk.transform(&Transform2::new(0., (0., depth)));
+        assert_abs_diff_eq!(dimer.separation(&moved), 0., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn mirror() {
+        let mol = MolecularShape2::from_trimer(0.7, 120., 1.);
+        let mirrored = mol.mirror(0.);
+        assert_eq!(mirrored.name, "Trimer (mirror)");
+        for (original, image) in mol.iter().zip(mirrored.iter()) {
+            assert_abs_diff_eq!(
+                image.position,
+                Point2::new(original.position.x, -original.position.y)
+            );
+            assert_abs_diff_eq!(image.radius, original.radius);
+        }
+        assert_eq!(mirrored.mirror(PI / 3.).name, "Trimer");
+    }
 }
@@ -0,0 +1,190 @@
+//
+// patchy_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::{fmt, slice};
+
+use itertools::iproduct;
+use nalgebra::Point2;
+use serde::{Deserialize, Serialize};
+
+use super::{Patchy2, PatchyCore, Transform2};
+use crate::traits::{Potential, Shape};
+
+/// A shape defined by a collection of patchy particles
+///
+/// The patches give directional interactions between shapes, allowing for the study of
+/// particles which form specific bonds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchyShape2 {
+    pub name: String,
+    pub items: Vec<Patchy2>,
+}
+
+impl<'a> IntoIterator for &'a PatchyShape2 {
+    type Item = &'a Patchy2;
+    type IntoIter = slice::Iter<'a, Patchy2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Potential for PatchyShape2 {
+    fn energy(&self, other: &Self) -> f64 {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| s.energy(o))
+            .sum()
+    }
+
+    fn interaction_range(&self, other: &Self) -> Option<f64> {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| {
+                let range = s.interaction_range(o)?;
+                Some(range + s.position.coords.norm() + o.position.coords.norm())
+            })
+            .try_fold(0., |range: f64, r| Some(range.max(r?)))
+    }
+}
+
+impl Shape for PatchyShape2 {
+    type Component = Patchy2;
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        Some(self.energy(other))
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|p| nalgebra::distance(&Point2::origin(), &p.position) + p.radius())
+            .fold(f64::MIN, f64::max)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl fmt::Display for PatchyShape2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PatchyShape2 {{ ")?;
+        for item in self.items.iter() {
+            write!(f, "{},", item)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl PatchyShape2 {
+    /// Create a single patchy particle with a unit diameter and equally spaced patches
+    ///
+    /// # Arguments
+    ///
+    /// - `core` - The interaction between the cores of the particles
+    /// - `num_patches` - The number of patches on the surface of the particle
+    /// - `half_width` - The angular half width of each patch in radians
+    /// - `range` - The range of the patch interaction, in units of the core diameter
+    /// - `strength` - The energy of a bond between two patches
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use packing::{PatchyCore, PatchyShape2};
+    /// let shape = PatchyShape2::regular(PatchyCore::Hard, 3, 0.3, 1.2, 1.);
+    /// # assert_eq!(shape.items[0].patches.len(), 3);
+    /// ```
+    ///
+    pub fn regular(
+        core: PatchyCore,
+        num_patches: usize,
+        half_width: f64,
+        range: f64,
+        strength: f64,
+    ) -> Self {
+        Self {
+            name: String::from("Patchy"),
+            items: vec![Patchy2::regular(
+                1.,
+                core,
+                num_patches,
+                half_width,
+                range,
+                strength,
+            )],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn transform_rotates_patches() {
+        let shape = PatchyShape2::regular(PatchyCore::Hard, 2, 0.2, 1.2, 1.);
+        let rotated = shape.transform(&Transform2::new(PI / 2., (1., 0.)));
+        assert_abs_diff_eq!(
+            rotated.items[0].patches[0].angle(),
+            PI / 2.,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            rotated.items[0].patches[1].angle(),
+            -PI / 2.,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(rotated.items[0].position, Point2::new(1., 0.));
+    }
+
+    #[test]
+    fn bond_depends_on_orientation() {
+        let shape = PatchyShape2::regular(PatchyCore::Hard, 2, 0.2, 1.2, 1.);
+        // The second patch points back at the first particle
+        let bonded = shape.transform(&Transform2::new(0., (1.1, 0.)));
+        assert_abs_diff_eq!(shape.energy(&bonded), -1.);
+        let unbonded = shape.transform(&Transform2::new(PI / 2., (1.1, 0.)));
+        assert_abs_diff_eq!(shape.energy(&unbonded), 0.);
+        assert_abs_diff_eq!(unbonded.energy(&shape), 0.);
+    }
+
+    #[test]
+    fn interaction_range() {
+        let shape = PatchyShape2::regular(PatchyCore::Hard, 2, 0.2, 1.2, 1.);
+        let range = shape.interaction_range(&shape).unwrap();
+        assert_abs_diff_eq!(range, 1.2);
+        let bonded = shape.transform(&Transform2::new(0., (range - 1e-8, 0.)));
+        assert_abs_diff_eq!(shape.energy(&bonded), -1.);
+        let beyond = shape.transform(&Transform2::new(0., (range + 1e-8, 0.)));
+        assert_abs_diff_eq!(shape.energy(&beyond), 0.);
+
+        // Patches shorter than the core still interact through the core
+        let short = PatchyShape2::regular(PatchyCore::Hard, 2, 0.2, 0.5, 1.);
+        assert_abs_diff_eq!(short.interaction_range(&short).unwrap(), 1.);
+
+        let lj = PatchyShape2::regular(PatchyCore::LennardJones { epsilon: 1. }, 2, 0.2, 1.2, 1.);
+        assert!(lj.interaction_range(&lj).is_none());
+        assert!(lj.interaction_range(&shape).is_some());
+    }
+}
@@ -0,0 +1,429 @@
+//
+// rounded_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{distance, Point2};
+use serde::{Deserialize, Serialize};
+
+use super::{Line2, LineShape};
+use crate::traits::{Intersect, Shape};
+use crate::Transform2;
+
+/// A Shape with rounded edges, being a core of lines expanded by a radius
+///
+/// This is the shape which is made by sweeping a circle of `radius` over every point of the core,
+/// known as the Minkowski sum of the core with a disk. Where the core is a single line segment
+/// this is a spherocylinder (or stadium), and where the lines form a closed polygon this is a
+/// rounded polygon. A core of more than one line has to be a closed convex polygon, which is what
+/// makes the area and containment checks exact, and is assumed to be centred close to the origin.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundedShape {
+    pub name: String,
+    pub items: Vec<Line2>,
+    pub radius: f64,
+}
+
+impl<'a> IntoIterator for &'a RoundedShape {
+    type Item = &'a Line2;
+    type IntoIter = slice::Iter<'a, Line2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl fmt::Display for RoundedShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RoundedShape {{ {}, {} }}",
+            self.radius,
+            self.items.iter().format(", ")
+        )
+    }
+}
+
+impl Intersect for RoundedShape {
+    /// Check whether this shape intersects with another shape
+    ///
+    /// Two rounded shapes intersect when the distance between any two lines of their cores is
+    /// less than the sum of their radii. Additionally, where one shape is completely contained
+    /// within the core of the other, none of the lines will be close, so this is checked
+    /// separately.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        let contact = self.radius + other.radius;
+        if iproduct!(self.iter(), other.iter()).any(|(s, o)| s.distance(o) < contact) {
+            return true;
+        }
+        match (self.items.first(), other.items.first()) {
+            (Some(s), Some(o)) => self.core_contains(&o.start) || other.core_contains(&s.start),
+            _ => false,
+        }
+    }
+
+    /// The area enclosed by the shape
+    ///
+    /// For a convex core the area is given exactly by Steiner's formula $A + Pr + \pi r^2$, where
+    /// $A$ and $P$ are the area and perimeter of the core. A line segment is treated as a polygon
+    /// with no area which has a perimeter of twice its length.
+    ///
+    fn area(&self) -> f64 {
+        let (core_area, perimeter) = match self.items.len() {
+            1 => (0., 2. * self.items[0].length()),
+            _ => (
+                self.core_area(),
+                self.iter().map(Line2::length).sum::<f64>(),
+            ),
+        };
+        core_area + perimeter * self.radius + PI * self.radius.powi(2)
+    }
+}
+
+impl Shape for RoundedShape {
+    type Component = Line2;
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.iter()
+            .flat_map(|p| vec![p.start, p.end])
+            .map(|p| distance(&Point2::origin(), &p))
+            // The f64 type doesn't have complete ordering because of Nan and Inf, so the
+            // standard min/max comparators don't work. Instead we use the f64::max which ignores
+            // the NAN and max values.
+            .fold(f64::MIN, f64::max)
+            + self.radius
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+            radius: self.radius,
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl RoundedShape {
+    /// The area of the polygon making up the core of the shape
+    ///
+    /// This uses the shoelace formula, which is valid for any simple polygon.
+    ///
+    fn core_area(&self) -> f64 {
+        self.iter()
+            .map(|l| l.start.x * l.end.y - l.end.x * l.start.y)
+            .sum::<f64>()
+            .abs()
+            / 2.
+    }
+
+    /// Whether a point lies within the polygon making up the core of the shape
+    ///
+    /// A core with fewer than three lines doesn't enclose any area so can't contain a point.
+    ///
+    pub fn core_contains(&self, point: &Point2<f64>) -> bool {
+        if self.items.len() < 3 {
+            return false;
+        }
+        self.iter().filter(|l| l.crosses_ray(point)).count() % 2 == 1
+    }
+
+    /// Ensure the lines form a valid core for a rounded shape
+    ///
+    /// A single line is the core of a spherocylinder. Any more lines have to form a closed convex
+    /// polygon, with each line starting at the end of the previous line and every corner turning
+    /// the same way, going around the polygon only once.
+    ///
+    pub(crate) fn check_core(items: &[Line2]) -> Result<(), Error> {
+        if items.len() == 1 {
+            return Ok(());
+        }
+        if items.len() < 3 {
+            bail!(
+                "The core of a rounded shape has to be a single line or a closed polygon, found {} lines",
+                items.len()
+            )
+        }
+        let mut turning = 0.;
+        let mut direction = 0.;
+        for (line, next) in items.iter().zip(items.iter().cycle().skip(1)) {
+            if distance(&line.end, &next.start) > 1e-8 {
+                bail!(
+                    "The core of a rounded shape has to be closed, the lines {} and {} are not connected",
+                    line,
+                    next
+                )
+            }
+            let cross = line.dx() * next.dy() - line.dy() * next.dx();
+            let dot = line.dx() * next.dx() + line.dy() * next.dy();
+            if cross * direction < -1e-12 {
+                bail!(
+                    "The core of a rounded shape has to be convex, found a reflex corner at {}",
+                    next.start
+                )
+            }
+            if cross.abs() > 1e-12 {
+                direction = cross.signum();
+            }
+            turning += f64::atan2(cross, dot);
+        }
+        if (turning.abs() - 2. * PI).abs() > 1e-6 {
+            bail!("The core of a rounded shape has to go around the polygon once")
+        }
+        Ok(())
+    }
+
+    /// Create a RoundedShape from a collection of lines and a radius
+    ///
+    /// The lines are either a single line segment, or a closed convex polygon.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::{Line2, RoundedShape};
+    /// let shape = RoundedShape::new("Rod", vec![Line2::new((0., -1.), (0., 1.))], 0.5).unwrap();
+    /// # assert_eq!(shape.name, "Rod");
+    /// ```
+    ///
+    pub fn new(name: &str, items: Vec<Line2>, radius: f64) -> Result<RoundedShape, Error> {
+        if items.is_empty() {
+            bail!("At least one line is required to create a rounded shape.")
+        }
+        if radius < 0. {
+            bail!(
+                "The radius of a rounded shape can't be negative, found {}",
+                radius
+            )
+        }
+        RoundedShape::check_core(&items)?;
+        Ok(RoundedShape {
+            name: String::from(name),
+            items,
+            radius,
+        })
+    }
+
+    /// Create a Spherocylinder, a line segment with rounded ends
+    ///
+    /// In 2D this is also known as a stadium or a discorectangle. The line segment at the core of
+    /// the shape is centred on the origin and aligned with the y axis, which matches the
+    /// orientation of the other shapes.
+    ///
+    /// # Arguments
+    ///
+    /// - `length` - The length of the line segment at the core of the shape
+    /// - `radius` - The radius of the rounded ends
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::RoundedShape;
+    /// let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+    /// # assert_eq!(rod.items.len(), 1);
+    /// ```
+    ///
+    pub fn spherocylinder(length: f64, radius: f64) -> Result<RoundedShape, Error> {
+        RoundedShape::new(
+            "Spherocylinder",
+            vec![Line2::new((0., -length / 2.), (0., length / 2.))],
+            radius,
+        )
+    }
+
+    /// Create a rounded version of a LineShape
+    ///
+    /// The lines of the `shape` form the core of the new shape, which are expanded by `radius`.
+    /// This is an error when the `shape` isn't convex.
+    ///
+    pub fn from_line_shape(shape: &LineShape, radius: f64) -> Result<RoundedShape, Error> {
+        RoundedShape::new(&shape.name, shape.items.clone(), radius)
+    }
+
+    /// Create a regular polygon with rounded corners
+    ///
+    /// The core of the shape is a regular polygon with `sides` vertices a distance of 1 from the
+    /// origin.
+    ///
+    pub fn rounded_polygon(sides: usize, radius: f64) -> Result<RoundedShape, Error> {
+        RoundedShape::from_line_shape(&LineShape::polygon(sides)?, radius)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn create_square() -> RoundedShape {
+        RoundedShape::from_line_shape(&LineShape::polygon(4).unwrap(), 0.5).unwrap()
+    }
+
+    #[test]
+    fn invalid_new() {
+        assert!(RoundedShape::new("Empty", vec![], 1.).is_err());
+        assert!(RoundedShape::spherocylinder(1., -1.).is_err());
+    }
+
+    #[test]
+    fn open_chain() {
+        let lines = vec![
+            Line2::new((1., 0.), (0., 0.)),
+            Line2::new((0., 0.), (0., 1.)),
+        ];
+        assert!(RoundedShape::new("L", lines, 0.1).is_err());
+
+        let lines = vec![
+            Line2::new((1., 0.), (0., 0.)),
+            Line2::new((0., 0.), (0., 1.)),
+            Line2::new((0., 1.), (1., 1.)),
+        ];
+        assert!(RoundedShape::new("U", lines, 0.1).is_err());
+    }
+
+    #[test]
+    fn non_convex_core() {
+        let star = LineShape::from_radial("Star", vec![1., 0.4, 1., 0.4, 1., 0.4]).unwrap();
+        assert!(RoundedShape::from_line_shape(&star, 0.1).is_err());
+    }
+
+    #[test]
+    fn wound_twice() {
+        // The points of a regular pentagon joined every second point, making a pentagram
+        let points: Vec<_> = (0..5)
+            .map(|i| {
+                let angle = 4. * PI * i as f64 / 5.;
+                (angle.sin(), angle.cos())
+            })
+            .collect();
+        let lines = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(&s, &e)| Line2::new(s, e))
+            .collect();
+        assert!(RoundedShape::new("Pentagram", lines, 0.1).is_err());
+    }
+
+    #[test]
+    fn either_orientation() {
+        let square = LineShape::polygon(4).unwrap();
+        let reversed: Vec<_> = square
+            .iter()
+            .rev()
+            .map(|l| Line2::new((l.end.x, l.end.y), (l.start.x, l.start.y)))
+            .collect();
+        assert!(RoundedShape::new("Reversed", reversed, 0.1).is_ok());
+    }
+
+    #[test]
+    fn spherocylinder_area() {
+        let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+        assert_abs_diff_eq!(rod.area(), 2. + PI / 4.);
+    }
+
+    #[test]
+    fn rounded_square_area() {
+        // The square has a side length of sqrt(2)
+        let square = create_square();
+        let expected = 2. + 4. * f64::sqrt(2.) * 0.5 + PI * 0.25;
+        assert_abs_diff_eq!(square.area(), expected, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn zero_radius_area() {
+        let square = RoundedShape::rounded_polygon(4, 0.).unwrap();
+        assert_abs_diff_eq!(square.area(), 2., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn enclosing_radius() {
+        let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+        assert_abs_diff_eq!(rod.enclosing_radius(), 1.5);
+
+        let square = create_square();
+        assert_abs_diff_eq!(square.enclosing_radius(), 1.5);
+    }
+
+    #[test]
+    fn spherocylinder_side_by_side() {
+        let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+        assert!(rod.intersects(&rod.transform(&Transform2::new(0., (0.99, 0.)))));
+        assert!(!rod.intersects(&rod.transform(&Transform2::new(0., (1.01, 0.)))));
+    }
+
+    #[test]
+    fn spherocylinder_end_to_end() {
+        let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+        assert!(rod.intersects(&rod.transform(&Transform2::new(0., (0., 2.99)))));
+        assert!(!rod.intersects(&rod.transform(&Transform2::new(0., (0., 3.01)))));
+    }
+
+    #[test]
+    fn spherocylinder_crossed() {
+        let rod = RoundedShape::spherocylinder(2., 0.1).unwrap();
+        assert!(rod.intersects(&rod.transform(&Transform2::new(PI / 2., (0., 0.)))));
+    }
+
+    #[test]
+    fn rounded_corner_no_intersection() {
+        // The corners of the square core are on the axes, so shifting along the diagonal has the
+        // flat faces approach each other.
+        let square = create_square();
+        let separation = 2. * 1. / f64::sqrt(2.) + 2. * 0.5;
+        let offset = separation / f64::sqrt(2.) + 0.01;
+        assert!(!square.intersects(&square.transform(&Transform2::new(0., (offset, offset)))));
+    }
+
+    #[test]
+    fn rounded_corner_intersection() {
+        let square = create_square();
+        // The vertices are at a distance of 2, within the sum of the radii
+        assert!(square.intersects(&square.transform(&Transform2::new(0., (2.9, 0.)))));
+        assert!(!square.intersects(&square.transform(&Transform2::new(0., (3.01, 0.)))));
+    }
+
+    #[test]
+    fn contained_intersection() {
+        let square = RoundedShape::rounded_polygon(4, 0.).unwrap();
+        let small = RoundedShape::spherocylinder(0.1, 0.1).unwrap();
+        assert!(square.intersects(&small));
+        assert!(small.intersects(&square));
+    }
+
+    #[test]
+    fn core_contains() {
+        let square = create_square();
+        assert!(square.core_contains(&Point2::new(0., 0.)));
+        assert!(!square.core_contains(&Point2::new(1., 1.)));
+
+        let rod = RoundedShape::spherocylinder(2., 0.5).unwrap();
+        assert!(!rod.core_contains(&Point2::new(0., 0.)));
+    }
+}
@@ -0,0 +1,482 @@
+//
+// shape_file.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Error};
+use nalgebra::{Point2, Vector2};
+use serde::de::DeserializeOwned;
+
+use super::{
+    Component2, CompositeShape, LJShape2, Line2, LineShape, MixingRule, MolecularShape2,
+    RoundedShape,
+};
+use crate::traits::{Intersect, Potential, Shape};
+use crate::Transform2;
+
+/// Shapes which can be read from a definition stored in a file
+///
+/// A definition in a file can contain anything, so each shape describes what makes a valid
+/// definition and where its centre is. Once read, the shape is moved so its centre lies on the
+/// origin, which is what the rest of the crate assumes.
+///
+pub trait ShapeFile: Shape + DeserializeOwned {
+    /// Check the shape describes something physically meaningful
+    fn validate(&self) -> Result<(), Error>;
+
+    /// The point which is moved to the origin when recentring the shape
+    fn centroid(&self) -> Point2<f64>;
+
+    /// Translate the shape so the centroid lies on the origin
+    fn recentre(&self) -> Self {
+        let centroid = self.centroid();
+        self.transform(&Transform2::new(0., (-centroid.x, -centroid.y)))
+    }
+
+    /// Parse a shape from a string in the format given by `extension`
+    ///
+    /// The supported formats are `json` and `toml`.
+    ///
+    fn from_str_format(contents: &str, extension: &str) -> Result<Self, Error> {
+        let shape: Self = match extension {
+            "json" => serde_json::from_str(contents)?,
+            "toml" => toml::from_str(contents)?,
+            x => bail!("Unable to read shape from file with extension '{}'", x),
+        };
+        shape.validate()?;
+        Ok(shape.recentre())
+    }
+
+    /// Read a shape from a json or toml file
+    ///
+    /// The format of the file is determined from the extension, with the shape being validated and
+    /// recentred once it has been read.
+    ///
+    fn from_file(path: &Path) -> Result<Self, Error> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| anyhow!("Unable to determine format of {}", path.display()))?;
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read shape from {}", path.display()))?;
+        Self::from_str_format(&contents, extension)
+            .with_context(|| format!("Invalid shape definition in {}", path.display()))
+    }
+}
+
+fn check_finite(point: &Point2<f64>) -> Result<(), Error> {
+    if !(point.x.is_finite() && point.y.is_finite()) {
+        bail!("Found invalid position ({}, {})", point.x, point.y)
+    }
+    Ok(())
+}
+
+/// Ensure a value is greater than zero, which also excludes NaN values
+fn check_positive(value: f64, name: &str) -> Result<(), Error> {
+    if value > 0. {
+        Ok(())
+    } else {
+        bail!("The {} has to be positive, found {}", name, value)
+    }
+}
+
+/// Ensure a value is greater than or equal to zero, which also excludes NaN values
+fn check_non_negative(value: f64, name: &str) -> Result<(), Error> {
+    if value >= 0. {
+        Ok(())
+    } else {
+        bail!("The {} can't be negative, found {}", name, value)
+    }
+}
+
+/// Ensure a collection of lines forms a closed loop enclosing some area
+///
+/// Each line has to start at the end of the previous line, with the final line ending at the
+/// start of the first. The outline also can't be degenerate, like all points lying on a line,
+/// or self-cancelling, like a figure eight, since the area is required to find the centroid.
+///
+pub(crate) fn check_closed(lines: &[Line2]) -> Result<(), Error> {
+    if lines.len() < 3 {
+        bail!("A polygon requires at least 3 lines, found {}", lines.len())
+    }
+    for line in lines {
+        check_finite(&line.start)?;
+        check_finite(&line.end)?;
+    }
+    for (line, next) in lines.iter().zip(lines.iter().cycle().skip(1)) {
+        if nalgebra::distance(&line.end, &next.start) > 1e-8 {
+            bail!("The lines {} and {} are not connected", line, next)
+        }
+    }
+    let perimeter: f64 = lines.iter().map(|l| (l.end - l.start).norm()).sum();
+    let (area, _) = polygon_centroid(lines);
+    if area <= 1e-8 * perimeter.powi(2) {
+        bail!("The polygon has to enclose an area, found {}", area)
+    }
+    Ok(())
+}
+
+/// The area and centroid of the polygon formed by a collection of lines
+fn polygon_centroid(lines: &[Line2]) -> (f64, Point2<f64>) {
+    let (area, moment) = lines.iter().fold(
+        (0., Vector2::zeros()),
+        |(area, moment): (f64, Vector2<f64>), l| {
+            let cross = l.start.x * l.end.y - l.end.x * l.start.y;
+            (
+                area + cross / 2.,
+                moment + (l.start.coords + l.end.coords) * cross / 6.,
+            )
+        },
+    );
+    (area.abs(), Point2::from(moment / area))
+}
+
+/// The mean of a collection of points weighted by `weights`
+fn weighted_mean(points: impl Iterator<Item = (Point2<f64>, f64)>) -> Point2<f64> {
+    let (total, weight) = points.fold((Vector2::zeros(), 0.), |(total, weight), (p, w)| {
+        (total + p.coords * w, weight + w)
+    });
+    Point2::from(total / weight)
+}
+
+impl ShapeFile for MolecularShape2 {
+    fn validate(&self) -> Result<(), Error> {
+        if self.items.is_empty() {
+            bail!("The shape {} contains no atoms", self.name)
+        }
+        for atom in self.iter() {
+            check_finite(&atom.position)?;
+            check_positive(atom.radius, "radius")?;
+        }
+        Ok(())
+    }
+
+    fn centroid(&self) -> Point2<f64> {
+        weighted_mean(self.iter().map(|a| (a.position, a.area())))
+    }
+}
+
+impl ShapeFile for LJShape2 {
+    fn validate(&self) -> Result<(), Error> {
+        if self.items.is_empty() {
+            bail!("The shape {} contains no particles", self.name)
+        }
+        for particle in self.iter() {
+            check_finite(&particle.position)?;
+            check_positive(particle.sigma, "sigma")?;
+            check_non_negative(particle.epsilon, "epsilon")?;
+            if let Some(cutoff) = particle.cutoff {
+                check_positive(cutoff, "cutoff")?;
+            }
+            particle.potential.validate()?;
+        }
+        if let MixingRule::Table(entries) = &self.mixing {
+            for potential in entries.iter().filter_map(|e| e.potential.as_ref()) {
+                potential.validate()?;
+            }
+        }
+        // The particles of a shape also interact with the other copies of the shape
+        self.check_compatible(self)
+    }
+
+    fn centroid(&self) -> Point2<f64> {
+        weighted_mean(self.iter().map(|p| (p.position, p.sigma.powi(2))))
+    }
+}
+
+impl ShapeFile for LineShape {
+    fn validate(&self) -> Result<(), Error> {
+        check_closed(&self.items)
+    }
+
+    fn centroid(&self) -> Point2<f64> {
+        polygon_centroid(&self.items).1
+    }
+}
+
+impl ShapeFile for RoundedShape {
+    fn validate(&self) -> Result<(), Error> {
+        check_non_negative(self.radius, "radius")?;
+        match self.items.len() {
+            0 => bail!("The shape {} contains no lines", self.name),
+            1 => {
+                check_finite(&self.items[0].start)?;
+                check_finite(&self.items[0].end)
+            }
+            _ => {
+                check_closed(&self.items)?;
+                RoundedShape::check_core(&self.items)
+            }
+        }
+    }
+
+    fn centroid(&self) -> Point2<f64> {
+        match self.items.len() {
+            1 => nalgebra::center(&self.items[0].start, &self.items[0].end),
+            _ => polygon_centroid(&self.items).1,
+        }
+    }
+}
+
+impl ShapeFile for CompositeShape {
+    fn validate(&self) -> Result<(), Error> {
+        if self.items.is_empty() {
+            bail!("The shape {} contains no components", self.name)
+        }
+        for component in self.iter() {
+            match component {
+                Component2::Disk(a) => {
+                    check_finite(&a.position)?;
+                    check_positive(a.radius, "radius")?;
+                }
+                Component2::Segment(l) => {
+                    check_finite(&l.start)?;
+                    check_finite(&l.end)?;
+                }
+                Component2::Polygon(p) => check_closed(&p.items)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The centroid of the components weighted by area
+    ///
+    /// Where none of the components have any area, all the line segments contribute equally.
+    ///
+    fn centroid(&self) -> Point2<f64> {
+        let weighted: Vec<_> = self
+            .iter()
+            .map(|c| match c {
+                Component2::Disk(a) => (a.position, a.area()),
+                Component2::Segment(l) => (nalgebra::center(&l.start, &l.end), 0.),
+                Component2::Polygon(p) => {
+                    let (area, centroid) = polygon_centroid(&p.items);
+                    (centroid, area)
+                }
+            })
+            .collect();
+        if weighted.iter().all(|(_, w)| *w == 0.) {
+            weighted_mean(weighted.into_iter().map(|(p, _)| (p, 1.)))
+        } else {
+            weighted_mean(weighted.into_iter())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::{Atom2, PairPotential};
+
+    #[test]
+    fn molecular_json() {
+        let contents = r#"{
+            "name": "Dimer",
+            "items": [
+                {"position": [1.0, 1.0], "radius": 1.0},
+                {"position": [3.0, 1.0], "radius": 1.0}
+            ]
+        }"#;
+        let shape = MolecularShape2::from_str_format(contents, "json").unwrap();
+        assert_eq!(shape.name, "Dimer");
+        assert_abs_diff_eq!(shape.items[0].position, Point2::new(-1., 0.));
+        assert_abs_diff_eq!(shape.items[1].position, Point2::new(1., 0.));
+    }
+
+    #[test]
+    fn lj_toml() {
+        let contents = r#"
+            name = "Dimer"
+
+            [[items]]
+            position = [0.0, 0.0]
+            sigma = 1.0
+            epsilon = 1.0
+
+            [[items]]
+            position = [0.0, 1.0]
+            sigma = 1.0
+            epsilon = 1.0
+            cutoff = 2.5
+        "#;
+        let shape = LJShape2::from_str_format(contents, "toml").unwrap();
+        assert_eq!(shape.items.len(), 2);
+        assert_eq!(shape.items[0].cutoff, None);
+        assert_eq!(shape.items[1].cutoff, Some(2.5));
+        assert_abs_diff_eq!(shape.items[0].position, Point2::new(0., -0.5));
+    }
+
+    #[test]
+    fn lj_mixed_potentials() {
+        let particles = r#"
+            [[items]]
+            position = [0.0, 0.0]
+            sigma = 1.0
+            epsilon = 1.0
+
+            [[items]]
+            position = [0.0, 1.0]
+            sigma = 1.0
+            epsilon = 1.0
+            species = 1
+            potential = { Morse = { alpha = 3.0 } }
+        "#;
+        let contents = format!("name = \"Dimer\"\n{}", particles);
+        assert!(LJShape2::from_str_format(&contents, "toml").is_err());
+
+        let table = r#"
+            name = "Dimer"
+            mixing = { Table = [
+                { species = [0, 1], parameters = { sigma = 1.0, epsilon = 1.0 }, potential = { Morse = { alpha = 3.0 } } },
+            ] }
+        "#;
+        let contents = format!("{}{}", table, particles);
+        let shape = LJShape2::from_str_format(&contents, "toml").unwrap();
+        let morse = PairPotential::Morse { alpha: 3. };
+        assert_eq!(
+            shape.mixing.potential(&shape.items[0], &shape.items[1]),
+            Some(morse)
+        );
+    }
+
+    #[test]
+    fn line_shape_recentre() {
+        let square = LineShape::polygon(4)
+            .unwrap()
+            .transform(&Transform2::new(0., (2., -1.)));
+        let serialised = serde_json::to_string(&square).unwrap();
+        let shape = LineShape::from_str_format(&serialised, "json").unwrap();
+        assert_abs_diff_eq!(shape.centroid(), Point2::origin(), epsilon = 1e-12);
+        assert_abs_diff_eq!(shape.area(), 2., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn line_shape_rectangle_area() {
+        let contents = r#"
+            name = "Rectangle"
+
+            [[items]]
+            start = [0.0, 0.0]
+            end = [3.0, 0.0]
+
+            [[items]]
+            start = [3.0, 0.0]
+            end = [3.0, 1.0]
+
+            [[items]]
+            start = [3.0, 1.0]
+            end = [0.0, 1.0]
+
+            [[items]]
+            start = [0.0, 1.0]
+            end = [0.0, 0.0]
+        "#;
+        let shape = LineShape::from_str_format(contents, "toml").unwrap();
+        assert_abs_diff_eq!(shape.area(), 3., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn line_shape_not_closed() {
+        let mut square = LineShape::polygon(4).unwrap();
+        square.items.pop();
+        let serialised = serde_json::to_string(&square).unwrap();
+        assert!(LineShape::from_str_format(&serialised, "json").is_err());
+    }
+
+    #[test]
+    fn line_shape_zero_area() {
+        let line = LineShape {
+            name: String::from("Line"),
+            items: vec![
+                Line2::new((0., 0.), (1., 0.)),
+                Line2::new((1., 0.), (2., 0.)),
+                Line2::new((2., 0.), (0., 0.)),
+            ],
+        };
+        assert!(line.validate().is_err());
+
+        // The two halves of the figure eight have areas of opposite sign which cancel
+        let figure_eight = LineShape {
+            name: String::from("Figure Eight"),
+            items: vec![
+                Line2::new((0., 0.), (1., 1.)),
+                Line2::new((1., 1.), (1., -1.)),
+                Line2::new((1., -1.), (-1., 1.)),
+                Line2::new((-1., 1.), (-1., -1.)),
+                Line2::new((-1., -1.), (0., 0.)),
+            ],
+        };
+        assert!(figure_eight.validate().is_err());
+    }
+
+    #[test]
+    fn lj_invalid_potential() {
+        let contents = r#"{
+            "name": "Invalid",
+            "items": [
+                {
+                    "position": [0.0, 0.0],
+                    "sigma": 1.0,
+                    "epsilon": 1.0,
+                    "cutoff": null,
+                    "potential": {"Mie": {"repulsive": 6.0, "attractive": 12.0}}
+                }
+            ]
+        }"#;
+        assert!(LJShape2::from_str_format(contents, "json").is_err());
+    }
+
+    #[test]
+    fn invalid_radius() {
+        let shape = MolecularShape2 {
+            name: String::from("Invalid"),
+            items: vec![Atom2::new(0., 0., -1.)],
+        };
+        assert!(shape.validate().is_err());
+    }
+
+    #[test]
+    fn composite_round_trip() {
+        let shape = CompositeShape::substituted_polygon(6, &[0, 3], 0.5, 1.5).unwrap();
+        let serialised = serde_json::to_string(&shape).unwrap();
+        let read = CompositeShape::from_str_format(&serialised, "json").unwrap();
+        assert_eq!(read.items.len(), shape.items.len());
+        assert_abs_diff_eq!(read.centroid(), Point2::origin(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn composite_toml() {
+        let contents = r#"
+            name = "Substituted Triangle"
+            items = [
+                { Disk = { position = [0.0, 1.5], radius = 0.5 } },
+                { Polygon = { name = "Triangle", items = [
+                    { start = [0.0, 1.0], end = [1.0, -1.0] },
+                    { start = [1.0, -1.0], end = [-1.0, -1.0] },
+                    { start = [-1.0, -1.0], end = [0.0, 1.0] },
+                ] } },
+            ]
+        "#;
+        let shape = CompositeShape::from_str_format(contents, "toml").unwrap();
+        assert_eq!(shape.items.len(), 2);
+        assert_abs_diff_eq!(shape.centroid(), Point2::origin(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn rounded_segment_centroid() {
+        let rod = RoundedShape::new("Rod", vec![Line2::new((1., 0.), (1., 2.))], 0.5).unwrap();
+        assert_abs_diff_eq!(rod.centroid(), Point2::new(1., 1.));
+        assert_abs_diff_eq!(rod.recentre().centroid(), Point2::origin());
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(MolecularShape2::from_str_format("", "yaml").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//
+// size_distribution.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use anyhow::{bail, Error};
+use rand::Rng;
+
+/// The sizes of the particles within a polydisperse packing
+///
+/// The sizes can either be given explicitly, or drawn from a distribution. Each size is used for
+/// a separate shape in the state, which occupies its own site in the unit cell.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeDistribution {
+    /// A fixed collection of sizes
+    Fixed(Vec<f64>),
+    /// `count` sizes drawn uniformly from the range [`min`, `max`)
+    Uniform { count: usize, min: f64, max: f64 },
+    /// `count` sizes drawn from a normal distribution, with values which are not positive redrawn
+    Normal { count: usize, mean: f64, std: f64 },
+}
+
+impl SizeDistribution {
+    /// The sizes for a binary mixture, like the AB2 superlattice
+    ///
+    /// There are `count_a` particles of size 1 and `count_b` particles of size `ratio`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::SizeDistribution;
+    /// let ab2 = SizeDistribution::binary(0.5, 1, 2);
+    /// assert_eq!(ab2, SizeDistribution::Fixed(vec![1., 0.5, 0.5]));
+    /// ```
+    ///
+    pub fn binary(ratio: f64, count_a: usize, count_b: usize) -> Self {
+        let mut sizes = vec![1.; count_a];
+        sizes.extend(vec![ratio; count_b]);
+        SizeDistribution::Fixed(sizes)
+    }
+
+    /// Generate the sizes from the distribution
+    pub fn sizes<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Vec<f64>, Error> {
+        let sizes = match self {
+            SizeDistribution::Fixed(sizes) => sizes.clone(),
+            SizeDistribution::Uniform { count, min, max } => {
+                if min.is_nan() || max.is_nan() || *min <= 0. || min >= max {
+                    bail!("The range of sizes [{}, {}) is invalid", min, max)
+                }
+                (0..*count).map(|_| rng.gen_range(min, max)).collect()
+            }
+            SizeDistribution::Normal { count, mean, std } => {
+                if mean.is_nan() || *mean <= 0. || std.is_nan() || *std < 0. {
+                    bail!("The normal distribution N({}, {}) is invalid", mean, std)
+                }
+                (0..*count)
+                    .map(|_| loop {
+                        // Use the Box-Muller transform to draw from the normal distribution
+                        let u1: f64 = 1. - rng.gen::<f64>();
+                        let u2: f64 = rng.gen();
+                        let value = mean + std * (-2. * u1.ln()).sqrt() * f64::cos(2. * PI * u2);
+                        if value > 0. {
+                            break value;
+                        }
+                    })
+                    .collect()
+            }
+        };
+        if sizes.is_empty() {
+            bail!("At least one size is required")
+        }
+        if let Some(size) = sizes.iter().find(|&&s| s.is_nan() || s <= 0.) {
+            bail!("The sizes have to be positive, found {}", size)
+        }
+        Ok(sizes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    #[test]
+    fn fixed() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let sizes = SizeDistribution::binary(0.4, 2, 1).sizes(&mut rng).unwrap();
+        assert_eq!(sizes, vec![1., 1., 0.4]);
+        assert!(SizeDistribution::Fixed(vec![1., -1.])
+            .sizes(&mut rng)
+            .is_err());
+        assert!(SizeDistribution::Fixed(vec![]).sizes(&mut rng).is_err());
+    }
+
+    #[test]
+    fn uniform() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let distribution = SizeDistribution::Uniform {
+            count: 100,
+            min: 0.5,
+            max: 1.,
+        };
+        let sizes = distribution.sizes(&mut rng).unwrap();
+        assert_eq!(sizes.len(), 100);
+        assert!(sizes.iter().all(|s| (0.5..1.).contains(s)));
+    }
+
+    #[test]
+    fn normal() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let distribution = SizeDistribution::Normal {
+            count: 1000,
+            mean: 1.,
+            std: 0.1,
+        };
+        let sizes = distribution.sizes(&mut rng).unwrap();
+        let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
+        assert!((mean - 1.).abs() < 0.02);
+        assert!(sizes.iter().all(|&s| s > 0.));
+    }
+}
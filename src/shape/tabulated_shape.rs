@@ -0,0 +1,264 @@
+//
+// tabulated_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::sync::Arc;
+use std::{fmt, slice};
+
+use itertools::iproduct;
+use nalgebra::Point2;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{LJShape2, Tabulated2, TabulatedPotential, Transform2};
+use crate::traits::{Potential, PotentialGradient, Shape};
+
+/// A shape defined by a collection of particles interacting through tabulated potentials
+///
+/// This is the equivalent of the `LJShape2` for coarse-grained models where the pair potential
+/// is only known as a table of values. Each table is stored once, being shared between all the
+/// particles using it, and all the copies of the shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabulatedShape2 {
+    pub name: String,
+    /// The tables of the potentials, which each particle refers to by index
+    #[serde(
+        serialize_with = "serialize_tables",
+        deserialize_with = "deserialize_tables"
+    )]
+    pub tables: Arc<Vec<TabulatedPotential>>,
+    pub items: Vec<Tabulated2>,
+}
+
+fn serialize_tables<S: Serializer>(
+    tables: &Arc<Vec<TabulatedPotential>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    tables.as_slice().serialize(serializer)
+}
+
+fn deserialize_tables<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Arc<Vec<TabulatedPotential>>, D::Error> {
+    Vec::deserialize(deserializer).map(Arc::new)
+}
+
+impl<'a> IntoIterator for &'a TabulatedShape2 {
+    type Item = &'a Tabulated2;
+    type IntoIter = slice::Iter<'a, Tabulated2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Potential for TabulatedShape2 {
+    fn energy(&self, other: &Self) -> f64 {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| self.pair_value(s, other, o, TabulatedPotential::energy))
+            .sum()
+    }
+
+    fn interaction_range(&self, other: &Self) -> Option<f64> {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| {
+                f64::max(
+                    self.tables[s.table].cutoff(),
+                    other.tables[o.table].cutoff(),
+                ) + s.position.coords.norm()
+                    + o.position.coords.norm()
+            })
+            .fold(None, |range, r| Some(f64::max(range.unwrap_or(0.), r)))
+    }
+}
+
+impl PotentialGradient for TabulatedShape2 {
+    fn pair_derivatives(&self, other: &Self) -> Vec<(Point2<f64>, Point2<f64>, f64)> {
+        iproduct!(self.items.iter(), other.items.iter())
+            .map(|(s, o)| {
+                let derivative = self.pair_value(s, other, o, TabulatedPotential::derivative);
+                (s.position, o.position, derivative)
+            })
+            .filter(|(_, _, derivative)| *derivative != 0.)
+            .collect()
+    }
+}
+
+impl Shape for TabulatedShape2 {
+    type Component = Tabulated2;
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        Some(self.energy(other))
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|p| nalgebra::distance(&Point2::origin(), &p.position) + p.radius)
+            .fold(f64::MIN, f64::max)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            tables: self.tables.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl fmt::Display for TabulatedShape2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TabulatedShape2 {{ ")?;
+        for item in self.items.iter() {
+            write!(f, "{},", item)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl TabulatedShape2 {
+    /// Create a single particle at the origin interacting through `potential`
+    pub fn circle(potential: TabulatedPotential) -> Self {
+        Self {
+            name: String::from("circle"),
+            items: vec![Tabulated2::new(0., 0., 0, &potential)],
+            tables: Arc::new(vec![potential]),
+        }
+    }
+
+    /// Create a shape with a particle at each of the sites of `shape`
+    ///
+    /// All the particles interact through the same `potential`, with the parameters of the LJ
+    /// particles being ignored.
+    ///
+    pub fn from_lj_shape(shape: &LJShape2, potential: TabulatedPotential) -> Self {
+        Self {
+            name: shape.name.clone(),
+            items: shape
+                .iter()
+                .map(|p| Tabulated2::new(p.position.x, p.position.y, 0, &potential))
+                .collect(),
+            tables: Arc::new(vec![potential]),
+        }
+    }
+
+    /// Find `value` for the interaction of the particle `s` of this shape with `o` of `other`
+    ///
+    /// Particles sharing a table interact through that table. Where the particles have
+    /// different tables, the value is the mean of the two tables, so the result doesn't depend
+    /// on the order of the particles.
+    ///
+    fn pair_value(
+        &self,
+        s: &Tabulated2,
+        other: &Self,
+        o: &Tabulated2,
+        value: impl Fn(&TabulatedPotential, f64) -> f64,
+    ) -> f64 {
+        let r = nalgebra::distance(&s.position, &o.position);
+        let table = &self.tables[s.table];
+        if Arc::ptr_eq(&self.tables, &other.tables) && s.table == o.table {
+            value(table, r)
+        } else {
+            (value(table, r) + value(&other.tables[o.table], r)) / 2.
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::shape::{PairPotential, LJ2};
+
+    fn lj_table() -> TabulatedPotential {
+        lj_table_cutoff(3.5)
+    }
+
+    fn lj_table_cutoff(cutoff: f64) -> TabulatedPotential {
+        let distances: Vec<f64> = (0..600).map(|i| 0.8 + i as f64 / 200.).collect();
+        let energies = distances
+            .iter()
+            .map(|r| PairPotential::LennardJones.energy(r * r, 1., 1.))
+            .collect();
+        TabulatedPotential::new(distances, energies, Some(cutoff)).unwrap()
+    }
+
+    #[test]
+    fn matches_lj_shape() {
+        let trimer = LJShape2::from_trimer(0.5, 120., 1.);
+        // The trimer has a cutoff of 3.5, the same as the table
+        let lj = LJShape2 {
+            items: trimer
+                .iter()
+                .map(|p| LJ2 {
+                    sigma: 1.,
+                    ..p.clone()
+                })
+                .collect(),
+            ..trimer
+        };
+        let tabulated = TabulatedShape2::from_lj_shape(&lj, lj_table());
+        let transform = Transform2::new(0.4, (1.5, 1.2));
+        assert_abs_diff_eq!(
+            tabulated.energy(&tabulated.transform(&transform)),
+            lj.energy(&lj.transform(&transform)),
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn energy_symmetric() {
+        let a = TabulatedShape2::circle(lj_table());
+        let b = TabulatedShape2::from_lj_shape(&LJShape2::from_trimer(0.7, 120., 1.), lj_table())
+            .transform(&Transform2::new(0.2, (1.1, 1.6)));
+        assert_abs_diff_eq!(a.energy(&b), b.energy(&a), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn energy_symmetric_different_tables() {
+        let cutoff = TabulatedShape2 {
+            tables: Arc::new(vec![lj_table_cutoff(2.)]),
+            ..TabulatedShape2::circle(lj_table())
+        };
+        let a = TabulatedShape2::circle(lj_table());
+        let b = cutoff.transform(&Transform2::new(0., (1.2, 0.3)));
+        assert_abs_diff_eq!(a.energy(&b), b.energy(&a), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn tables_serialised_once() {
+        let shape =
+            TabulatedShape2::from_lj_shape(&LJShape2::from_trimer(0.7, 120., 1.), lj_table());
+        let serialised = serde_json::to_string(&shape).unwrap();
+        assert_eq!(serialised.matches("second_derivatives").count(), 1);
+
+        let read: TabulatedShape2 = serde_json::from_str(&serialised).unwrap();
+        let transform = Transform2::new(0.4, (1.5, 1.2));
+        assert_abs_diff_eq!(
+            read.energy(&read.transform(&transform)),
+            shape.energy(&shape.transform(&transform)),
+        );
+    }
+
+    #[test]
+    fn enclosing_radius() {
+        let shape = TabulatedShape2::circle(lj_table());
+        assert_abs_diff_eq!(shape.enclosing_radius(), 0.5, epsilon = 1e-2);
+    }
+}
@@ -0,0 +1,462 @@
+//
+// xyz_file.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Error};
+use log::warn;
+use nalgebra::{Matrix3, Point2, Point3, Vector3};
+
+use super::{Atom2, LJShape2, MolecularShape2, LJ2};
+use crate::ShapeFile;
+
+/// The parameters describing the size and interactions of each element
+///
+/// All lengths are in Ångström and energies in kcal/mol.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElementParameters {
+    pub symbol: &'static str,
+    /// The van der Waals radius from Bondi (1964)
+    pub vdw_radius: f64,
+    /// The Lennard Jones $\sigma$, being the distance where the potential is zero
+    pub sigma: f64,
+    /// The Lennard Jones $\epsilon$, being the depth of the potential well
+    pub epsilon: f64,
+}
+
+/// The symbol, van der Waals radius, and the UFF bond distance $x$ and well depth $D$
+///
+/// The UFF parameters are from Rappé et al. (1992), where the bond distance is the position of
+/// the minimum in the potential.
+///
+const ELEMENTS: [(&str, f64, f64, f64); 19] = [
+    ("H", 1.20, 2.886, 0.044),
+    ("He", 1.40, 2.362, 0.056),
+    ("Li", 1.82, 2.451, 0.025),
+    ("B", 1.92, 4.083, 0.180),
+    ("C", 1.70, 3.851, 0.105),
+    ("N", 1.55, 3.660, 0.069),
+    ("O", 1.52, 3.500, 0.060),
+    ("F", 1.47, 3.364, 0.050),
+    ("Ne", 1.54, 3.243, 0.042),
+    ("Na", 2.27, 2.983, 0.030),
+    ("Si", 2.10, 4.295, 0.402),
+    ("P", 1.80, 4.147, 0.305),
+    ("S", 1.80, 4.035, 0.274),
+    ("Cl", 1.75, 3.947, 0.227),
+    ("Ar", 1.88, 3.868, 0.185),
+    ("K", 2.75, 3.812, 0.035),
+    ("Se", 1.90, 4.205, 0.291),
+    ("Br", 1.85, 4.189, 0.251),
+    ("I", 1.98, 4.500, 0.339),
+];
+
+/// Find the parameters of an element from the chemical symbol
+///
+/// The symbol is case insensitive, and any numeric label following the symbol, like the `C1`
+/// used by many programs, is ignored.
+///
+/// # Example
+///
+/// ```
+/// use packing::element_parameters;
+/// let carbon = element_parameters("C1").unwrap();
+/// # assert_eq!(carbon.symbol, "C");
+/// assert_eq!(carbon.vdw_radius, 1.70);
+/// ```
+///
+pub fn element_parameters(symbol: &str) -> Result<ElementParameters, Error> {
+    let symbol = symbol.trim_end_matches(|c: char| c.is_ascii_digit());
+    ELEMENTS
+        .iter()
+        .find(|(s, ..)| s.eq_ignore_ascii_case(symbol))
+        .map(|&(symbol, vdw_radius, x, d)| ElementParameters {
+            symbol,
+            vdw_radius,
+            sigma: x / 2_f64.powf(1. / 6.),
+            epsilon: d,
+        })
+        .ok_or_else(|| anyhow!("No parameters are available for the element '{}'", symbol))
+}
+
+/// A molecule read from an XYZ file
+///
+/// The XYZ format has the number of atoms on the first line, a comment on the second line, and
+/// then a line for each atom consisting of the chemical symbol followed by the x, y and z
+/// coordinates in Ångström.
+///
+/// No conversion is made to reduced units when creating a shape. A length of one in the
+/// resulting shape is 1 Å, and for the Lennard Jones shapes an energy of one is 1 kcal/mol, so
+/// the temperature $kT$ used when optimising is also in kcal/mol (room temperature is about
+/// 0.6 kcal/mol).
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct XYZMolecule {
+    pub name: String,
+    pub atoms: Vec<(String, Point3<f64>)>,
+}
+
+impl FromStr for XYZMolecule {
+    type Err = Error;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let mut lines = contents.lines();
+        let count: usize = lines
+            .next()
+            .ok_or_else(|| anyhow!("The XYZ file is empty"))?
+            .trim()
+            .parse()
+            .context("The first line of an XYZ file has to be the number of atoms")?;
+        let name = lines.next().unwrap_or_default().trim();
+
+        let atoms = lines
+            .filter(|l| !l.trim().is_empty())
+            .take(count)
+            .map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 4 {
+                    bail!("Unable to parse atom from the line '{}'", line)
+                }
+                let coords = fields[1..4]
+                    .iter()
+                    .map(|f| f.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Invalid coordinates in the line '{}'", line))?;
+                Ok((
+                    String::from(fields[0]),
+                    Point3::new(coords[0], coords[1], coords[2]),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if atoms.len() != count {
+            bail!("Expected {} atoms, found {}", count, atoms.len())
+        }
+        if atoms.is_empty() {
+            bail!("The molecule contains no atoms")
+        }
+        Ok(XYZMolecule {
+            name: String::from(name),
+            atoms,
+        })
+    }
+}
+
+impl XYZMolecule {
+    /// Read a molecule from an XYZ file
+    ///
+    /// Where the comment line of the file is empty, the molecule is named after the file.
+    ///
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read molecule from {}", path.display()))?;
+        let mut molecule: Self = contents
+            .parse()
+            .with_context(|| format!("Invalid XYZ file {}", path.display()))?;
+        if molecule.name.is_empty() {
+            molecule.name = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(String::from)
+                .unwrap_or_default();
+        }
+        Ok(molecule)
+    }
+
+    fn centre(&self) -> Point3<f64> {
+        Point3::from(
+            self.atoms
+                .iter()
+                .fold(Vector3::zeros(), |sum, (_, p)| sum + p.coords)
+                / self.atoms.len() as f64,
+        )
+    }
+
+    /// The axes of the best fit plane and the normal to that plane
+    ///
+    /// These are the eigenvectors of the covariance matrix of the atomic positions, ordered from
+    /// the largest to the smallest eigenvalue. The final axis is the normal to the plane.
+    ///
+    /// The sign of each eigenvector is arbitrary, which would make the handedness of the
+    /// projection arbitrary, turning a chiral molecule into its mirror image. To prevent this
+    /// the normal is chosen so its largest component is positive, which means the plane is
+    /// viewed from the same side as a molecule lying in the xy plane is when looking down the
+    /// z axis. The first axis points towards the side with the larger third moment of the
+    /// positions, and the second axis completes a right handed coordinate system, so the
+    /// projection is always a rotation of the molecule viewed from that side.
+    ///
+    fn principal_axes(&self) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
+        let centre = self.centre();
+        let covariance = self.atoms.iter().fold(Matrix3::zeros(), |sum, (_, p)| {
+            let d = p - centre;
+            sum + d * d.transpose()
+        });
+        let eigen = covariance.symmetric_eigen();
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| eigen.eigenvalues[b].total_cmp(&eigen.eigenvalues[a]));
+        let mut first: Vector3<f64> = eigen.eigenvectors.column(order[0]).into();
+        let mut normal: Vector3<f64> = eigen.eigenvectors.column(order[2]).into();
+        if normal[normal.iamax()] < 0. {
+            normal = -normal;
+        }
+        let third_moment: f64 = self
+            .atoms
+            .iter()
+            .map(|(_, p)| (p - centre).dot(&first).powi(3))
+            .sum();
+        if third_moment < 0. {
+            first = -first;
+        }
+        (first, normal.cross(&first), normal)
+    }
+
+    /// Project the positions of the atoms onto the best fit plane of the molecule
+    ///
+    /// The longest axis of the molecule is aligned with the x axis of the projection, and the
+    /// projection is never a reflection of the molecule, see `principal_axes`.
+    ///
+    pub fn project(&self) -> Vec<Point2<f64>> {
+        let centre = self.centre();
+        let (x_axis, y_axis, _) = self.principal_axes();
+        self.atoms
+            .iter()
+            .map(|(_, p)| Point2::new((p - centre).dot(&x_axis), (p - centre).dot(&y_axis)))
+            .collect()
+    }
+
+    /// The root mean squared distance of the atoms from the best fit plane
+    pub fn planarity(&self) -> f64 {
+        let centre = self.centre();
+        let (_, _, normal) = self.principal_axes();
+        let total: f64 = self
+            .atoms
+            .iter()
+            .map(|(_, p)| (p - centre).dot(&normal).powi(2))
+            .sum();
+        (total / self.atoms.len() as f64).sqrt()
+    }
+
+    /// The parameters of each atom along with the projected position
+    fn projected_parameters(&self) -> Result<Vec<(ElementParameters, Point2<f64>)>, Error> {
+        let deviation = self.planarity();
+        if deviation > 0.5 {
+            warn!(
+                "The molecule {} is not planar, with atoms an average of {:.2} Å from the plane",
+                self.name, deviation
+            );
+        }
+        self.atoms
+            .iter()
+            .zip(self.project())
+            .map(|((symbol, _), position)| Ok((element_parameters(symbol)?, position)))
+            .collect()
+    }
+
+    /// Create a MolecularShape2 with each atom having the van der Waals radius of the element
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use packing::XYZMolecule;
+    /// let molecule: XYZMolecule = "2\nHydrogen\nH 0 0 0\nH 0.74 0 0".parse().unwrap();
+    /// let shape = molecule.to_molecular_shape().unwrap();
+    /// # assert_eq!(shape.items.len(), 2);
+    /// # assert_eq!(shape.name, "Hydrogen");
+    /// ```
+    ///
+    pub fn to_molecular_shape(&self) -> Result<MolecularShape2, Error> {
+        let shape = MolecularShape2 {
+            name: self.name.clone(),
+            items: self
+                .projected_parameters()?
+                .into_iter()
+                .map(|(e, p)| Atom2::new(p.x, p.y, e.vdw_radius))
+                .collect(),
+        };
+        shape.validate()?;
+        Ok(shape.recentre())
+    }
+
+    /// Create a LJShape2 with each particle having the UFF parameters of the element
+    ///
+    /// The potential of each particle is truncated and shifted at 2.5 $\sigma$.
+    ///
+    pub fn to_lj_shape(&self) -> Result<LJShape2, Error> {
+        let shape = LJShape2 {
+            name: self.name.clone(),
+            items: self
+                .projected_parameters()?
+                .into_iter()
+                .map(|(e, p)| LJ2 {
+                    position: p,
+                    sigma: e.sigma,
+                    epsilon: e.epsilon,
+                    cutoff: Some(2.5 * e.sigma),
+                    ..Default::default()
+                })
+                .collect(),
+            mixing: Default::default(),
+        };
+        shape.validate()?;
+        Ok(shape.recentre())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::traits::Shape;
+
+    /// Benzene with the ring in a plane tilted from all the coordinate axes
+    fn benzene() -> XYZMolecule {
+        let normal = Vector3::new(1., 2., 3.).normalize();
+        let a = normal.cross(&Vector3::z()).normalize();
+        let b = normal.cross(&a);
+        let atoms = (0..6)
+            .flat_map(|i| {
+                let (sin, cos) = (f64::from(i) * std::f64::consts::PI / 3.).sin_cos();
+                vec![
+                    ("C", 1.39 * (a * cos + b * sin)),
+                    ("H", 2.48 * (a * cos + b * sin)),
+                ]
+            })
+            .map(|(s, v)| (String::from(s), Point3::from(v + Vector3::new(1., 1., 1.))))
+            .collect();
+        XYZMolecule {
+            name: String::from("Benzene"),
+            atoms,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let contents = "3\nWater\nO 0.0 0.0 0.117\nH 0.0 0.757 -0.467\nH 0.0 -0.757 -0.467\n";
+        let molecule: XYZMolecule = contents.parse().unwrap();
+        assert_eq!(molecule.name, "Water");
+        assert_eq!(molecule.atoms.len(), 3);
+        assert_abs_diff_eq!(molecule.atoms[1].1, Point3::new(0., 0.757, -0.467));
+    }
+
+    #[test]
+    fn parse_wrong_count() {
+        let contents = "3\nWater\nO 0.0 0.0 0.117\nH 0.0 0.757 -0.467\n";
+        assert!(contents.parse::<XYZMolecule>().is_err());
+    }
+
+    #[test]
+    fn parse_invalid_coordinates() {
+        assert!("1\n\nC 0.0 zero 0.0\n".parse::<XYZMolecule>().is_err());
+    }
+
+    #[test]
+    fn element_lookup() {
+        let hydrogen = element_parameters("h").unwrap();
+        assert_eq!(hydrogen.symbol, "H");
+        assert_abs_diff_eq!(hydrogen.sigma * 2_f64.powf(1. / 6.), 2.886);
+        assert!(element_parameters("Xx").is_err());
+    }
+
+    #[test]
+    fn projection_preserves_distances() {
+        let molecule = benzene();
+        let projected = molecule.project();
+        assert_abs_diff_eq!(molecule.planarity(), 0., epsilon = 1e-12);
+        for (i, j) in iproduct!(0..projected.len(), 0..projected.len()) {
+            assert_abs_diff_eq!(
+                nalgebra::distance(&projected[i], &projected[j]),
+                nalgebra::distance(&molecule.atoms[i].1, &molecule.atoms[j].1),
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    /// Twice the signed area of the triangle, which is positive when anticlockwise
+    fn orientation(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> f64 {
+        (b - a).perp(&(c - a))
+    }
+
+    /// A chiral molecule lying in the xy plane and its mirror image
+    fn chiral() -> (XYZMolecule, XYZMolecule) {
+        let positions = [(0., 0.), (3., 0.), (3., 1.), (-1., 0.5)];
+        let molecule = |sign: f64| XYZMolecule {
+            name: String::from("Chiral"),
+            atoms: positions
+                .iter()
+                .map(|&(x, y)| (String::from("C"), Point3::new(x, sign * y, 0.)))
+                .collect(),
+        };
+        (molecule(1.), molecule(-1.))
+    }
+
+    #[test]
+    fn projection_handedness() {
+        let (molecule, mirror) = chiral();
+        let projected = molecule.project();
+        assert!(orientation(projected[0], projected[1], projected[2]) > 0.);
+        let projected = mirror.project();
+        assert!(orientation(projected[0], projected[1], projected[2]) < 0.);
+    }
+
+    #[test]
+    fn projection_rotation_invariant() {
+        let (molecule, _) = chiral();
+        let expected = molecule.project();
+        for angle in &[0.3, 1.5, 2.9, -2.] {
+            let rotation = nalgebra::Rotation3::from_axis_angle(&Vector3::z_axis(), *angle);
+            let rotated = XYZMolecule {
+                name: molecule.name.clone(),
+                atoms: molecule
+                    .atoms
+                    .iter()
+                    .map(|(s, p)| (s.clone(), rotation * p))
+                    .collect(),
+            };
+            for (p, e) in rotated.project().iter().zip(expected.iter()) {
+                assert_abs_diff_eq!(p, e, epsilon = 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn planarity() {
+        let mut molecule = benzene();
+        molecule
+            .atoms
+            .push((String::from("C"), Point3::new(5., 5., 5.)));
+        assert!(molecule.planarity() > 0.1);
+    }
+
+    #[test]
+    fn molecular_shape() {
+        let shape = benzene().to_molecular_shape().unwrap();
+        assert_eq!(shape.items.len(), 12);
+        assert_abs_diff_eq!(shape.items[0].radius, 1.70);
+        assert_abs_diff_eq!(shape.items[1].radius, 1.20);
+        assert_abs_diff_eq!(shape.centroid(), Point2::origin(), epsilon = 1e-10);
+        assert_abs_diff_eq!(shape.enclosing_radius(), 2.48 + 1.20, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn lj_shape() {
+        let shape = benzene().to_lj_shape().unwrap();
+        let carbon = element_parameters("C").unwrap();
+        assert_abs_diff_eq!(shape.items[0].sigma, carbon.sigma);
+        assert_abs_diff_eq!(shape.items[0].epsilon, carbon.epsilon);
+        assert_abs_diff_eq!(shape.centroid(), Point2::origin(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn unknown_element() {
+        let molecule: XYZMolecule = "1\n\nXx 0 0 0".parse().unwrap();
+        assert!(molecule.to_molecular_shape().is_err());
+    }
+}
@@ -6,37 +6,40 @@
 
 use std::f64::consts::PI;
 
+use nalgebra::{Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
-use crate::basis::{SharedValue, StandardBasis};
 use crate::wallpaper::WyckoffSite;
-use crate::Transform2;
+use crate::{Cell2, Parameters, Transform2};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OccupiedSite {
     wyckoff: WyckoffSite,
-    x: SharedValue,
-    y: SharedValue,
-    angle: SharedValue,
+    x: f64,
+    y: f64,
+    angle: f64,
+    /// The index of the shape occupying the site, for states containing multiple shapes
+    #[serde(default)]
+    shape: usize,
+    /// Whether the shapes on this site are the mirror image of the shape
+    #[serde(default)]
+    mirrored: bool,
 }
 
-impl Clone for OccupiedSite {
-    fn clone(&self) -> Self {
-        OccupiedSite {
-            wyckoff: self.wyckoff.clone(),
-            x: SharedValue::new(self.x.get_value()),
-            y: SharedValue::new(self.y.get_value()),
-            angle: SharedValue::new(self.angle.get_value()),
-        }
-    }
+/// Move a fractional coordinate into the range [-0.5, 0.5)
+fn wrap_position(value: f64) -> f64 {
+    (value + 0.5).rem_euclid(1.) - 0.5
 }
 
 impl OccupiedSite {
     pub fn transform(&self) -> Transform2 {
-        Transform2::new(
-            self.angle.get_value(),
-            (self.x.get_value(), self.y.get_value()),
-        )
+        let transform = Transform2::new(self.angle, (self.x, self.y));
+        if self.mirrored {
+            // Reflect the shape before placing it, which keeps the position unchanged
+            transform * Transform2::reflection(PI / 2.)
+        } else {
+            transform
+        }
     }
 
     pub fn positions<'a>(&'a self) -> impl Iterator<Item = Transform2> + 'a {
@@ -47,43 +50,162 @@ impl OccupiedSite {
     }
 
     pub fn multiplicity(&self) -> usize {
-        self.wyckoff.symmetries.len() as usize
+        self.wyckoff.symmetries.len()
     }
 
     pub fn from_wyckoff(wyckoff: &WyckoffSite) -> Self {
+        Self::from_wyckoff_spread(wyckoff, 0, 1)
+    }
+
+    /// Create the site at `index` of `count` sites sharing the same Wyckoff site
+    ///
+    /// The initial positions of the sites are spread evenly along the x axis of the cell, so
+    /// all the sites can be occupied at the same time without the shapes overlapping.
+    ///
+    pub fn from_wyckoff_spread(wyckoff: &WyckoffSite, index: usize, count: usize) -> Self {
         let position = -0.5 + 0.5 / wyckoff.multiplicity() as f64;
-        let x = SharedValue::new(position);
-        let y = SharedValue::new(position);
-        let angle = SharedValue::new(0.);
+        let x = wrap_position(position + index as f64 / count as f64);
+        let y = position;
+        let angle = 0.;
 
         OccupiedSite {
             wyckoff: wyckoff.clone(),
             x,
             y,
             angle,
+            shape: 0,
+            mirrored: false,
+        }
+    }
+
+    /// Create the sites for a collection of shapes, each paired with the Wyckoff site it occupies
+    ///
+    /// The initial positions of all the sites are spread out across the cell, so none of the
+    /// shapes overlap.
+    ///
+    pub fn from_components(sites: &[(usize, WyckoffSite)]) -> Vec<Self> {
+        sites
+            .iter()
+            .enumerate()
+            .map(|(index, (shape, wyckoff))| {
+                Self::from_wyckoff_spread(wyckoff, index, sites.len()).with_shape(*shape)
+            })
+            .collect()
+    }
+
+    /// Set the index of the shape which occupies this site
+    pub fn with_shape(mut self, shape: usize) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Create a copy of this site occupied by the mirror image of the shape
+    ///
+    /// The initial position is offset along x from this site by half the `spacing` between the
+    /// sites spread out by `from_wyckoff_spread`, so the copy lies midway between two sites and
+    /// all of them can be occupied at the same time without overlapping.
+    ///
+    pub fn mirror_image(&self, spacing: f64) -> Self {
+        OccupiedSite {
+            x: wrap_position(self.x + spacing / 2.),
+            mirrored: !self.mirrored,
+            ..self.clone()
+        }
+    }
+
+    pub fn wyckoff(&self) -> &WyckoffSite {
+        &self.wyckoff
+    }
+
+    pub fn shape(&self) -> usize {
+        self.shape
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        self.mirrored
+    }
+
+    /// The values of the site which are able to be changed, along with their bounds
+    ///
+    /// These are the fractional coordinates and the angle of the shape, for each of the
+    /// degrees of freedom of the Wyckoff site.
+    ///
+    pub fn parameters(&self) -> Parameters {
+        let dof = self.wyckoff.degrees_of_freedom();
+        let mut parameters = Parameters::new();
+
+        if dof[0] {
+            parameters.push(self.x, -0.5, 0.5);
         }
+        if dof[1] {
+            parameters.push(self.y, -0.5, 0.5);
+        }
+        if dof[2] {
+            parameters.push(self.angle, 0., 2. * PI);
+        }
+        parameters
+    }
+
+    /// The number of values in `parameters`
+    pub fn num_parameters(&self) -> usize {
+        self.wyckoff
+            .degrees_of_freedom()
+            .iter()
+            .filter(|&&dof| dof)
+            .count()
     }
 
-    pub fn get_basis(&self, rot_symmetry: u64) -> Vec<StandardBasis> {
-        let mut basis: Vec<StandardBasis> = vec![];
+    /// Update the site from `values`, which are in the same order as `parameters`
+    pub fn set_parameters(&mut self, values: &[f64]) {
         let dof = self.wyckoff.degrees_of_freedom();
+        let mut values = values.iter().cloned();
+        let mut next = || values.next().expect("Missing a value for the site");
 
         if dof[0] {
-            basis.push(StandardBasis::new(&self.x, -0.5, 0.5));
+            self.x = next();
         }
         if dof[1] {
-            basis.push(StandardBasis::new(&self.y, -0.5, 0.5));
+            self.y = next();
         }
         if dof[2] {
-            basis.push(StandardBasis::new(
-                &self.angle,
-                0.,
-                2. * PI / rot_symmetry as f64,
-            ));
+            self.angle = next();
         }
-        basis
     }
-    pub fn symmetries<'a>(&'a self) -> impl Iterator<Item = &Transform2> + 'a {
+
+    /// The derivatives of the Cartesian position of a point on a shape occupying the site
+    ///
+    /// The shape is placed using the `symmetry`, one of the symmetry operations of the site,
+    /// with `offset` being the vector from the center of the shape to the point in Cartesian
+    /// coordinates. There is a derivative for each of the values in `parameters`, in the same
+    /// order.
+    ///
+    pub fn point_derivatives(
+        &self,
+        symmetry: &Transform2,
+        cell: &Cell2,
+        offset: Vector2<f64>,
+    ) -> Vec<Vector2<f64>> {
+        let dof = self.wyckoff.degrees_of_freedom();
+        let to_cartesian = |v: Vector2<f64>| cell.to_cartesian_point(Point2::from(v)).coords;
+        let mut derivatives = vec![];
+        if dof[0] {
+            derivatives.push(to_cartesian(symmetry * Vector2::x()));
+        }
+        if dof[1] {
+            derivatives.push(to_cartesian(symmetry * Vector2::y()));
+        }
+        if dof[2] {
+            // Rotating the shape rotates the offset before the symmetry operation is applied
+            let inverse = symmetry
+                .inverse()
+                .expect("Symmetry operations are always invertible");
+            let local = inverse * offset;
+            derivatives.push(symmetry * Vector2::new(-local.y, local.x));
+        }
+        derivatives
+    }
+
+    pub fn symmetries<'a>(&'a self) -> impl Iterator<Item = &'a Transform2> + 'a {
         self.wyckoff.symmetries.iter()
     }
 }
@@ -0,0 +1,385 @@
+//
+// hybrid.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::type_repetition_in_bounds)]
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+use svg::Document;
+
+use super::potential::IMAGE_WEIGHT;
+use super::PackedState;
+use crate::traits::*;
+use crate::wallpaper::WallpaperGroup;
+use crate::{NeighbourList, Parameters, Transform2};
+
+pub type HybridState2<S> = HybridState<S>;
+
+/// The attraction between two hard shapes which aren't overlapping
+///
+/// The energy depends on the separation between the surfaces of the two shapes, with the
+/// deepest part of the well being when the shapes are in contact.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AttractiveWell {
+    /// A constant energy of `-depth` for shapes separated by less than `width`
+    SquareWell { width: f64, depth: f64 },
+    /// The attractive tail of the Lennard Jones potential
+    ///
+    /// The minimum of the potential, with an energy of `-epsilon`, is at contact, with the
+    /// potential shifted so the energy is zero at a separation of `cutoff`.
+    ///
+    LennardJones {
+        sigma: f64,
+        epsilon: f64,
+        cutoff: f64,
+    },
+}
+
+impl Default for AttractiveWell {
+    fn default() -> Self {
+        AttractiveWell::SquareWell {
+            width: 0.5,
+            depth: 1.,
+        }
+    }
+}
+
+impl AttractiveWell {
+    /// The separation beyond which the energy is zero
+    pub fn range(&self) -> f64 {
+        match self {
+            AttractiveWell::SquareWell { width, .. } => *width,
+            AttractiveWell::LennardJones { cutoff, .. } => *cutoff,
+        }
+    }
+
+    /// The energy of two shapes with surfaces separated by `separation`
+    pub fn energy(&self, separation: f64) -> f64 {
+        if separation >= self.range() {
+            return 0.;
+        }
+        match self {
+            AttractiveWell::SquareWell { depth, .. } => -depth,
+            AttractiveWell::LennardJones {
+                sigma,
+                epsilon,
+                cutoff,
+            } => {
+                // Shift the separation so contact is at the minimum of the potential
+                let r_min = 2_f64.powf(1. / 6.) * sigma;
+                let lj = |r: f64| {
+                    let sigma6 = (sigma / r).powi(6);
+                    4. * epsilon * (sigma6.powi(2) - sigma6)
+                };
+                lj(separation.max(0.) + r_min) - lj(cutoff + r_min)
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        let valid = match *self {
+            AttractiveWell::SquareWell { width, depth } => width > 0. && depth >= 0.,
+            AttractiveWell::LennardJones {
+                sigma,
+                epsilon,
+                cutoff,
+            } => sigma > 0. && epsilon >= 0. && cutoff > 0.,
+        };
+        if !valid {
+            bail!("Invalid parameters for the attractive well {}", self)
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AttractiveWell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttractiveWell::SquareWell { width, depth } => {
+                write!(f, "SquareWell {{ {}, {} }}", width, depth)
+            }
+            AttractiveWell::LennardJones {
+                sigma,
+                epsilon,
+                cutoff,
+            } => write!(f, "LennardJones {{ {}, {}, {} }}", sigma, epsilon, cutoff),
+        }
+    }
+}
+
+/// A state of hard shapes which also attract each other
+///
+/// Like the `PackedState`, configurations where the shapes overlap are invalid. The valid
+/// configurations are scored by the negative energy of the attraction between the shapes,
+/// along with a weighting of the packing fraction. This models real crystals, where both
+/// the shape of the molecules and the attractions between them influence the packing.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    /// The positions of the shapes, which determines whether there are any overlaps
+    pub packed: PackedState<S>,
+    pub well: AttractiveWell,
+    /// The contribution of the packing fraction to the score
+    #[serde(default)]
+    pub density_weight: f64,
+}
+
+impl<S> Eq for HybridState<S> where S: Shape + Intersect + Separation {}
+
+impl<S> PartialEq for HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+// Invalid states can't be compared, so the ordering isn't the same as `cmp`
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl<S> PartialOrd for HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other)
+            .expect("Unable to compare states with invalid scores")
+    }
+}
+
+impl<S> State for HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    fn score(&self) -> Option<f64> {
+        let packing_fraction = self.packed.score()?;
+        Some(-self.energy() / self.total_shapes() as f64 + self.density_weight * packing_fraction)
+    }
+
+    fn parameters(&self) -> Parameters {
+        self.packed.parameters()
+    }
+
+    fn set_parameters(&mut self, values: &[f64]) {
+        self.packed.set_parameters(values)
+    }
+
+    fn total_shapes(&self) -> usize {
+        self.packed.total_shapes()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        self.packed.as_positions()
+    }
+}
+
+impl<S> ToSVG for HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        self.packed.as_svg()
+    }
+}
+
+impl<S> HybridState<S>
+where
+    S: Shape + Intersect + Separation,
+{
+    /// Add an attraction between the shapes of a packed state
+    pub fn new(packed: PackedState<S>, well: AttractiveWell) -> Result<Self, Error> {
+        well.check()?;
+        Ok(Self {
+            packed,
+            well,
+            density_weight: 0.,
+        })
+    }
+
+    pub fn from_group(
+        shape: S,
+        group: &WallpaperGroup,
+        well: AttractiveWell,
+    ) -> Result<Self, Error> {
+        Self::new(PackedState::from_group(shape, group)?, well)
+    }
+
+    /// Include the packing fraction in the score, multiplied by `weight`
+    pub fn with_density_weight(self, weight: f64) -> Result<Self, Error> {
+        if !weight.is_finite() {
+            bail!("The density weight needs to be finite, found {}", weight)
+        }
+        Ok(Self {
+            density_weight: weight,
+            ..self
+        })
+    }
+
+    /// The total energy of the attractions of the shapes within the unit cell
+    ///
+    /// This only considers the pairs of shapes which are within the range of the well, which
+    /// is found from the enclosing radius of each shape. The nearby pairs are found using a
+    /// `NeighbourList`, with pairs within the unit cell included once. Pairs with a periodic
+    /// image are found from both of the shapes, so each contributes `IMAGE_WEIGHT` of its
+    /// energy, giving the energy of the unit cell within the lattice.
+    ///
+    pub fn energy(&self) -> f64 {
+        let state = &self.packed;
+        let range = self.well.range();
+        let positions: Vec<(usize, Transform2)> = state.site_positions().collect();
+        let shapes: Vec<(Transform2, S)> = positions
+            .iter()
+            .map(|(index, position)| {
+                let transform = state.cell.to_cartesian_isometry(*position);
+                (transform, state.shapes[*index].transform(&transform))
+            })
+            .collect();
+        let radii: Vec<f64> = state.shapes.iter().map(Shape::enclosing_radius).collect();
+        let max_radius = radii.iter().cloned().fold(0., f64::max);
+
+        let points: Vec<_> = positions.iter().map(|(_, p)| p.position()).collect();
+        let neighbours = NeighbourList::new(&state.cell, &points, 2. * max_radius + range);
+        let mut sum = 0.;
+        for (index1, (transform1, shape1)) in shapes.iter().enumerate() {
+            for (index2, (x, y)) in neighbours
+                .neighbours(index1)
+                .filter(|&(index2, image)| image != (0, 0) || index1 < index2)
+            {
+                let (shape_index1, shape_index2) = (positions[index1].0, positions[index2].0);
+                let transform2 = state.cell.to_cartesian_translate(positions[index2].1, x, y);
+                let distance = (transform1.position() - transform2.position()).norm();
+                if distance >= radii[shape_index1] + radii[shape_index2] + range {
+                    continue;
+                }
+                sum += if x == 0 && y == 0 {
+                    self.well.energy(shape1.separation(&shapes[index2].1))
+                } else {
+                    let shape2 = state.shapes[shape_index2].transform(&transform2);
+                    IMAGE_WEIGHT * self.well.energy(shape1.separation(&shape2))
+                };
+            }
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::wallpaper::{get_wallpaper_group, WallpaperGroups};
+    use crate::{Cell2, CrystalFamily, MolecularShape2};
+
+    fn circle_state(well: AttractiveWell, side: f64) -> HybridState<MolecularShape2> {
+        let group = get_wallpaper_group(WallpaperGroups::p1).unwrap();
+        let mut state = HybridState::from_group(MolecularShape2::circle(), &group, well).unwrap();
+        state.packed.cell = Cell2::from_family(CrystalFamily::Tetragonal, side);
+        state
+    }
+
+    #[test]
+    fn square_well_energy() {
+        let well = AttractiveWell::SquareWell {
+            width: 0.5,
+            depth: 1.,
+        };
+        assert_abs_diff_eq!(well.energy(0.), -1.);
+        assert_abs_diff_eq!(well.energy(0.49), -1.);
+        assert_abs_diff_eq!(well.energy(0.5), 0.);
+    }
+
+    #[test]
+    fn lennard_jones_energy() {
+        let well = AttractiveWell::LennardJones {
+            sigma: 1.,
+            epsilon: 1.,
+            cutoff: 10.,
+        };
+        assert_abs_diff_eq!(well.energy(0.), -1., epsilon = 1e-3);
+        assert!(well.energy(0.5) > well.energy(0.));
+        assert_abs_diff_eq!(well.energy(10.), 0.);
+    }
+
+    #[test]
+    fn invalid_well() {
+        let group = get_wallpaper_group(WallpaperGroups::p1).unwrap();
+        let well = AttractiveWell::SquareWell {
+            width: -1.,
+            depth: 1.,
+        };
+        assert!(HybridState::from_group(MolecularShape2::circle(), &group, well).is_err());
+    }
+
+    #[test]
+    fn square_lattice() {
+        // Each circle has four periodic neighbours within the well, each bond shared by two
+        let state = circle_state(AttractiveWell::default(), 2.1);
+        assert_abs_diff_eq!(state.score().unwrap(), 2.);
+        // Beyond the range of the well there is no attraction
+        let state = circle_state(AttractiveWell::default(), 2.6);
+        assert_abs_diff_eq!(state.score().unwrap(), 0.);
+    }
+
+    #[test]
+    fn long_range_well() {
+        // The well extends beyond three periodic images of the cell
+        let well = AttractiveWell::LennardJones {
+            sigma: 1.,
+            epsilon: 1.,
+            cutoff: 8.,
+        };
+        let state = circle_state(well, 2.1);
+        let expected: f64 = iproduct!(-5..=5, -5..=5)
+            .filter(|&image| image != (0, 0))
+            .map(|(x, y)| 2.1 * f64::from(x * x + y * y).sqrt() - 2.)
+            .filter(|&separation| separation < 8.)
+            .map(|separation| IMAGE_WEIGHT * well.energy(separation))
+            .sum();
+        assert_abs_diff_eq!(state.energy(), expected, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn overlap_invalid() {
+        let state = circle_state(AttractiveWell::default(), 1.9);
+        assert!(state.score().is_none());
+    }
+
+    #[test]
+    fn density_weight() {
+        let state = circle_state(AttractiveWell::default(), 2.1)
+            .with_density_weight(2.)
+            .unwrap();
+        let packing_fraction = state.packed.score().unwrap();
+        assert_abs_diff_eq!(state.score().unwrap(), 2. + 2. * packing_fraction);
+        assert!(state.with_density_weight(f64::NAN).is_err());
+    }
+}
@@ -4,8 +4,217 @@
 // Distributed under terms of the MIT license.
 //
 
+pub mod hybrid;
 pub mod packed;
 pub mod potential;
 
+pub use hybrid::*;
 pub use packed::*;
 pub use potential::*;
+
+use std::ops::Range;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Deserializer};
+
+use crate::wallpaper::{WallpaperGroup, WyckoffSite};
+use crate::{Cell2, NeighbourList, OccupiedSite, Parameters, Transform2};
+
+/// Read the shapes of a state, which were previously stored as a single `shape`
+///
+/// States containing a single shape were written with the field `shape` holding that shape,
+/// rather than a list of shapes, so both forms are accepted.
+///
+fn deserialize_shapes<'de, D, S>(deserializer: D) -> Result<Vec<S>, D::Error>
+where
+    D: Deserializer<'de>,
+    S: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<S> {
+        Many(Vec<S>),
+        One(S),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(shapes) => shapes,
+        OneOrMany::One(shape) => vec![shape],
+    })
+}
+
+/// The parameters of the cell followed by those of each of the sites
+fn state_parameters(cell: &Cell2, sites: &[OccupiedSite]) -> Parameters {
+    let mut parameters = cell.parameters();
+    for site in sites {
+        parameters.append(site.parameters());
+    }
+    parameters
+}
+
+/// Update the cell and the sites from `values`, in the same order as `state_parameters`
+///
+/// This gives the indices of the sites with values which have changed.
+///
+fn set_state_parameters(
+    cell: &mut Cell2,
+    sites: &mut [OccupiedSite],
+    values: &[f64],
+) -> Vec<usize> {
+    let (cell_values, mut values) = values.split_at(cell.num_parameters());
+    cell.set_parameters(cell_values);
+    let mut moved = vec![];
+    for (index, site) in sites.iter_mut().enumerate() {
+        let (site_values, remaining) = values.split_at(site.num_parameters());
+        if site.parameters().values() != site_values {
+            site.set_parameters(site_values);
+            moved.push(index);
+        }
+        values = remaining;
+    }
+    moved
+}
+
+/// The positions of the shapes within a state, along with the neighbours of each shape
+///
+/// Finding the positions and neighbours of every shape is most of the work of scoring the move
+/// of a single site, so these are kept between moves, with only the positions of the moved
+/// sites updated. The neighbours are only valid for the cell and range they were found with,
+/// with a range of None having every shape interact.
+///
+#[derive(Clone, Debug)]
+struct Neighbours {
+    /// The index of the shape at each position along with the relative position
+    positions: Vec<(usize, Transform2)>,
+    list: Option<NeighbourList>,
+    cell: Cell2,
+    range: Option<f64>,
+}
+
+impl Neighbours {
+    fn new(cell: &Cell2, sites: &[OccupiedSite], range: Option<f64>) -> Self {
+        let positions: Vec<(usize, Transform2)> = sites
+            .iter()
+            .flat_map(|site| site.positions().map(move |p| (site.shape(), p)))
+            .collect();
+        let list = range.map(|range| {
+            let points: Vec<_> = positions.iter().map(|(_, p)| p.position()).collect();
+            NeighbourList::new(cell, &points, range)
+        });
+        Self {
+            positions,
+            list,
+            cell: cell.clone(),
+            range,
+        }
+    }
+
+    /// Update the neighbours after setting the parameters of a state
+    ///
+    /// When the cell and range are unchanged, only the positions of the `moved` sites are
+    /// updated, otherwise the neighbours are found again.
+    ///
+    fn update(
+        neighbours: Option<Self>,
+        cell: &Cell2,
+        sites: &[OccupiedSite],
+        moved: &[usize],
+        range: Option<f64>,
+    ) -> Self {
+        let mut neighbours = match neighbours {
+            Some(n) if n.is_valid(cell, range) => n,
+            _ => return Self::new(cell, sites, range),
+        };
+        for &site in moved {
+            let start: usize = sites[..site].iter().map(OccupiedSite::multiplicity).sum();
+            for (index, position) in (start..).zip(sites[site].positions()) {
+                neighbours.positions[index].1 = position;
+                if let Some(list) = &mut neighbours.list {
+                    list.update(cell, index, position.position());
+                }
+            }
+        }
+        neighbours
+    }
+
+    /// The positions along with the list of neighbours, for states where the range is finite
+    fn positions_list(&self) -> (&[(usize, Transform2)], &NeighbourList) {
+        let list = self
+            .list
+            .as_ref()
+            .expect("The neighbours need a finite range");
+        (&self.positions, list)
+    }
+
+    /// Whether the neighbours were found within the `cell` over the `range`
+    fn is_valid(&self, cell: &Cell2, range: Option<f64>) -> bool {
+        self.cell == *cell && self.range == range
+    }
+}
+
+/// The positions modified by the parameter at `index`, as a range of the positions of the state
+///
+/// The parameters of a state are those of the cell followed by those of each site, with the
+/// positions of each site following each other. Modifying the cell changes every position, so
+/// this is None for the parameters of the cell. This is also None for a state with a single
+/// site, where finding the complete score is less work than the contributions before and after
+/// the move.
+///
+fn parameter_positions(cell: &Cell2, sites: &[OccupiedSite], index: usize) -> Option<Range<usize>> {
+    let mut start_parameter = cell.num_parameters();
+    if index < start_parameter || sites.len() < 2 {
+        return None;
+    }
+    let mut start_position = 0;
+    for site in sites {
+        if index < start_parameter + site.num_parameters() {
+            return Some(start_position..start_position + site.multiplicity());
+        }
+        start_parameter += site.num_parameters();
+        start_position += site.multiplicity();
+    }
+    None
+}
+
+/// Ensure each site refers to a valid shape, and every shape occupies at least one site
+fn check_components(num_shapes: usize, sites: &[(usize, WyckoffSite)]) -> Result<(), Error> {
+    if let Some((index, _)) = sites.iter().find(|(index, _)| *index >= num_shapes) {
+        bail!(
+            "A site is occupied by shape {}, however there are only {} shapes",
+            index,
+            num_shapes
+        )
+    }
+    if let Some(index) = (0..num_shapes).find(|i| sites.iter().all(|(index, _)| index != i)) {
+        bail!("The shape {} doesn't occupy any sites", index)
+    }
+    Ok(())
+}
+
+/// The general positions occupied by each component for a given stoichiometry
+fn stoichiometry_sites<S>(
+    components: &[(S, usize)],
+    group: &WallpaperGroup,
+) -> Result<Vec<(usize, WyckoffSite)>, Error> {
+    if components.is_empty() {
+        bail!("At least one shape is required to create a state")
+    }
+    if components.iter().any(|(_, count)| *count == 0) {
+        bail!("Each component of the state requires at least one site")
+    }
+    let wyckoff = WyckoffSite::new(group)?;
+    Ok(components
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, count))| vec![(index, wyckoff.clone()); *count])
+        .collect())
+}
+
+/// The number of each of the shapes within the unit cell
+fn stoichiometry(num_shapes: usize, sites: &[OccupiedSite]) -> Vec<usize> {
+    let mut counts = vec![0; num_shapes];
+    for site in sites {
+        counts[site.shape()] += site.multiplicity();
+    }
+    counts
+}
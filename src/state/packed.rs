@@ -6,18 +6,23 @@
 
 #![allow(clippy::type_repetition_in_bounds)]
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::f64::consts::PI;
 use std::fmt::Write;
-use std::ops::Mul;
+use std::ops::Range;
 
-use anyhow::Error;
+use anyhow::{bail, Error};
 use log::debug;
 use serde::{Deserialize, Serialize};
 
+use super::{
+    check_components, deserialize_shapes, parameter_positions, set_state_parameters,
+    state_parameters, stoichiometry, stoichiometry_sites, Neighbours,
+};
 use crate::traits::*;
 use crate::wallpaper::{Wallpaper, WallpaperGroup, WyckoffSite};
-use crate::{Cell2, OccupiedSite, StandardBasis, Transform2};
+use crate::{Cell2, OccupiedSite, Parameters, Transform2};
 
 pub type PackedState2<S> = PackedState<S>;
 
@@ -27,9 +32,18 @@ where
     S: Shape + Intersect,
 {
     pub wallpaper: Wallpaper,
-    pub shape: S,
+    /// The shapes within the state, with each occupied site referring to one of these shapes
+    #[serde(
+        alias = "shape",
+        deserialize_with = "deserialize_shapes",
+        bound(deserialize = "S: Deserialize<'de>")
+    )]
+    pub shapes: Vec<S>,
     pub cell: Cell2,
     occupied_sites: Vec<OccupiedSite>,
+    /// The neighbours of each shape, kept between moves of the sites
+    #[serde(skip)]
+    neighbours: Option<Neighbours>,
 }
 
 impl<S> Eq for PackedState<S> where S: Shape + Intersect {}
@@ -46,6 +60,8 @@ where
     }
 }
 
+// Invalid states can't be compared, so the ordering isn't the same as `cmp`
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl<S> PartialOrd for PackedState<S>
 where
     S: Shape + Intersect,
@@ -63,7 +79,8 @@ where
     S: Shape + Intersect,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.partial_cmp(other)
+            .expect("Unable to compare states with invalid scores")
     }
 }
 
@@ -81,17 +98,39 @@ where
         if self.check_intersection() {
             None
         } else {
-            Some((self.shape.area() * self.total_shapes() as f64) / self.cell.area())
+            Some(self.packing_fraction())
         }
     }
 
-    fn generate_basis(&self) -> Vec<StandardBasis> {
-        let mut basis: Vec<StandardBasis> = vec![];
-        basis.append(&mut self.cell.get_degrees_of_freedom());
-        for site in self.occupied_sites.iter() {
-            basis.append(&mut site.get_basis(1));
+    fn parameter_score(&self, index: usize) -> Option<Option<f64>> {
+        let moved = parameter_positions(&self.cell, &self.occupied_sites, index)?;
+        if self.check_moved_intersection(moved) {
+            Some(None)
+        } else {
+            // The packing fraction only depends on the cell
+            Some(Some(0.))
         }
-        basis
+    }
+
+    /// A valid state has no intersections, so there is nothing to check before a move
+    fn parameter_score_before(&self, index: usize) -> Option<Option<f64>> {
+        parameter_positions(&self.cell, &self.occupied_sites, index)?;
+        Some(Some(0.))
+    }
+
+    fn parameters(&self) -> Parameters {
+        state_parameters(&self.cell, &self.occupied_sites)
+    }
+
+    fn set_parameters(&mut self, values: &[f64]) {
+        let moved = set_state_parameters(&mut self.cell, &mut self.occupied_sites, values);
+        self.neighbours = Some(Neighbours::update(
+            self.neighbours.take(),
+            &self.cell,
+            &self.occupied_sites,
+            &moved,
+            Some(self.neighbour_range()),
+        ));
     }
 
     fn as_positions(&self) -> Result<String, Error> {
@@ -118,44 +157,73 @@ where
         self.occupied_sites.iter().flat_map(OccupiedSite::positions)
     }
 
-    /// Check for intersections of shapes in the current state.
+    /// The index of the shape at each position along with the relative position
+    pub fn site_positions<'a>(&'a self) -> impl Iterator<Item = (usize, Transform2)> + 'a {
+        self.occupied_sites
+            .iter()
+            .flat_map(|site| site.positions().map(move |p| (site.shape(), p)))
+    }
+
+    /// The fraction of the area of the unit cell covered by the shapes
+    fn packing_fraction(&self) -> f64 {
+        let area: f64 = self
+            .occupied_sites
+            .iter()
+            .map(|site| self.shapes[site.shape()].area() * site.multiplicity() as f64)
+            .sum();
+        area / self.cell.area()
+    }
+
+    /// The score found by checking every pair of shapes within the nearby periodic images
     ///
-    /// This checks for intersections between any shapes, checking all occupied sites and their
-    /// symmetry defined copies for the current cell and the neighbouring cells. Checking the
-    /// neighbouring cells ensures there are no intersections of when tiling space.
+    /// This gives the same score without using a `NeighbourList`, which allows comparing the
+    /// performance of the two approaches.
     ///
-    fn check_intersection(&self) -> bool {
+    #[doc(hidden)]
+    pub fn score_all_pairs(&self) -> Option<f64> {
+        if self.check_intersection_all_pairs() {
+            None
+        } else {
+            Some(self.packing_fraction())
+        }
+    }
+
+    /// Check for intersections by comparing each shape with every shape in the nearby images
+    ///
+    /// The number of periodic images compared depends on how far the cell is from a square.
+    ///
+    fn check_intersection_all_pairs(&self) -> bool {
         let periodic_range = match (self.cell.a() / self.cell.b(), self.cell.angle()) {
             (p, a) if 0.5 < p && p < 2. && f64::abs(a - PI / 2.) < 0.2 => 1,
             (p, a) if 0.3 < p && p < 3. && f64::abs(a - PI / 2.) < 0.5 => 2,
             _ => 3,
         };
+        let shapes: Vec<(usize, Transform2, S)> = self
+            .site_positions()
+            .map(|(index, position)| {
+                let transform = self.cell.to_cartesian_isometry(position);
+                (index, transform, self.shapes[index].transform(&transform))
+            })
+            .collect();
+        let radii: Vec<f64> = self.shapes.iter().map(Shape::enclosing_radius).collect();
+
         // Compare within the current cell
-        for (index, shape1) in self
-            .cartesian_positions()
-            .map(|p| self.shape.transform(&p))
-            .enumerate()
-        {
-            for shape2 in self
-                .cartesian_positions()
-                .map(|p| self.shape.transform(&p))
-                .skip(index + 1)
-            {
-                if shape1.intersects(&shape2) {
+        for (index, (_, _, shape1)) in shapes.iter().enumerate() {
+            for (_, _, shape2) in shapes.iter().skip(index + 1) {
+                if shape1.intersects(shape2) {
                     return true;
                 }
             }
         }
 
-        let radius_sq = self.shape.enclosing_radius().mul(2.).powi(2);
         // Compare in periodic cells
-        for transform1 in self.cartesian_positions() {
-            let shape1 = self.shape.transform(&transform1);
-            for position in self.relative_positions() {
+        for (index1, transform1, shape1) in shapes.iter() {
+            for (index2, position) in self.site_positions() {
+                let radius_sq = (radii[*index1] + radii[index2]).powi(2);
                 for transform2 in self.cell.periodic_images(position, periodic_range, false) {
                     let distance = (transform1.position() - transform2.position()).norm_squared();
                     if distance <= radius_sq {
-                        let shape2 = self.shape.transform(&transform2);
+                        let shape2 = self.shapes[index2].transform(&transform2);
                         if shape1.intersects(&shape2) {
                             return true;
                         }
@@ -166,38 +234,259 @@ where
         false
     }
 
+    /// Check for intersections of shapes in the current state.
+    ///
+    /// This checks for intersections between any shapes, checking all occupied sites and their
+    /// symmetry defined copies for the current cell and the neighbouring cells. Checking the
+    /// neighbouring cells ensures there are no intersections of when tiling space. Only the
+    /// pairs of shapes close enough for their enclosing circles to overlap are compared, which
+    /// are found using a `NeighbourList`.
+    ///
+    fn check_intersection(&self) -> bool {
+        let cache = self.neighbours();
+        let (positions, neighbours) = cache.positions_list();
+        let shapes: Vec<(Transform2, S)> = positions
+            .iter()
+            .map(|(index, position)| {
+                let transform = self.cell.to_cartesian_isometry(*position);
+                (transform, self.shapes[*index].transform(&transform))
+            })
+            .collect();
+        let radii: Vec<f64> = self.shapes.iter().map(Shape::enclosing_radius).collect();
+        for (index1, (transform1, shape1)) in shapes.iter().enumerate() {
+            // Each pair only needs to be checked once, so it is checked from the shape with the
+            // smaller index, with a shape checked against the positive images of itself.
+            for (index2, (x, y)) in neighbours
+                .neighbours(index1)
+                .filter(|&(index2, image)| index1 < index2 || (index1 == index2 && image > (0, 0)))
+            {
+                if x == 0 && y == 0 {
+                    if shape1.intersects(&shapes[index2].1) {
+                        return true;
+                    }
+                    continue;
+                }
+                let (shape_index1, shape_index2) = (positions[index1].0, positions[index2].0);
+                let transform2 = self.cell.to_cartesian_translate(positions[index2].1, x, y);
+                let distance = (transform1.position() - transform2.position()).norm();
+                if distance <= radii[shape_index1] + radii[shape_index2]
+                    && shape1.intersects(&self.shapes[shape_index2].transform(&transform2))
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Check for intersections involving any of the `moved` shapes
+    ///
+    /// The `moved` shapes are a range of the positions of the state, which are compared to
+    /// every neighbouring shape, stopping at the first intersection. When only the `moved`
+    /// shapes have changed since the state was valid, this finds any intersection in the state.
+    ///
+    fn check_moved_intersection(&self, moved: Range<usize>) -> bool {
+        let cache = self.neighbours();
+        let (positions, neighbours) = cache.positions_list();
+        let radii: Vec<f64> = self.shapes.iter().map(Shape::enclosing_radius).collect();
+        for index1 in moved {
+            let (shape_index1, position1) = positions[index1];
+            let transform1 = self.cell.to_cartesian_isometry(position1);
+            let shape1 = self.shapes[shape_index1].transform(&transform1);
+            for (index2, (x, y)) in neighbours.neighbours(index1) {
+                let shape_index2 = positions[index2].0;
+                let transform2 = self.cell.to_cartesian_translate(positions[index2].1, x, y);
+                let distance = (transform1.position() - transform2.position()).norm();
+                if distance <= radii[shape_index1] + radii[shape_index2]
+                    && shape1.intersects(&self.shapes[shape_index2].transform(&transform2))
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The distance within which shapes are close enough to intersect
+    fn neighbour_range(&self) -> f64 {
+        let max_radius = self
+            .shapes
+            .iter()
+            .map(Shape::enclosing_radius)
+            .fold(0., f64::max);
+        2. * max_radius
+    }
+
+    /// The neighbours of each shape, found again when those kept from the last move are invalid
+    fn neighbours(&self) -> Cow<'_, Neighbours> {
+        let range = Some(self.neighbour_range());
+        match &self.neighbours {
+            Some(neighbours) if neighbours.is_valid(&self.cell, range) => Cow::Borrowed(neighbours),
+            _ => Cow::Owned(Neighbours::new(&self.cell, &self.occupied_sites, range)),
+        }
+    }
+
     pub fn initialise(
         shape: S,
         wallpaper: Wallpaper,
         isopointal: &[WyckoffSite],
     ) -> PackedState<S> {
-        let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
-        let max_cell_size = 4. * shape.enclosing_radius() * num_shapes as f64;
+        Self::initialise_shapes(vec![shape], wallpaper, isopointal)
+    }
 
-        let cell = Cell2::from_family(wallpaper.family, max_cell_size);
+    /// Create a state containing multiple shapes
+    ///
+    /// Each of the `shapes` occupies its own copy of each of the Wyckoff sites in `isopointal`,
+    /// with the initial positions spread out so none of the shapes overlap.
+    ///
+    pub fn initialise_shapes(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        isopointal: &[WyckoffSite],
+    ) -> PackedState<S> {
+        let sites: Vec<_> = (0..shapes.len())
+            .flat_map(|index| isopointal.iter().map(move |w| (index, w.clone())))
+            .collect();
+        Self::from_sites(shapes, wallpaper, OccupiedSite::from_components(&sites))
+    }
 
-        debug!("Cell: {:?}", cell);
+    /// Create a state where each shape occupies the Wyckoff sites it is paired with
+    ///
+    /// Each of the `sites` pairs the index of a shape in `shapes` with a Wyckoff site, allowing a
+    /// shape to occupy multiple sites, or sites of different multiplicities. Every shape has to
+    /// occupy at least one site.
+    ///
+    pub fn initialise_components(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        sites: &[(usize, WyckoffSite)],
+    ) -> Result<PackedState<S>, Error> {
+        check_components(shapes.len(), sites)?;
+        Ok(Self::from_sites(
+            shapes,
+            wallpaper,
+            OccupiedSite::from_components(sites),
+        ))
+    }
 
-        let occupied_sites: Vec<_> = isopointal.iter().map(OccupiedSite::from_wyckoff).collect();
+    fn from_sites(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        occupied_sites: Vec<OccupiedSite>,
+    ) -> PackedState<S> {
+        let cell = Cell2::from_family(
+            wallpaper.family,
+            Self::max_cell_size(&shapes, &occupied_sites),
+        );
+        debug!("Cell: {:?}", cell);
 
         PackedState {
             wallpaper,
-            shape,
+            shapes,
             cell,
             occupied_sites,
+            neighbours: None,
         }
     }
 
+    /// A cell size which is large enough that none of the shapes will overlap
+    fn max_cell_size(shapes: &[S], occupied_sites: &[OccupiedSite]) -> f64 {
+        let radius = shapes
+            .iter()
+            .map(Shape::enclosing_radius)
+            .fold(f64::MIN, f64::max);
+        let num_shapes: usize = occupied_sites.iter().map(OccupiedSite::multiplicity).sum();
+        4. * radius * num_shapes as f64
+    }
+
     pub fn from_group(shape: S, group: &WallpaperGroup) -> Result<Self, Error> {
-        let wallpaper = Wallpaper::new(group);
-        let isopointal = &[WyckoffSite::new(group)?];
-        Ok(Self::initialise(shape, wallpaper, isopointal))
+        Self::from_shapes(vec![shape], group)
+    }
+
+    /// Create a state with `z_prime` independent copies of the shape on the general position
+    ///
+    /// Each copy has its own position and orientation, with the initial positions spread out
+    /// across the cell so the copies don't overlap.
+    ///
+    pub fn from_group_z_prime(
+        shape: S,
+        group: &WallpaperGroup,
+        z_prime: usize,
+    ) -> Result<Self, Error> {
+        Self::from_stoichiometry(vec![(shape, z_prime)], group)
+    }
+
+    /// Create a state where each of the `shapes` occupies a general position of the group
+    pub fn from_shapes(shapes: Vec<S>, group: &WallpaperGroup) -> Result<Self, Error> {
+        Self::from_stoichiometry(shapes.into_iter().map(|s| (s, 1)).collect(), group)
+    }
+
+    /// Create a multi-component state with a fixed stoichiometry
+    ///
+    /// Each component is a shape along with the number of copies of the shape which occupy a
+    /// general position of the group. For example a 1:2 co-crystal of A and B has A occupying
+    /// one general position and B occupying two.
+    ///
+    pub fn from_stoichiometry(
+        components: Vec<(S, usize)>,
+        group: &WallpaperGroup,
+    ) -> Result<Self, Error> {
+        let sites = stoichiometry_sites(&components, group)?;
+        let shapes = components.into_iter().map(|(shape, _)| shape).collect();
+        Self::initialise_components(shapes, Wallpaper::new(group), &sites)
+    }
+
+    /// The number of each of the shapes within the unit cell
+    pub fn stoichiometry(&self) -> Vec<usize> {
+        stoichiometry(self.shapes.len(), &self.occupied_sites)
+    }
+
+    /// Convert the state into a racemic mixture of the shape and its mirror image
+    ///
+    /// Each occupied site is duplicated, with the new site being occupied by the mirror image of
+    /// the shape. This is only possible for wallpaper groups without any mirror or glide
+    /// operations, since those groups already contain both mirror images of the shape. The cell
+    /// is enlarged to accommodate the additional shapes.
+    ///
+    pub fn racemic(mut self) -> Result<Self, Error> {
+        if self
+            .occupied_sites
+            .iter()
+            .flat_map(OccupiedSite::symmetries)
+            .any(Transform2::is_reflection)
+        {
+            bail!(
+                "The group {} already contains the mirror image of the shape",
+                self.wallpaper.name
+            )
+        }
+        let original: Vec<_> = self
+            .occupied_sites
+            .iter()
+            .filter(|site| !site.is_mirrored())
+            .collect();
+        let spacing = 1. / original.len() as f64;
+        let mirrored: Vec<_> = original
+            .into_iter()
+            .map(|site| site.mirror_image(spacing))
+            .collect();
+        self.occupied_sites.extend(mirrored);
+        self.neighbours = None;
+
+        self.cell = Cell2::from_family(
+            self.wallpaper.family,
+            Self::max_cell_size(&self.shapes, &self.occupied_sites),
+        );
+        Ok(self)
     }
 }
 
 #[cfg(test)]
 mod packed_state_tests {
+    use std::f64::consts::PI;
+
     use super::*;
+    use crate::wallpaper::{get_wallpaper_group, WallpaperGroups};
     use crate::{CrystalFamily, LineShape, Transform2};
     use approx::assert_abs_diff_eq;
 
@@ -254,6 +543,16 @@ mod packed_state_tests {
         PackedState::initialise(square, wallpaper, &isopointal)
     }
 
+    /// A state with two copies of the square on the general position of p2mg
+    fn init_two_squares() -> PackedState<LineShape> {
+        let (wallpaper, isopointal) = create_wallpaper_p2mg();
+        PackedState::initialise_shapes(
+            vec![create_square(), create_square()],
+            wallpaper,
+            &isopointal,
+        )
+    }
+
     #[test]
     fn total_shapes_p1() {
         let state = init_packed_state("p1");
@@ -277,4 +576,170 @@ mod packed_state_tests {
         let state = init_packed_state("p2mg");
         assert_abs_diff_eq!(state.score().unwrap(), 1. / 32.);
     }
+
+    #[test]
+    fn packing_fraction_binary() {
+        let (wallpaper, isopointal) = create_wallpaper_p1();
+        let shapes = vec![
+            create_square(),
+            LineShape::from_radial("Small", vec![0.5; 4]).unwrap(),
+        ];
+        let state = PackedState::initialise_shapes(shapes, wallpaper, &isopointal);
+        assert_eq!(state.total_shapes(), 2);
+        // The cell has a side length of 8, with the squares having areas of 2 and 0.5
+        assert_abs_diff_eq!(state.score().unwrap(), 2.5 / 64.);
+    }
+
+    #[test]
+    fn binary_intersection() {
+        let (wallpaper, isopointal) = create_wallpaper_p1();
+        let shapes = vec![
+            create_square(),
+            LineShape::from_radial("Small", vec![0.5; 4]).unwrap(),
+        ];
+        let mut state = PackedState::initialise_shapes(shapes, wallpaper, &isopointal);
+        // The small square is half a cell from the large square along the x axis
+        state.cell = Cell2::from_family(CrystalFamily::Monoclinic, 3.2);
+        assert!(state.score().is_some());
+        state.cell = Cell2::from_family(CrystalFamily::Monoclinic, 2.8);
+        assert!(state.score().is_none());
+    }
+
+    #[test]
+    fn periodic_image_intersection() {
+        let (wallpaper, isopointal) = create_wallpaper_p1();
+        let mut state = PackedState::initialise(create_square(), wallpaper, &isopointal);
+        assert!(state.score().is_some());
+        let length = state.cell.a();
+        // A narrow cell overlaps the square with its own periodic images
+        state.cell.set_parameters(&[length, 0.1, PI / 2.]);
+        assert!(state.score().is_none());
+        // Shearing the cell brings the images close along the diagonal
+        state.cell.set_parameters(&[2.5, 1., PI / 2.]);
+        assert!(state.score().is_some());
+        state.cell.set_parameters(&[2.5, 1., PI / 6.]);
+        assert!(state.score().is_none());
+    }
+
+    #[test]
+    fn all_pairs_matches_neighbour_list() {
+        let (wallpaper, isopointal) = create_wallpaper_p1();
+        let shapes = vec![
+            create_square(),
+            LineShape::from_radial("Small", vec![0.5; 4]).unwrap(),
+        ];
+        let mut state = PackedState::initialise_shapes(shapes, wallpaper, &isopointal);
+        for cell in [
+            [8., 8., PI / 2.],
+            [3.2, 3.2, PI / 2.],
+            [2.8, 2.8, PI / 2.],
+            [8., 0.1, PI / 2.],
+            [5., 2., PI / 3.],
+            [5., 2., PI / 6.],
+        ] {
+            state.cell.set_parameters(&cell);
+            assert_eq!(state.score(), state.score_all_pairs());
+        }
+    }
+
+    #[test]
+    fn parameter_score_intersection() {
+        let mut state = init_two_squares();
+        let mut parameters = state.parameters();
+        let score = state.score().unwrap();
+        // The cell can't be separated from the rest of the score
+        assert!(state.parameter_score(0).is_none());
+        let index = parameters.len() - 3;
+        assert_eq!(state.parameter_score(index), Some(Some(0.)));
+        // Moving the site onto the mirror plane overlaps the shape with its mirror image
+        let previous = parameters.set(index, 0.25);
+        state.set_parameters(parameters.values());
+        assert!(state.score().is_none());
+        assert_eq!(state.parameter_score(index), Some(None));
+        parameters.set(index, previous);
+        state.set_parameters(parameters.values());
+        assert_abs_diff_eq!(state.score().unwrap(), score);
+    }
+
+    #[test]
+    fn parameter_score_single_site() {
+        let state = init_packed_state("p2mg");
+        // Every shape moves with the single site, so the complete score is required
+        let index = state.parameters().len() - 1;
+        assert!(state.parameter_score(index).is_none());
+    }
+
+    #[test]
+    fn kept_neighbours_match_new() {
+        let mut state = init_two_squares();
+        let mut parameters = state.parameters();
+        for step in 0..40 {
+            // Move the site across the cell, with the occasional change to the cell
+            let index = if step % 10 == 9 {
+                0
+            } else {
+                parameters.len() - 3 + step % 3
+            };
+            let (min, max) = parameters.bounds(index);
+            let fraction = (step as f64 * 0.618).fract();
+            parameters.set(index, min + (max - min) * (0.5 + fraction / 2.));
+            state.set_parameters(parameters.values());
+
+            let new = PackedState {
+                neighbours: None,
+                ..state.clone()
+            };
+            assert_eq!(state.score(), new.score());
+            assert_eq!(state.parameter_score(index), new.parameter_score(index));
+        }
+    }
+
+    #[test]
+    fn racemic_p1() {
+        let state = init_packed_state("p1").racemic().unwrap();
+        assert_eq!(state.total_shapes(), 2);
+        assert_eq!(
+            state
+                .relative_positions()
+                .filter(|t| t.is_reflection())
+                .count(),
+            1
+        );
+        assert!(state.score().is_some());
+    }
+
+    #[test]
+    fn racemic_mirror_group() {
+        assert!(init_packed_state("p2mg").racemic().is_err());
+    }
+
+    #[test]
+    fn z_prime() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PackedState::from_group_z_prime(create_square(), &group, 3).unwrap();
+        assert_eq!(state.total_shapes(), 6);
+        // Each copy has an x, y and angle degree of freedom, along with those of the cell
+        assert_eq!(state.parameters().len(), state.cell.num_parameters() + 9);
+        assert!(state.score().is_some());
+        assert!(PackedState::from_group_z_prime(create_square(), &group, 0).is_err());
+
+        let group = get_wallpaper_group(WallpaperGroups::p1).unwrap();
+        let state = PackedState::from_group_z_prime(create_square(), &group, 2).unwrap();
+        assert!(state.racemic().unwrap().score().is_some());
+    }
+
+    #[test]
+    fn stoichiometry() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let small = LineShape::from_radial("Small", vec![0.5; 4]).unwrap();
+        let components = vec![(create_square(), 1), (small, 2)];
+        let state = PackedState::from_stoichiometry(components, &group).unwrap();
+        assert_eq!(state.stoichiometry(), vec![2, 4]);
+        assert!(state.score().is_some());
+
+        let racemic = state.racemic().unwrap();
+        assert_eq!(racemic.stoichiometry(), vec![4, 8]);
+        // The mirror images are placed between the spread out sites, rather than on them
+        assert!(racemic.score().is_some());
+    }
 }
@@ -7,28 +7,62 @@
 // This is an issue with the Derived traits
 #![allow(clippy::type_repetition_in_bounds)]
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fmt::Write;
+use std::ops::Range;
 
-use anyhow::Error;
+use anyhow::{bail, Error};
+use itertools::iproduct;
 use log::debug;
+use nalgebra::{Matrix2, Vector2};
 use serde::{Deserialize, Serialize};
 
-use crate::traits::{Potential, Shape, State};
+use super::{
+    check_components, deserialize_shapes, parameter_positions, set_state_parameters,
+    state_parameters, stoichiometry, stoichiometry_sites, Neighbours,
+};
+use crate::traits::{Potential, PotentialGradient, ScoreGradient, Shape, State};
 use crate::wallpaper::{Wallpaper, WallpaperGroup, WyckoffSite};
-use crate::{Cell2, OccupiedSite, StandardBasis, Transform2};
+use crate::{Cell2, Ewald, OccupiedSite, Parameters, Transform2};
 
 pub type PotentialState2<S> = PotentialState<S>;
 
+/// The fraction of the energy of an interaction with a periodic image assigned to the unit cell
+///
+/// Each interaction between a shape and a periodic image is found from both of the shapes, so
+/// half the energy of each gives the energy of the unit cell within the lattice. This is the
+/// same energy as the Ewald sum, so the Coulomb and pair interactions are weighted equally.
+///
+pub(crate) const IMAGE_WEIGHT: f64 = 0.5;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PotentialState<S>
 where
     S: Shape + Potential,
 {
     pub wallpaper: Wallpaper,
-    pub shape: S,
+    /// The shapes within the state, with each occupied site referring to one of these shapes
+    #[serde(
+        alias = "shape",
+        deserialize_with = "deserialize_shapes",
+        bound(deserialize = "S: Deserialize<'de>")
+    )]
+    pub shapes: Vec<S>,
     pub cell: Cell2,
     occupied_sites: Vec<OccupiedSite>,
+    /// The summation of the Coulomb interactions between charges, which are ignored when None
+    #[serde(default)]
+    pub ewald: Option<Ewald>,
+    /// The external pressure applied to the cell
+    #[serde(default)]
+    pub pressure: f64,
+    /// Whether to include the energy of the interactions beyond the cutoff
+    #[serde(default)]
+    pub tail_correction: bool,
+    /// The neighbours of each shape, kept between moves of the sites
+    #[serde(skip)]
+    neighbours: Option<Neighbours>,
 }
 
 impl<S> Eq for PotentialState<S> where S: Shape + Potential {}
@@ -45,6 +79,8 @@ where
     }
 }
 
+// Invalid states can't be compared, so the ordering isn't the same as `cmp`
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl<S> PartialOrd for PotentialState<S>
 where
     S: Shape + Potential,
@@ -62,7 +98,8 @@ where
     S: Shape + Potential,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(&other).unwrap()
+        self.partial_cmp(other)
+            .expect("Unable to compare states with invalid scores")
     }
 }
 
@@ -70,48 +107,25 @@ impl<S> State for PotentialState<S>
 where
     S: Shape + Potential,
 {
-    fn generate_basis(&self) -> Vec<StandardBasis> {
-        let mut basis: Vec<StandardBasis> = vec![];
-        basis.append(&mut self.cell.get_degrees_of_freedom());
-        for site in self.occupied_sites.iter() {
-            basis.append(&mut site.get_basis(1));
-        }
-        basis
+    fn parameters(&self) -> Parameters {
+        state_parameters(&self.cell, &self.occupied_sites)
     }
 
-    fn score(&self) -> Option<f64> {
-        let mut sum = 0.;
-
-        // Compare within the current cell
-        for (index, shape1) in self
-            .cartesian_positions()
-            .map(|p| self.shape.transform(&p))
-            .enumerate()
-        {
-            for shape2 in self
-                .cartesian_positions()
-                .map(|p| self.shape.transform(&p))
-                .skip(index + 1)
-            {
-                sum += shape1.energy(&shape2);
-            }
-        }
+    fn set_parameters(&mut self, values: &[f64]) {
+        let moved = set_state_parameters(&mut self.cell, &mut self.occupied_sites, values);
+        self.neighbours = Some(Neighbours::update(
+            self.neighbours.take(),
+            &self.cell,
+            &self.occupied_sites,
+            &moved,
+            self.interaction_range(),
+        ));
+    }
 
-        // Compare in periodic cells
-        for shape1 in self.cartesian_positions().map(|p| self.shape.transform(&p)) {
-            for position in self.relative_positions() {
-                for shape2 in self
-                    .cell
-                    .periodic_images(position, 3, false)
-                    .map(|p| self.shape.transform(&p))
-                {
-                    sum += shape1.energy(&shape2);
-                }
-            }
-        }
-        // We want to minimize the potential energy, so the score we want to maximize is the
-        // negation of the potential energy.
-        Some(-sum / self.total_shapes() as f64)
+    fn score(&self) -> Option<f64> {
+        let neighbours = self.neighbours();
+        let pairs = self.interacting_pairs(&neighbours);
+        Some(self.pair_score(&neighbours.positions, pairs))
     }
 
     fn total_shapes(&self) -> usize {
@@ -120,6 +134,32 @@ where
             .fold(0, |sum, site| sum + site.multiplicity())
     }
 
+    fn parameter_score(&self, index: usize) -> Option<Option<f64>> {
+        // The Ewald sum can't be separated into the contributions of each shape
+        if self.ewald.is_some() {
+            return None;
+        }
+        let moved = parameter_positions(&self.cell, &self.occupied_sites, index)?;
+        let neighbours = self.neighbours();
+        let positions = &neighbours.positions;
+        let moved_shapes: Vec<S> = positions[moved.clone()]
+            .iter()
+            .map(|(index, position)| {
+                self.shapes[*index].transform(&self.cell.to_cartesian_isometry(*position))
+            })
+            .collect();
+
+        let mut sum = 0.;
+        for (index1, index2, (x, y), weight) in self.moved_pairs(&neighbours, moved.clone()) {
+            let (shape2, position2) = positions[index2];
+            let image = self.cell.to_cartesian_translate(position2, x, y);
+            sum += weight
+                * moved_shapes[index1 - moved.start].energy(&self.shapes[shape2].transform(&image));
+        }
+        // The tail correction and the pressure only depend on the cell, so are unchanged
+        Some(Some(-sum / self.total_shapes() as f64))
+    }
+
     fn as_positions(&self) -> Result<String, Error> {
         let mut output = String::new();
         writeln!(&mut output, "{}", self.cell)?;
@@ -132,6 +172,123 @@ where
     }
 }
 
+impl<S> ScoreGradient for PotentialState<S>
+where
+    S: Shape + PotentialGradient,
+{
+    fn score_gradient(&self) -> Result<Vec<f64>, Error> {
+        if self.ewald.is_some() {
+            bail!("The gradient of the Ewald sum is not implemented")
+        }
+        let (mut gradient, _) = self.energy_derivatives();
+        // The tail energy is inversely proportional to the area of the cell
+        let tail_area = if self.tail_correction {
+            -self.tail_energy() / self.cell.area()
+        } else {
+            0.
+        };
+        for (value, area) in gradient.iter_mut().zip(self.cell.area_derivatives()) {
+            *value += (self.pressure + tail_area) * area;
+        }
+        let total_shapes = self.total_shapes() as f64;
+        Ok(gradient.into_iter().map(|g| -g / total_shapes).collect())
+    }
+}
+
+impl<S> PotentialState<S>
+where
+    S: Shape + PotentialGradient,
+{
+    /// The stress on the unit cell from the interactions between the shapes
+    ///
+    /// This is the derivative of the energy of the unit cell with respect to a homogeneous
+    /// strain of the cell, divided by the area of the cell. The centers of the shapes move
+    /// with the strain, while the shapes themselves remain rigid. The external pressure and
+    /// Coulomb interactions aren't included.
+    ///
+    pub fn stress(&self) -> Matrix2<f64> {
+        let (_, strain) = self.energy_derivatives();
+        strain / self.cell.area()
+    }
+
+    /// The derivatives of the energy with respect to the parameters and a strain of the cell
+    ///
+    /// This follows the same summation as the score, with each pair of interacting sites
+    /// contributing to the derivatives of each of the values describing the positions of the
+    /// two sites.
+    ///
+    fn energy_derivatives(&self) -> (Vec<f64>, Matrix2<f64>) {
+        let num_cell = self.cell.num_parameters();
+        let mut offsets = vec![];
+        let mut total = num_cell;
+        for site in self.occupied_sites.iter() {
+            offsets.push(total);
+            total += site.num_parameters();
+        }
+        let mut gradient = vec![0.; total];
+        let mut strain = Matrix2::zeros();
+
+        // Each shape within the cell, along with the site and symmetry which placed it
+        let placements: Vec<(usize, &Transform2, Transform2)> = self
+            .occupied_sites
+            .iter()
+            .enumerate()
+            .flat_map(|(index, site)| {
+                let transform = site.transform();
+                site.symmetries()
+                    .map(move |sym| (index, sym, (sym * transform).periodic(1., -0.5)))
+            })
+            .collect();
+
+        let mut add_pair = |(site1, sym1, position1): (usize, &Transform2, Transform2),
+                            (site2, sym2, position2): (usize, &Transform2, Transform2),
+                            weight: f64| {
+            let (fractional1, fractional2) = (position1.position(), position2.position());
+            let center1 = self.cell.to_cartesian_point(fractional1);
+            let center2 = self.cell.to_cartesian_point(fractional2);
+            let shape1 = self.shapes[self.occupied_sites[site1].shape()]
+                .transform(&self.cell.to_cartesian_isometry(position1));
+            let shape2 = self.shapes[self.occupied_sites[site2].shape()]
+                .transform(&self.cell.to_cartesian_isometry(position2));
+            let cell1 = self.cell.point_derivatives(fractional1);
+            let cell2 = self.cell.point_derivatives(fractional2);
+
+            for (point1, point2, derivative) in shape1.pair_derivatives(&shape2) {
+                let direction = (point1 - point2).normalize() * weight * derivative;
+                for (value, (d1, d2)) in gradient.iter_mut().zip(cell1.iter().zip(cell2.iter())) {
+                    *value += direction.dot(&(d1 - d2));
+                }
+                let derivatives1 = self.occupied_sites[site1].point_derivatives(
+                    sym1,
+                    &self.cell,
+                    point1 - center1,
+                );
+                for (i, d) in derivatives1.iter().enumerate() {
+                    gradient[offsets[site1] + i] += direction.dot(d);
+                }
+                let derivatives2 = self.occupied_sites[site2].point_derivatives(
+                    sym2,
+                    &self.cell,
+                    point2 - center2,
+                );
+                for (i, d) in derivatives2.iter().enumerate() {
+                    gradient[offsets[site2] + i] -= direction.dot(d);
+                }
+                strain += direction * (center1 - center2).transpose();
+            }
+        };
+
+        for (index1, index2, (x, y)) in self.interacting_pairs(&self.neighbours()) {
+            let (site2, sym2, position2) = placements[index2];
+            let image =
+                position2.set_position(position2.position() + Vector2::new(x as f64, y as f64));
+            let weight = if (x, y) == (0, 0) { 1. } else { IMAGE_WEIGHT };
+            add_pair(placements[index1], (site2, sym2, image), weight);
+        }
+        (gradient, strain)
+    }
+}
+
 impl<S> PotentialState<S>
 where
     S: Shape + Potential,
@@ -145,10 +302,214 @@ where
         self.occupied_sites.iter().flat_map(OccupiedSite::positions)
     }
 
+    /// The index of the shape at each position along with the relative position
+    pub fn site_positions<'a>(&'a self) -> impl Iterator<Item = (usize, Transform2)> + 'a {
+        self.occupied_sites
+            .iter()
+            .flat_map(|site| site.positions().map(move |p| (site.shape(), p)))
+    }
+
+    /// The score of the state from the interactions of the `pairs` of shapes
+    ///
+    /// The `pairs` have the same form as those from `interacting_pairs`.
+    ///
+    fn pair_score(
+        &self,
+        positions: &[(usize, Transform2)],
+        pairs: Vec<(usize, usize, (i64, i64))>,
+    ) -> f64 {
+        let mut sum = 0.;
+        let shapes: Vec<S> = positions
+            .iter()
+            .map(|(index, position)| {
+                self.shapes[*index].transform(&self.cell.to_cartesian_isometry(*position))
+            })
+            .collect();
+
+        for (index1, index2, (x, y)) in pairs {
+            sum += if x == 0 && y == 0 {
+                shapes[index1].energy(&shapes[index2])
+            } else {
+                let (shape, position) = positions[index2];
+                let image = self.cell.to_cartesian_translate(position, x, y);
+                IMAGE_WEIGHT * shapes[index1].energy(&self.shapes[shape].transform(&image))
+            };
+        }
+        if let Some(ewald) = &self.ewald {
+            let molecules: Vec<_> = shapes.iter().map(Potential::charges).collect();
+            sum += ewald
+                .energy(&self.cell, &molecules)
+                .expect("The charges were checked to be neutral when adding the Ewald sum");
+        }
+
+        if self.tail_correction {
+            sum += self.tail_energy();
+        }
+
+        // Under an external pressure the quantity to minimise is the enthalpy
+        sum += self.pressure * self.cell.area();
+
+        // We want to minimize the potential energy, so the score we want to maximize is the
+        // negation of the potential energy.
+        -sum / self.total_shapes() as f64
+    }
+
+    /// The score found by summing every pair of shapes within three periodic images of the cell
+    ///
+    /// This is the same as the score for shapes which interact over a short range, without the
+    /// `NeighbourList`, which allows comparing the performance of the two approaches.
+    ///
+    #[doc(hidden)]
+    pub fn score_all_pairs(&self) -> Option<f64> {
+        let positions: Vec<(usize, Transform2)> = self.site_positions().collect();
+        let pairs = Self::all_pairs(positions.len());
+        Some(self.pair_score(&positions, pairs))
+    }
+
+    /// The positions and neighbours of the shapes, found again when those kept from the last
+    /// move are invalid
+    fn neighbours(&self) -> Cow<'_, Neighbours> {
+        let range = self.interaction_range();
+        match &self.neighbours {
+            Some(neighbours) if neighbours.is_valid(&self.cell, range) => Cow::Borrowed(neighbours),
+            _ => Cow::Owned(Neighbours::new(&self.cell, &self.occupied_sites, range)),
+        }
+    }
+
+    /// The largest distance between the centres of any two shapes which interact
+    fn interaction_range(&self) -> Option<f64> {
+        iproduct!(self.shapes.iter(), self.shapes.iter())
+            .map(|(shape1, shape2)| shape1.interaction_range(shape2))
+            .try_fold(0., |range: f64, r| Some(range.max(r?)))
+    }
+
+    /// Each pair of interacting shapes, along with the periodic image of the second shape
+    ///
+    /// The shapes are the indices within the positions of the `neighbours`, which are the same
+    /// as `site_positions`. Pairs within the unit cell are included once, while pairs with a
+    /// periodic image are included from both of the shapes, so each contributes `IMAGE_WEIGHT`
+    /// of its energy. When the shapes interact over a
+    /// finite range, only the pairs within that range are found using a `NeighbourList`.
+    /// Otherwise all the pairs within three periodic images of the unit cell are included.
+    ///
+    fn interacting_pairs(&self, neighbours: &Neighbours) -> Vec<(usize, usize, (i64, i64))> {
+        let num_shapes = neighbours.positions.len();
+        match &neighbours.list {
+            Some(list) => (0..num_shapes)
+                .flat_map(|index1| {
+                    list.neighbours(index1)
+                        .filter(move |&(index2, image)| image != (0, 0) || index1 < index2)
+                        .map(move |(index2, image)| (index1, index2, image))
+                })
+                .collect(),
+            None => Self::all_pairs(num_shapes),
+        }
+    }
+
+    /// Every pair of the `num_shapes` shapes within three periodic images of the unit cell
+    fn all_pairs(num_shapes: usize) -> Vec<(usize, usize, (i64, i64))> {
+        let mut pairs = vec![];
+        // Compare within the current cell
+        for index1 in 0..num_shapes {
+            for index2 in (index1 + 1)..num_shapes {
+                pairs.push((index1, index2, (0, 0)));
+            }
+        }
+        // Compare in periodic cells
+        for index1 in 0..num_shapes {
+            for index2 in 0..num_shapes {
+                for image in iproduct!(-3..=3, -3..=3).filter(|&image| image != (0, 0)) {
+                    pairs.push((index1, index2, image));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// The pairs of interacting shapes which involve one of the `moved` shapes
+    ///
+    /// This has the same form as `interacting_pairs`, with the first shape of each pair being
+    /// one of the `moved` shapes, along with the number of times the pair is counted in the
+    /// complete set of pairs. Summing the interactions of these pairs with the weights gives
+    /// all the contributions to the energy which change when the `moved` shapes move.
+    ///
+    fn moved_pairs(
+        &self,
+        neighbours: &Neighbours,
+        moved: Range<usize>,
+    ) -> Vec<(usize, usize, (i64, i64), f64)> {
+        let weight = |index1: usize, index2: usize, image: (i64, i64)| {
+            match (image == (0, 0), moved.contains(&index2)) {
+                // Pairs within the cell are only counted once
+                (true, false) => Some(1.),
+                (true, true) if index1 < index2 => Some(1.),
+                (true, true) => None,
+                // Pairs with an image are counted from both shapes, each with half the energy
+                (false, false) => Some(2. * IMAGE_WEIGHT),
+                (false, true) => Some(IMAGE_WEIGHT),
+            }
+        };
+
+        let candidates: Vec<(usize, usize, (i64, i64))> = match &neighbours.list {
+            Some(list) => moved
+                .clone()
+                .flat_map(|index1| {
+                    list.neighbours(index1)
+                        .map(move |(index2, image)| (index1, index2, image))
+                })
+                .collect(),
+            None => iproduct!(
+                moved.clone(),
+                0..neighbours.positions.len(),
+                iproduct!(-3..=3, -3..=3)
+            )
+            .filter(|&(index1, index2, image)| image != (0, 0) || index1 != index2)
+            .collect(),
+        };
+
+        candidates
+            .into_iter()
+            .filter_map(|(index1, index2, image)| {
+                weight(index1, index2, image).map(|w| (index1, index2, image, w))
+            })
+            .collect()
+    }
+
     pub fn from_group(shape: S, group: &WallpaperGroup) -> Result<Self, Error> {
-        let wallpaper = Wallpaper::new(group);
-        let isopointal = &[WyckoffSite::new(group)?];
-        Ok(Self::initialise(shape, wallpaper, isopointal))
+        Self::from_shapes(vec![shape], group)
+    }
+
+    /// Create a state with `z_prime` independent copies of the shape on the general position
+    ///
+    /// Each copy has its own position and orientation, with the initial positions spread out
+    /// across the cell so the copies don't overlap.
+    ///
+    pub fn from_group_z_prime(
+        shape: S,
+        group: &WallpaperGroup,
+        z_prime: usize,
+    ) -> Result<Self, Error> {
+        Self::from_stoichiometry(vec![(shape, z_prime)], group)
+    }
+
+    /// Create a state where each of the `shapes` occupies a general position of the group
+    pub fn from_shapes(shapes: Vec<S>, group: &WallpaperGroup) -> Result<Self, Error> {
+        Self::from_stoichiometry(shapes.into_iter().map(|s| (s, 1)).collect(), group)
+    }
+
+    /// Create a multi-component state with a fixed stoichiometry
+    ///
+    /// Each component is a shape along with the number of copies of the shape which occupy a
+    /// general position of the group. For example a 1:2 co-crystal of A and B has A occupying
+    /// one general position and B occupying two.
+    ///
+    pub fn from_stoichiometry(
+        components: Vec<(S, usize)>,
+        group: &WallpaperGroup,
+    ) -> Result<Self, Error> {
+        let sites = stoichiometry_sites(&components, group)?;
+        let shapes = components.into_iter().map(|(shape, _)| shape).collect();
+        Self::initialise_components(shapes, Wallpaper::new(group), &sites)
     }
 
     pub fn initialise(
@@ -156,28 +517,168 @@ where
         wallpaper: Wallpaper,
         isopointal: &[WyckoffSite],
     ) -> PotentialState<S> {
-        let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
-        let max_cell_size = 2. * shape.enclosing_radius() * num_shapes as f64;
+        let sites: Vec<_> = isopointal.iter().map(|w| (0, w.clone())).collect();
+        Self::from_sites(
+            vec![shape],
+            wallpaper,
+            OccupiedSite::from_components(&sites),
+        )
+    }
+
+    /// Create a state containing multiple shapes
+    ///
+    /// Each of the `shapes` occupies its own copy of each of the Wyckoff sites in `isopointal`,
+    /// with the initial positions spread out so none of the shapes overlap.
+    ///
+    pub fn initialise_shapes(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        isopointal: &[WyckoffSite],
+    ) -> Result<PotentialState<S>, Error> {
+        let sites: Vec<_> = (0..shapes.len())
+            .flat_map(|index| isopointal.iter().map(move |w| (index, w.clone())))
+            .collect();
+        Self::initialise_components(shapes, wallpaper, &sites)
+    }
+
+    /// Create a state where each shape occupies the Wyckoff sites it is paired with
+    ///
+    /// Each of the `sites` pairs the index of a shape in `shapes` with a Wyckoff site, allowing a
+    /// shape to occupy multiple sites, or sites of different multiplicities. Every shape has to
+    /// occupy at least one site, and the interactions between each pair of shapes can't depend
+    /// on their order.
+    ///
+    pub fn initialise_components(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        sites: &[(usize, WyckoffSite)],
+    ) -> Result<PotentialState<S>, Error> {
+        check_components(shapes.len(), sites)?;
+        // Each shape also interacts with the other copies of itself
+        for (index, shape1) in shapes.iter().enumerate() {
+            for shape2 in &shapes[index..] {
+                shape1.check_compatible(shape2)?;
+            }
+        }
+        Ok(Self::from_sites(
+            shapes,
+            wallpaper,
+            OccupiedSite::from_components(sites),
+        ))
+    }
+
+    fn from_sites(
+        shapes: Vec<S>,
+        wallpaper: Wallpaper,
+        occupied_sites: Vec<OccupiedSite>,
+    ) -> PotentialState<S> {
+        let radius = shapes
+            .iter()
+            .map(Shape::enclosing_radius)
+            .fold(f64::MIN, f64::max);
+        let num_shapes: usize = occupied_sites.iter().map(OccupiedSite::multiplicity).sum();
+        let max_cell_size = 2. * radius * num_shapes as f64;
 
         let cell = Cell2::from_family(wallpaper.family, max_cell_size);
 
         debug!("Cell: {:?}", cell);
 
-        let occupied_sites: Vec<_> = isopointal.iter().map(OccupiedSite::from_wyckoff).collect();
-
         PotentialState {
             wallpaper,
-            shape,
+            shapes,
             cell,
             occupied_sites,
+            ewald: None,
+            pressure: 0.,
+            tail_correction: false,
+            neighbours: None,
+        }
+    }
+
+    /// Whether any of the shapes have partial charges
+    pub fn has_charges(&self) -> bool {
+        self.shapes.iter().any(|s| !s.charges().is_empty())
+    }
+
+    /// Include the Coulomb interactions between the charges of the shapes
+    ///
+    /// The interactions are calculated using an Ewald sum, which requires the unit cell to be
+    /// neutral.
+    ///
+    pub fn with_ewald(self, ewald: Ewald) -> Result<Self, Error> {
+        let charges: Vec<_> = self
+            .site_positions()
+            .flat_map(|(index, _)| self.shapes[index].charges())
+            .collect();
+        Ewald::check_neutral(&charges)?;
+        Ok(Self {
+            ewald: Some(ewald),
+            ..self
+        })
+    }
+
+    /// Minimise the enthalpy of the state under an external `pressure`
+    ///
+    /// The score becomes $-(E + PA)/N$, where $A$ is the area of the unit cell. Increasing the
+    /// pressure favours denser structures, approaching the packing of hard shapes in the limit of
+    /// high pressure.
+    ///
+    pub fn with_pressure(self, pressure: f64) -> Result<Self, Error> {
+        if !pressure.is_finite() || pressure < 0. {
+            bail!(
+                "The pressure needs to be finite and non-negative, found {}",
+                pressure
+            )
+        }
+        Ok(Self { pressure, ..self })
+    }
+
+    /// Include a correction for the energy of the interactions beyond the cutoff
+    ///
+    /// Beyond the cutoff the shapes are assumed to be uniformly distributed with the number
+    /// density of the unit cell, giving an analytic correction to the energy. This makes the
+    /// energies of states using different cutoffs comparable.
+    ///
+    pub fn with_tail_correction(self) -> Self {
+        Self {
+            tail_correction: true,
+            ..self
         }
     }
+
+    /// The energy of the interactions beyond the cutoff within the unit cell
+    ///
+    /// Each shape interacts with all the shapes beyond the cutoff, with each interaction shared
+    /// between the two shapes like the periodic interactions within the score. The correction
+    /// for a single component is $N \rho I / 2$, where $I$ is the integral of the truncated
+    /// potential over the plane.
+    ///
+    pub fn tail_energy(&self) -> f64 {
+        let counts = self.stoichiometry();
+        let area = self.cell.area();
+        iproduct!(
+            self.shapes.iter().zip(&counts),
+            self.shapes.iter().zip(&counts)
+        )
+        .map(|((s1, &n1), (s2, &n2))| IMAGE_WEIGHT * n1 as f64 * n2 as f64 / area * s1.tail(s2))
+        .sum()
+    }
+
+    /// The number of each of the shapes within the unit cell
+    pub fn stoichiometry(&self) -> Vec<usize> {
+        stoichiometry(self.shapes.len(), &self.occupied_sites)
+    }
 }
 
 #[cfg(test)]
 mod packed_state_tests {
+    use approx::assert_abs_diff_eq;
+
     use super::*;
-    use crate::{CrystalFamily, LJShape2, Transform2};
+    use crate::wallpaper::{get_wallpaper_group, WallpaperGroups};
+    use crate::{
+        CrystalFamily, LJShape2, MixingRule, PairEntry, PairPotential, Transform2, Truncation, LJ2,
+    };
 
     fn create_wallpaper_p1() -> (Wallpaper, Vec<WyckoffSite>) {
         let wallpaper = Wallpaper {
@@ -239,4 +740,462 @@ mod packed_state_tests {
         let state = init_state("p2mg");
         assert_eq!(state.total_shapes(), 4);
     }
+
+    #[test]
+    fn z_prime() {
+        let group = get_wallpaper_group(WallpaperGroups::p1).unwrap();
+        let state = PotentialState::from_group_z_prime(LJShape2::circle(), &group, 2).unwrap();
+        assert_eq!(state.total_shapes(), 2);
+        assert_eq!(state.stoichiometry(), vec![2]);
+    }
+
+    #[test]
+    fn stoichiometry() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let components = vec![
+            (LJShape2::circle(), 1),
+            (LJShape2::circle_with_sigma(0.5), 2),
+        ];
+        let state = PotentialState::from_stoichiometry(components, &group).unwrap();
+        assert_eq!(state.stoichiometry(), vec![2, 4]);
+        assert_eq!(state.total_shapes(), 6);
+        assert!(state.score().unwrap().is_finite());
+    }
+
+    fn ion_pair() -> LJShape2 {
+        LJShape2 {
+            name: String::from("ion pair"),
+            items: vec![
+                LJ2 {
+                    charge: 1.,
+                    ..LJ2::new(-0.5, 0., 1.)
+                },
+                LJ2 {
+                    charge: -1.,
+                    ..LJ2::new(0.5, 0., 1.)
+                },
+            ],
+            mixing: Default::default(),
+        }
+    }
+
+    #[test]
+    fn ewald_neutral_molecule() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PotentialState::from_group(ion_pair(), &group).unwrap();
+        assert!(state.has_charges());
+        let score = state.score().unwrap();
+        let state = state.with_ewald(Ewald::default()).unwrap();
+        let charged_score = state.score().unwrap();
+        assert!(charged_score.is_finite());
+        assert!(charged_score != score);
+    }
+
+    #[test]
+    fn ewald_same_convention_as_pairs() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PotentialState::from_shapes(vec![ion_pair(), LJShape2::circle()], &group)
+            .unwrap()
+            .with_ewald(Ewald::default())
+            .unwrap();
+
+        // Sum both the pair and Coulomb interactions directly, counting the interactions with
+        // the periodic images from both of the shapes.
+        let shapes: Vec<_> = state
+            .site_positions()
+            .map(|(index, p)| state.shapes[index].transform(&state.cell.to_cartesian_isometry(p)))
+            .collect();
+        let coulomb = |a: &LJShape2, b: &LJShape2| -> f64 {
+            iproduct!(a.charges(), b.charges())
+                .map(|((p1, q1), (p2, q2))| q1 * q2 / nalgebra::distance(&p1, &p2))
+                .sum()
+        };
+        let mut sum = 0.;
+        for (index, shape1) in shapes.iter().enumerate() {
+            for shape2 in shapes.iter().skip(index + 1) {
+                sum += shape1.energy(shape2) + coulomb(shape1, shape2);
+            }
+            for (index2, position) in state.site_positions() {
+                for image in state.cell.periodic_images(position, 30, false) {
+                    let shape2 = state.shapes[index2].transform(&image);
+                    sum += (shape1.energy(&shape2) + coulomb(shape1, &shape2)) / 2.;
+                }
+            }
+        }
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            -sum / state.total_shapes() as f64,
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn ewald_requires_neutral() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let ion = LJShape2 {
+            items: vec![LJ2 {
+                charge: 1.,
+                ..LJ2::new(0., 0., 1.)
+            }],
+            ..LJShape2::circle()
+        };
+        let state = PotentialState::from_group(ion.clone(), &group).unwrap();
+        assert!(state.with_ewald(Ewald::default()).is_err());
+        // Combining the cation with an anion gives a neutral cell
+        let anion = LJShape2 {
+            items: vec![LJ2 {
+                charge: -1.,
+                ..LJ2::new(0., 0., 1.)
+            }],
+            ..LJShape2::circle()
+        };
+        let state = PotentialState::from_shapes(vec![ion, anion], &group).unwrap();
+        assert!(state.with_ewald(Ewald::default()).is_ok());
+    }
+
+    #[test]
+    fn pressure() {
+        let state = init_state("p2mg");
+        let score = state.score().unwrap();
+        let area = state.cell.area();
+        let state = state.with_pressure(2.).unwrap();
+        assert_abs_diff_eq!(state.score().unwrap(), score - 2. * area / 4.);
+        assert!(state.clone().with_pressure(-1.).is_err());
+        assert!(state.with_pressure(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn tail_correction() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let state = trimer_state(WallpaperGroups::p2, &values);
+        let score = state.score().unwrap();
+        let tail = state.tail_energy();
+        // Each of the particles of the four trimers interacts with the particles of every other
+        // trimer, with all the particles having a cutoff of 3.5.
+        let items = &state.shapes[0].items;
+        let pair_tail: f64 = iproduct!(items, items)
+            .map(|(a, b)| PairPotential::LennardJones.tail(3.5, (a.sigma + b.sigma) / 2., 1.))
+            .sum();
+        let expected = 4. * 4. / state.cell.area() * pair_tail / 2.;
+        assert_abs_diff_eq!(tail, expected, epsilon = 1e-12);
+        assert!(tail < 0.);
+        let state = state.with_tail_correction();
+        assert_abs_diff_eq!(state.score().unwrap(), score - tail / 4.);
+    }
+
+    #[test]
+    fn tail_correction_untruncated() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group)
+            .unwrap()
+            .with_tail_correction();
+        assert_abs_diff_eq!(state.tail_energy(), 0.);
+    }
+
+    #[test]
+    fn neighbour_list_matches_all_pairs() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let state = trimer_state(WallpaperGroups::p2, &values);
+        assert!(state.interaction_range().is_some());
+
+        // Sum the interactions of every pair within three periodic images of the cell, with
+        // the interactions with an image shared between the two shapes
+        let shapes: Vec<_> = state
+            .site_positions()
+            .map(|(index, p)| state.shapes[index].transform(&state.cell.to_cartesian_isometry(p)))
+            .collect();
+        let mut sum = 0.;
+        for (index, shape1) in shapes.iter().enumerate() {
+            for shape2 in shapes.iter().skip(index + 1) {
+                sum += shape1.energy(shape2);
+            }
+            for (index2, position) in state.site_positions() {
+                for image in state.cell.periodic_images(position, 3, false) {
+                    sum += shape1.energy(&state.shapes[index2].transform(&image)) / 2.;
+                }
+            }
+        }
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            -sum / state.total_shapes() as f64,
+            epsilon = 1e-10
+        );
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            state.score_all_pairs().unwrap(),
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn parameter_score_matches_score() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let mut state = trimer_state(WallpaperGroups::p2, &values);
+        let mut parameters = state.parameters();
+        // The cell can't be separated from the rest of the score
+        assert!(state.parameter_score(0).is_none());
+        for index in 3..parameters.len() {
+            let score = state.score().unwrap();
+            let before = state.parameter_score(index).unwrap().unwrap();
+            parameters.set(index, parameters.get(index) + 0.05);
+            state.set_parameters(parameters.values());
+            let after = state.parameter_score(index).unwrap().unwrap();
+            assert_abs_diff_eq!(
+                state.score().unwrap(),
+                score - before + after,
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn kept_neighbours_match_new() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let mut state = trimer_state(WallpaperGroups::p2, &values);
+        let mut parameters = state.parameters();
+        for step in 0..40 {
+            // Move the sites across the cell, with the occasional change to the cell
+            let index = if step % 10 == 9 { 0 } else { 3 + step % 6 };
+            let (min, max) = parameters.bounds(index);
+            let fraction = (step as f64 * 0.618).fract();
+            parameters.set(index, min + (max - min) * (0.5 + fraction / 2.));
+            state.set_parameters(parameters.values());
+
+            let new = PotentialState {
+                neighbours: None,
+                ..state.clone()
+            };
+            let expected = new.score().unwrap();
+            let epsilon = 1e-12 * expected.abs().max(1.);
+            assert_abs_diff_eq!(state.score().unwrap(), expected, epsilon = epsilon);
+            if let Some(Some(score)) = state.parameter_score(index) {
+                let expected = new.parameter_score(index).unwrap().unwrap();
+                let epsilon = 1e-12 * expected.abs().max(1.);
+                assert_abs_diff_eq!(score, expected, epsilon = epsilon);
+            }
+        }
+    }
+
+    #[test]
+    fn parameter_score_all_pairs() {
+        let (wallpaper, isopointal) = create_wallpaper_p2mg();
+        let shapes = vec![LJShape2::circle(), LJShape2::circle()];
+        let mut state = PotentialState::initialise_shapes(shapes, wallpaper, &isopointal).unwrap();
+        assert!(state.interaction_range().is_none());
+        let mut parameters = state.parameters();
+        let index = parameters.len() - 2;
+        let score = state.score().unwrap();
+        let before = state.parameter_score(index).unwrap().unwrap();
+        parameters.set(index, parameters.get(index) + 0.05);
+        state.set_parameters(parameters.values());
+        let after = state.parameter_score(index).unwrap().unwrap();
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            score - before + after,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn parameter_score_ewald() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PotentialState::from_group(ion_pair(), &group)
+            .unwrap()
+            .with_ewald(Ewald::default())
+            .unwrap();
+        let index = state.parameters().len() - 1;
+        assert!(state.parameter_score(index).is_none());
+    }
+
+    /// Update the leading parameters of the state to `values`, clamped to their bounds
+    fn with_values<S: Shape + Potential>(
+        mut state: PotentialState<S>,
+        values: &[f64],
+    ) -> PotentialState<S> {
+        let mut parameters = state.parameters();
+        for (index, &value) in values.iter().enumerate() {
+            parameters.set(index, value);
+        }
+        state.set_parameters(parameters.values());
+        state
+    }
+
+    /// A compact state of trimers where the interactions between the shapes are significant
+    fn trimer_state(group: WallpaperGroups, values: &[f64]) -> PotentialState<LJShape2> {
+        let group = get_wallpaper_group(group).unwrap();
+        let trimer = LJShape2::from_trimer(0.63, 120., 1.);
+        with_values(
+            PotentialState::from_group_z_prime(trimer, &group, 2).unwrap(),
+            values,
+        )
+    }
+
+    /// The gradient of the score using a second order backward difference
+    ///
+    /// The upper bounds of the cell lengths are the current values, so the differences are
+    /// taken towards smaller values.
+    fn numerical_gradient<S: Shape + Potential>(state: &PotentialState<S>) -> Vec<f64> {
+        let h = 1e-5;
+        let score = state.score().unwrap();
+        let values = state.parameters().values().to_vec();
+        let mut shifted = state.clone();
+        (0..values.len())
+            .map(|i| {
+                let mut shifted_score = |step: f64| {
+                    let mut v = values.clone();
+                    v[i] -= step;
+                    shifted.set_parameters(&v);
+                    shifted.score().unwrap()
+                };
+                let score1 = shifted_score(h);
+                let score2 = shifted_score(2. * h);
+                (3. * score - 4. * score1 + score2) / (2. * h)
+            })
+            .collect()
+    }
+
+    fn assert_gradient<S: Shape + PotentialGradient>(state: &PotentialState<S>) {
+        let analytical = state.score_gradient().unwrap();
+        let numerical = numerical_gradient(state);
+        assert_eq!(analytical.len(), numerical.len());
+        for (a, n) in analytical.iter().zip(numerical.iter()) {
+            assert_abs_diff_eq!(a, n, epsilon = 1e-4 * n.abs().max(1.));
+        }
+    }
+
+    #[test]
+    fn gradient_monoclinic() {
+        // The values of the cell, followed by the two sites
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let state = trimer_state(WallpaperGroups::p2, &values);
+        assert!(state.score().unwrap().is_finite());
+        assert_gradient(&state);
+        assert_gradient(&state.clone().with_pressure(0.5).unwrap());
+        assert_gradient(&state.with_tail_correction());
+    }
+
+    #[test]
+    fn gradient_shifted_force() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let state = trimer_state(WallpaperGroups::p2, &values);
+        let shapes = state
+            .shapes
+            .iter()
+            .map(|s| s.clone().with_truncation(Truncation::ShiftedForce))
+            .collect();
+        let state = PotentialState { shapes, ..state };
+        assert_gradient(&state);
+    }
+
+    #[test]
+    fn gradient_mirror() {
+        let values = [12., 0.9, 0.02, 0.12, 0.3, 0.07, 0.37, 1.9];
+        let state = trimer_state(WallpaperGroups::p2mg, &values);
+        assert_gradient(&state);
+    }
+
+    #[test]
+    fn gradient_mixed_potentials() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let (lj, morse) = mixed_potentials();
+        let components = vec![(lj, 1), (morse, 1)];
+        let state = PotentialState::from_stoichiometry(components, &group).unwrap();
+        let state = with_values(state, &[2.4, 0.95, 1.4]);
+        assert_gradient(&state);
+    }
+
+    #[test]
+    fn stress_matches_cell_gradient() {
+        let values = [7., 0.9, 1.3, 0.1, 0.2, 0.7, -0.3, 0.35, 2.5];
+        let state = trimer_state(WallpaperGroups::p2, &values);
+        // Scaling the cell length is a uniform strain of the positions of the shapes
+        let energy_derivative = -state.score_gradient().unwrap()[0] * state.total_shapes() as f64;
+        let length = state.cell.a();
+        assert_abs_diff_eq!(
+            state.stress().trace() * state.cell.area(),
+            length * energy_derivative,
+            epsilon = 1e-8 * energy_derivative.abs().max(1.)
+        );
+    }
+
+    #[test]
+    fn gradient_requires_no_ewald() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let state = PotentialState::from_group(ion_pair(), &group)
+            .unwrap()
+            .with_ewald(Ewald::default())
+            .unwrap();
+        assert!(state.score_gradient().is_err());
+    }
+
+    #[test]
+    fn invalid_stoichiometry() {
+        let group = get_wallpaper_group(WallpaperGroups::p1).unwrap();
+        let components = vec![(LJShape2::circle(), 1), (LJShape2::circle(), 0)];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_err());
+    }
+
+    #[test]
+    fn mismatched_mixing() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let geometric = LJShape2::circle_with_sigma(0.5).with_mixing(MixingRule::Geometric);
+        let components = vec![(LJShape2::circle(), 1), (geometric.clone(), 1)];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_err());
+
+        let components = vec![
+            (LJShape2::circle().with_mixing(MixingRule::Geometric), 1),
+            (geometric, 1),
+        ];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_ok());
+    }
+
+    /// A Lennard-Jones and a Morse particle, interacting through the Morse potential
+    fn mixed_potentials() -> (LJShape2, LJShape2) {
+        let morse = PairPotential::Morse { alpha: 4. };
+        let lj = LJShape2::circle();
+        let mut other = LJShape2::circle_with_sigma(0.8).with_potential(morse);
+        other.items[0].species = 1;
+        let table = MixingRule::Table(vec![PairEntry {
+            species: (0, 1),
+            parameters: MixingRule::LorentzBerthelot.parameters(&lj.items[0], &other.items[0]),
+            potential: Some(morse),
+        }]);
+        (lj.with_mixing(table.clone()), other.with_mixing(table))
+    }
+
+    #[test]
+    fn mismatched_potentials() {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        let morse =
+            LJShape2::circle_with_sigma(0.8).with_potential(PairPotential::Morse { alpha: 4. });
+        let components = vec![(LJShape2::circle(), 1), (morse, 1)];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_err());
+
+        // The table gives the form of the interaction between the two species
+        let (lj, morse) = mixed_potentials();
+        let components = vec![(lj, 1), (morse.clone(), 1)];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_ok());
+
+        // Particles of the same species within a shape still need the same form
+        let mut shape = morse.clone();
+        shape.items.push(LJ2 {
+            potential: PairPotential::LennardJones,
+            ..morse.items[0].clone()
+        });
+        let components = vec![(shape, 1)];
+        assert!(PotentialState::from_stoichiometry(components, &group).is_err());
+    }
+
+    #[test]
+    fn invalid_components() {
+        let (wallpaper, isopointal) = create_wallpaper_p1();
+        let shapes = vec![LJShape2::circle(), LJShape2::circle()];
+        let sites = vec![(0, isopointal[0].clone())];
+        assert!(
+            PotentialState::initialise_components(shapes.clone(), wallpaper.clone(), &sites)
+                .is_err()
+        );
+        let sites = vec![(0, isopointal[0].clone()), (2, isopointal[0].clone())];
+        assert!(PotentialState::initialise_components(shapes, wallpaper, &sites).is_err());
+    }
 }
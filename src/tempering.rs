@@ -0,0 +1,435 @@
+//
+// tempering.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use log::debug;
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+use structopt::StructOpt;
+
+use crate::traits::*;
+use crate::{BuildOptimiser, Parameters};
+
+#[derive(StructOpt, Debug, Clone, Copy)]
+pub struct BuildTempering {
+    /// The number of replicas of the state, each at a different temperature.
+    #[structopt(long, default_value = "8")]
+    replicas: usize,
+
+    /// The temperature of the coldest replica.
+    #[structopt(long, default_value = "0.001")]
+    kt_min: f64,
+
+    /// The temperature of the hottest replica, with the temperatures of the replicas in between
+    /// spaced geometrically.
+    #[structopt(long, default_value = "0.1")]
+    kt_max: f64,
+
+    /// The number of times to attempt swapping the configurations of the replicas.
+    #[structopt(long, default_value = "100")]
+    exchanges: u64,
+
+    /// The number of Monte-Carlo steps each replica takes between attempted swaps.
+    #[structopt(long, default_value = "1000")]
+    exchange_steps: u64,
+
+    /// This option is skipped on the command line and filled in when setting up the iterations.
+    #[structopt(skip)]
+    seed: Option<u64>,
+}
+
+impl Default for BuildTempering {
+    fn default() -> Self {
+        Self {
+            replicas: 8,
+            kt_min: 0.001,
+            kt_max: 0.1,
+            exchanges: 100,
+            exchange_steps: 1000,
+            seed: None,
+        }
+    }
+}
+
+impl BuildTempering {
+    pub fn replicas(&mut self, replicas: usize) -> &mut Self {
+        self.replicas = replicas;
+        self
+    }
+
+    pub fn kt_min(&mut self, kt_min: f64) -> &mut Self {
+        self.kt_min = kt_min;
+        self
+    }
+
+    pub fn kt_max(&mut self, kt_max: f64) -> &mut Self {
+        self.kt_max = kt_max;
+        self
+    }
+
+    pub fn exchanges(&mut self, exchanges: u64) -> &mut Self {
+        self.exchanges = exchanges;
+        self
+    }
+
+    pub fn exchange_steps(&mut self, exchange_steps: u64) -> &mut Self {
+        self.exchange_steps = exchange_steps;
+        self
+    }
+
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Create the optimiser, with the Monte-Carlo moves of each replica using the `optimiser`
+    ///
+    /// The size of the moves is taken from the `optimiser`, while the temperature and the
+    /// number of steps are set for each replica.
+    ///
+    pub fn build(&self, optimiser: &BuildOptimiser) -> ParallelTempering {
+        let replicas = self.replicas.max(1);
+        let ratio = match replicas {
+            1 => 1.,
+            n => f64::powf(self.kt_max / self.kt_min, 1. / (n - 1) as f64),
+        };
+        let temperatures: Vec<f64> = (0..replicas)
+            .map(|i| self.kt_min * ratio.powi(i as i32))
+            .collect();
+        debug!("Setting replica temperatures to: {:?}", temperatures);
+        let seed = match self.seed {
+            None => Pcg64Mcg::from_entropy().gen(),
+            Some(x) => x,
+        };
+
+        ParallelTempering {
+            temperatures,
+            exchanges: self.exchanges,
+            exchange_steps: self.exchange_steps,
+            optimiser: *optimiser,
+            seed,
+        }
+    }
+}
+
+/// The number of attempted and accepted swaps between each pair of neighbouring temperatures
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapStatistics {
+    /// The temperature of each replica, from coldest to hottest
+    pub temperatures: Vec<f64>,
+    /// The swaps attempted between the replicas at index `i` and `i + 1` of the temperatures
+    pub attempted: Vec<u64>,
+    /// The swaps accepted between the replicas at index `i` and `i + 1` of the temperatures
+    pub accepted: Vec<u64>,
+}
+
+impl SwapStatistics {
+    fn new(temperatures: &[f64]) -> Self {
+        let pairs = temperatures.len().saturating_sub(1);
+        Self {
+            temperatures: temperatures.to_vec(),
+            attempted: vec![0; pairs],
+            accepted: vec![0; pairs],
+        }
+    }
+
+    /// The fraction of attempted swaps which were accepted for each pair of temperatures
+    pub fn acceptance_rates(&self) -> Vec<f64> {
+        self.attempted
+            .iter()
+            .zip(&self.accepted)
+            .map(|(&attempted, &accepted)| match attempted {
+                0 => 0.,
+                n => accepted as f64 / n as f64,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for SwapStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rates: Vec<String> = self
+            .acceptance_rates()
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| {
+                format!(
+                    "{:.4} <-> {:.4}: {:.1}%",
+                    self.temperatures[i],
+                    self.temperatures[i + 1],
+                    100. * rate
+                )
+            })
+            .collect();
+        write!(f, "Swap acceptance: {}", rates.join(", "))
+    }
+}
+
+/// A configuration at one of the temperatures, along with the moves at that temperature
+///
+/// The bounds of the `parameters` are those of the initial state and the `step_ratio` is the
+/// size of the moves, both of which stay with the temperature when the configurations are
+/// swapped. Keeping the initial bounds allows a hot replica to expand a cell which was
+/// compressed at a colder temperature, while keeping the size of the moves allows it to adapt
+/// over the rounds of moves.
+///
+struct Replica<S> {
+    state: S,
+    score: f64,
+    parameters: Parameters,
+    step_ratio: f64,
+}
+
+/// Optimisation using replica exchange, also known as parallel tempering
+///
+/// Many replicas of the state are run in parallel using Monte-Carlo moves, each at a fixed
+/// temperature. After every `exchange_steps` the configurations of replicas at neighbouring
+/// temperatures are swapped using the Metropolis criterion. The hot replicas are able to
+/// cross the barriers between minima, passing the configurations they find down to the
+/// colder replicas, which refine them. Unlike the independent replications, the replicas
+/// share the configurations they find.
+///
+pub struct ParallelTempering {
+    temperatures: Vec<f64>,
+    exchanges: u64,
+    exchange_steps: u64,
+    optimiser: BuildOptimiser,
+    seed: u64,
+}
+
+impl ParallelTempering {
+    /// The temperature of each replica, from coldest to hottest
+    pub fn temperatures(&self) -> &[f64] {
+        &self.temperatures
+    }
+
+    /// Whether to swap configurations with scores `score_cold` and `score_hot`
+    ///
+    /// The replicas are at temperatures `kt_cold` and `kt_hot` respectively. Since the score is
+    /// maximised, a swap moving the better score to the colder replica is always accepted.
+    ///
+    fn accept_swap<R: Rng + ?Sized>(
+        score_cold: f64,
+        score_hot: f64,
+        kt_cold: f64,
+        kt_hot: f64,
+        rng: &mut R,
+    ) -> bool {
+        let exponent = (score_hot - score_cold) * (1. / kt_cold - 1. / kt_hot);
+        exponent >= 0. || rng.gen::<f64>() < f64::exp(exponent)
+    }
+
+    /// Exchange the configurations of two replicas, leaving the moves with each temperature
+    fn swap_configurations<S>(a: &mut Replica<S>, b: &mut Replica<S>) {
+        std::mem::swap(&mut a.state, &mut b.state);
+        std::mem::swap(&mut a.score, &mut b.score);
+    }
+
+    /// Run a round of `exchange_steps` Monte-Carlo moves of the `replica` at the temperature `kt`
+    fn run_replica<S: State>(&self, replica: Replica<S>, kt: f64, seed: u64) -> Replica<S> {
+        let Replica {
+            state,
+            mut parameters,
+            mut step_ratio,
+            ..
+        } = replica;
+        let state = self
+            .optimiser
+            .clone()
+            .steps(self.exchange_steps)
+            .kt_start(kt)
+            .kt_ratio(Some(0.))
+            .convergence(None)
+            .seed(seed)
+            .build()
+            .optimise_chain(state, &mut parameters, &mut step_ratio);
+        // The step ratio carries over every round, so without a limit a replica accepting most
+        // of its moves grows the steps without bound, proposing cells far from the current one.
+        let step_ratio = step_ratio.min(1.);
+        let score = state.score().expect("The replica has become invalid");
+        Replica {
+            state,
+            score,
+            parameters,
+            step_ratio,
+        }
+    }
+
+    /// Optimise the state, returning the best state found by any of the replicas
+    ///
+    /// Every replica starts from the given `state`. Along with the best state, this returns
+    /// the statistics of the swaps between each pair of neighbouring temperatures.
+    ///
+    pub fn optimise_state<S: State>(&self, state: S) -> (S, SwapStatistics) {
+        let mut rng = Pcg64Mcg::seed_from_u64(self.seed);
+        let mut statistics = SwapStatistics::new(&self.temperatures);
+
+        let score = state
+            .score()
+            .expect("Invalid configuration passed to function");
+        let mut replicas: Vec<Replica<S>> = self
+            .temperatures
+            .iter()
+            .map(|_| Replica {
+                state: state.clone(),
+                score,
+                parameters: state.parameters(),
+                step_ratio: 1.,
+            })
+            .collect();
+        let mut best = (state, score);
+
+        for exchange in 0..self.exchanges {
+            // Each replica requires a different seed for every round of moves
+            let seeds: Vec<u64> = self.temperatures.iter().map(|_| rng.gen()).collect();
+            replicas = replicas
+                .into_par_iter()
+                .zip(self.temperatures.par_iter())
+                .zip(seeds)
+                .map(|((replica, &kt), seed)| self.run_replica(replica, kt, seed))
+                .collect();
+
+            for replica in replicas.iter() {
+                if replica.score > best.1 {
+                    best = (replica.state.clone(), replica.score);
+                }
+            }
+
+            // Alternate between swapping the even and odd pairs of temperatures, so every
+            // replica takes part in at most one swap.
+            for cold in (0..replicas.len().saturating_sub(1))
+                .skip((exchange % 2) as usize)
+                .step_by(2)
+            {
+                let hot = cold + 1;
+                statistics.attempted[cold] += 1;
+                if Self::accept_swap(
+                    replicas[cold].score,
+                    replicas[hot].score,
+                    self.temperatures[cold],
+                    self.temperatures[hot],
+                    &mut rng,
+                ) {
+                    let (colder, hotter) = replicas.split_at_mut(hot);
+                    Self::swap_configurations(&mut colder[cold], &mut hotter[0]);
+                    statistics.accepted[cold] += 1;
+                }
+            }
+        }
+        debug!("Score: {:.4}, {}", best.1, statistics);
+
+        (best.0, statistics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{get_wallpaper_group, WallpaperGroups};
+    use crate::{LJShape2, PotentialState2};
+
+    fn trimer_state() -> PotentialState2<LJShape2> {
+        let group = get_wallpaper_group(WallpaperGroups::p2).unwrap();
+        PotentialState2::from_group(LJShape2::from_trimer(0.63, 120., 1.), &group).unwrap()
+    }
+
+    fn tempering() -> ParallelTempering {
+        BuildTempering::default()
+            .replicas(4)
+            .exchanges(6)
+            .exchange_steps(20)
+            .seed(0)
+            .build(&BuildOptimiser::default())
+    }
+
+    #[test]
+    fn temperature_ladder() {
+        let tempering = BuildTempering::default()
+            .replicas(3)
+            .kt_min(0.01)
+            .kt_max(1.)
+            .build(&BuildOptimiser::default());
+        let temperatures = tempering.temperatures();
+        assert_eq!(temperatures.len(), 3);
+        assert_abs_diff_eq!(temperatures[0], 0.01);
+        assert_abs_diff_eq!(temperatures[1], 0.1, epsilon = 1e-12);
+        assert_abs_diff_eq!(temperatures[2], 1., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn accept_better_swap() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        // Moving the better score to the colder replica is always accepted
+        assert!(ParallelTempering::accept_swap(-2., -1., 0.1, 1., &mut rng));
+        // Moving a much worse score to the colder replica is never accepted
+        assert!(!ParallelTempering::accept_swap(
+            -1., -100., 0.1, 1., &mut rng
+        ));
+    }
+
+    #[test]
+    fn score_improves() {
+        let state = trimer_state();
+        let init_score = state.score().unwrap();
+        let (final_state, _) = tempering().optimise_state(state);
+        assert!(final_state.score().unwrap() > init_score);
+    }
+
+    #[test]
+    fn swap_statistics() {
+        let (_, statistics) = tempering().optimise_state(trimer_state());
+        assert_eq!(statistics.attempted.len(), 3);
+        // The even and odd pairs are attempted on alternating exchanges
+        assert_eq!(statistics.attempted, vec![3, 3, 3]);
+        for (&accepted, &attempted) in statistics.accepted.iter().zip(&statistics.attempted) {
+            assert!(accepted <= attempted);
+        }
+        for rate in statistics.acceptance_rates() {
+            assert!((0. ..=1.).contains(&rate));
+        }
+    }
+
+    #[test]
+    fn hot_replica_expands_cell() {
+        let state = trimer_state();
+        let length = state.cell.a();
+        let mut compressed = state.clone();
+        let mut parameters = compressed.parameters();
+        parameters.set(0, 0.4 * length);
+        compressed.set_parameters(parameters.values());
+
+        let replica = |state: PotentialState2<LJShape2>| Replica {
+            score: state.score().unwrap(),
+            parameters: state.parameters(),
+            state,
+            step_ratio: 1.,
+        };
+        // The colder replica has compressed the cell, which is passed to the hot replica
+        let mut cold = replica(compressed);
+        let mut hot = replica(state);
+        ParallelTempering::swap_configurations(&mut cold, &mut hot);
+        assert_abs_diff_eq!(hot.state.cell.a(), 0.4 * length);
+
+        let tempering = tempering();
+        let kt = tempering.temperatures()[3];
+        let hot = tempering.run_replica(hot, kt, 0);
+        assert!(hot.state.cell.a() > 0.4 * length);
+        // The bounds of the moves remain those of the initial state
+        assert_abs_diff_eq!(hot.parameters.bounds(0).1, length);
+    }
+
+    #[test]
+    fn reproducible() {
+        let (state1, statistics1) = tempering().optimise_state(trimer_state());
+        let (state2, statistics2) = tempering().optimise_state(trimer_state());
+        assert_eq!(statistics1, statistics2);
+        assert_eq!(state1.score(), state2.score());
+    }
+}
@@ -4,7 +4,7 @@
 // Distributed under terms of the MIT license.
 //
 
-use nalgebra::Matrix3;
+use nalgebra::{Matrix3, Vector2};
 use svg::node::element;
 use svg::Document;
 
@@ -45,6 +45,81 @@ impl ToSVG for LJShape2 {
     }
 }
 
+impl ToSVG for Tabulated2 {
+    type Value = element::Circle;
+
+    fn as_svg(&self) -> Self::Value {
+        element::Circle::new()
+            .set("r", self.radius)
+            .set("cx", self.position.x)
+            .set("cy", self.position.y)
+    }
+}
+
+impl ToSVG for TabulatedShape2 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut smol = element::Group::new();
+        for item in self {
+            smol = smol.add(item.as_svg())
+        }
+        smol
+    }
+}
+
+impl ToSVG for Patchy2 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        // Each patch is drawn as a sector extending to the range of the patch, behind the core
+        let mut group = element::Group::new();
+        for patch in self.patches.iter() {
+            let radius = patch.range * self.sigma / 2.;
+            let angle = patch.angle();
+            let start = Vector2::new(
+                (angle - patch.half_width).cos(),
+                (angle - patch.half_width).sin(),
+            ) * radius
+                + self.position.coords;
+            let end = Vector2::new(
+                (angle + patch.half_width).cos(),
+                (angle + patch.half_width).sin(),
+            ) * radius
+                + self.position.coords;
+            let large_arc = if patch.half_width > std::f64::consts::FRAC_PI_2 {
+                1
+            } else {
+                0
+            };
+            let data = element::path::Data::new()
+                .move_to((self.position.x, self.position.y))
+                .line_to((start.x, start.y))
+                .elliptical_arc_to((radius, radius, 0, large_arc, 1, end.x, end.y))
+                .close();
+            group = group.add(element::Path::new().set("d", data).set("fill-opacity", 0.5));
+        }
+        group.add(
+            element::Circle::new()
+                .set("r", self.sigma / 2.)
+                .set("cx", self.position.x)
+                .set("cy", self.position.y),
+        )
+    }
+}
+
+impl ToSVG for PatchyShape2 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut smol = element::Group::new();
+        for item in self {
+            smol = smol.add(item.as_svg())
+        }
+        smol
+    }
+}
+
 impl ToSVG for LineShape {
     type Value = element::Group;
 
@@ -59,6 +134,51 @@ impl ToSVG for LineShape {
     }
 }
 
+impl ToSVG for RoundedShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        // The rounded shape is the union of the core polygon, a rectangle extending out from each
+        // line and a circle at the ends of each line.
+        let mut group = element::Group::new();
+        if self.items.len() > 2 {
+            let start = self.items[0].start;
+            let mut data = element::path::Data::new().move_to((start.x, start.y));
+            for item in self {
+                data = data.line_to((item.end.x, item.end.y));
+            }
+            group = group.add(element::Path::new().set("d", data.close()));
+        }
+        for item in self {
+            if item.length() > 0. {
+                let normal = Vector2::new(-item.dy(), item.dx()) * self.radius / item.length();
+                let corners = [
+                    item.start + normal,
+                    item.end + normal,
+                    item.end - normal,
+                    item.start - normal,
+                ];
+                let data = element::path::Data::new()
+                    .move_to((corners[0].x, corners[0].y))
+                    .line_to((corners[1].x, corners[1].y))
+                    .line_to((corners[2].x, corners[2].y))
+                    .line_to((corners[3].x, corners[3].y))
+                    .close();
+                group = group.add(element::Path::new().set("d", data));
+            }
+            for point in &[item.start, item.end] {
+                group = group.add(
+                    element::Circle::new()
+                        .set("r", self.radius)
+                        .set("cx", point.x)
+                        .set("cy", point.y),
+                );
+            }
+        }
+        group
+    }
+}
+
 impl ToSVG for Cell2 {
     type Value = element::Group;
 
@@ -104,11 +224,35 @@ impl ToSVG for MolecularShape2 {
     }
 }
 
+impl ToSVG for Component2 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        match self {
+            Component2::Disk(a) => element::Group::new().add(a.as_svg()),
+            Component2::Segment(l) => element::Group::new().add(l.as_svg()),
+            Component2::Polygon(p) => p.as_svg(),
+        }
+    }
+}
+
+impl ToSVG for CompositeShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut smol = element::Group::new();
+        for item in self {
+            smol = smol.add(item.as_svg())
+        }
+        smol
+    }
+}
+
 impl ToSVG for Transform2 {
     type Value = element::Use;
 
     fn as_svg(&self) -> Self::Value {
-        let matrix: Matrix3<f64> = self.clone().into();
+        let matrix: Matrix3<f64> = (*self).into();
         element::Use::new().set(
             "transform",
             format!(
@@ -131,7 +275,11 @@ where
     type Value = Document;
 
     fn as_svg(&self) -> Self::Value {
-        let padding = self.shape.enclosing_radius();
+        let padding = self
+            .shapes
+            .iter()
+            .map(Shape::enclosing_radius)
+            .fold(f64::MIN, f64::max);
         let viewbox =
             self.cell
                 .get_corners()
@@ -145,20 +293,31 @@ where
                         f64::max(2. * (p.y + padding), acc.3),
                     )
                 });
-        let mut doc = Document::new().set("viewBox", viewbox).add(
-            element::Definitions::new()
-                .add(self.cell.as_svg().set("id", "cell"))
-                .add(self.shape.as_svg().set("id", "mol")),
-        );
+        let mut definitions = element::Definitions::new().add(self.cell.as_svg().set("id", "cell"));
+        for (index, shape) in self.shapes.iter().enumerate() {
+            definitions = definitions.add(shape.as_svg().set("id", format!("mol{}", index)));
+        }
+        let mut doc = Document::new().set("viewBox", viewbox).add(definitions);
         for transform in self.cell.periodic_images(Transform2::identity(), 1, true) {
             doc = doc.add(transform.as_svg().set("href", "#cell"));
         }
 
-        for position in self.relative_positions() {
+        for (index, position) in self.site_positions() {
+            let href = format!("#mol{}", index);
             let transform = self.cell.to_cartesian_isometry(position);
-            doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", "blue"));
+            doc = doc.add(
+                transform
+                    .as_svg()
+                    .set("href", href.clone())
+                    .set("fill", "blue"),
+            );
             for periodic in self.cell.periodic_images(position, 1, false) {
-                doc = doc.add(periodic.as_svg().set("href", "#mol").set("fill", "green"));
+                doc = doc.add(
+                    periodic
+                        .as_svg()
+                        .set("href", href.clone())
+                        .set("fill", "green"),
+                );
             }
         }
         doc
@@ -172,7 +331,11 @@ where
     type Value = Document;
 
     fn as_svg(&self) -> Self::Value {
-        let padding = self.shape.enclosing_radius();
+        let padding = self
+            .shapes
+            .iter()
+            .map(Shape::enclosing_radius)
+            .fold(f64::MIN, f64::max);
         let viewbox =
             self.cell
                 .get_corners()
@@ -186,19 +349,30 @@ where
                         f64::max(2. * (p.y + padding), acc.3),
                     )
                 });
-        let mut doc = Document::new().set("viewBox", viewbox).add(
-            element::Definitions::new()
-                .add(self.cell.as_svg().set("id", "cell"))
-                .add(self.shape.as_svg().set("id", "mol")),
-        );
+        let mut definitions = element::Definitions::new().add(self.cell.as_svg().set("id", "cell"));
+        for (index, shape) in self.shapes.iter().enumerate() {
+            definitions = definitions.add(shape.as_svg().set("id", format!("mol{}", index)));
+        }
+        let mut doc = Document::new().set("viewBox", viewbox).add(definitions);
         for transform in self.cell.periodic_images(Transform2::identity(), 1, true) {
             doc = doc.add(transform.as_svg().set("href", "#cell"));
         }
-        for position in self.relative_positions() {
+        for (index, position) in self.site_positions() {
+            let href = format!("#mol{}", index);
             let matrix = self.cell.to_cartesian_isometry(position);
-            doc = doc.add(matrix.as_svg().set("href", "#mol").set("fill", "blue"));
+            doc = doc.add(
+                matrix
+                    .as_svg()
+                    .set("href", href.clone())
+                    .set("fill", "blue"),
+            );
             for transform in self.cell.periodic_images(position, 1, false) {
-                doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", "green"));
+                doc = doc.add(
+                    transform
+                        .as_svg()
+                        .set("href", href.clone())
+                        .set("fill", "green"),
+                );
             }
         }
         doc
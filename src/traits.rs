@@ -4,30 +4,24 @@
 // Distributed under terms of the MIT license.
 //
 
+use std::fs;
+use std::path::Path;
 use std::{fmt, ops, slice};
 
-use anyhow::Error;
+use anyhow::{Context, Error};
 use nalgebra::allocator::Allocator;
-use nalgebra::{DefaultAllocator, DimName, VectorN};
-use rand::Rng;
+use nalgebra::{DefaultAllocator, DimName, Point2, VectorN};
 use serde::Serialize;
 use svg::node::element::Group;
 use svg::Document;
 
-use crate::{StandardBasis, Transform2};
+use crate::shape::mirror_name;
+use crate::{Parameters, Transform2};
 
 pub trait Transformer {
     fn as_simple(&self) -> String;
 }
 
-pub trait Basis {
-    fn set_value(&mut self, new_value: f64);
-    fn get_value(&self) -> f64;
-    fn reset_value(&self);
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R, step_size: f64) -> f64;
-    fn set_sampled<R: Rng + ?Sized>(&mut self, rng: &mut R, step_size: f64);
-}
-
 pub trait Periodic<Rhs = Self> {
     type Output;
 
@@ -51,8 +45,71 @@ pub trait Intersect {
     fn area(&self) -> f64;
 }
 
+/// The signed distance between the boundaries of two objects
+///
+/// This is a more detailed version of `Intersect::intersects`, giving the minimum distance
+/// between two objects when they are separated. When the objects overlap the value is
+/// negative, with the magnitude being the penetration depth, the shortest distance one of the
+/// objects has to be moved to remove the overlap.
+///
+pub trait Separation {
+    fn separation(&self, other: &Self) -> f64;
+}
+
 pub trait Potential {
     fn energy(&self, other: &Self) -> f64;
+
+    /// The position and value of each of the partial charges
+    ///
+    /// The charges interact through the long ranged Coulomb potential, which is calculated
+    /// separately to the short ranged interactions of `energy`.
+    ///
+    fn charges(&self) -> Vec<(Point2<f64>, f64)> {
+        vec![]
+    }
+
+    /// The energy beyond the cutoff of the interactions with `other`, per unit density
+    ///
+    /// This is the integral of the truncated part of the potential over the plane, which is
+    /// used to correct for the interactions missing beyond the cutoff.
+    ///
+    fn tail(&self, _other: &Self) -> f64 {
+        0.
+    }
+
+    /// The distance between the centres of two shapes beyond which they don't interact
+    ///
+    /// This is None when the interactions extend indefinitely, requiring every shape within
+    /// the surrounding periodic images to be considered.
+    ///
+    fn interaction_range(&self, _other: &Self) -> Option<f64> {
+        None
+    }
+
+    /// Ensure the interactions with `other` don't depend on the order of the two shapes
+    ///
+    /// A state checks each pair of its shapes when it is created, including each shape with
+    /// itself, since a shape also interacts with its own periodic images.
+    ///
+    fn check_compatible(&self, _other: &Self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The derivatives of a `Potential` with respect to the positions of the interacting sites
+///
+/// All the interactions are between pairs of sites, depending only on the distance between the
+/// sites, which is sufficient to find the derivatives with respect to any of the values which
+/// describe the positions of the sites.
+///
+pub trait PotentialGradient: Potential {
+    /// Each interacting pair of sites with the derivative of the energy with respect to distance
+    ///
+    /// The values are the position of the site within `self`, the position of the site within
+    /// `other`, and the derivative of the energy of the pair with respect to the distance
+    /// between them.
+    ///
+    fn pair_derivatives(&self, other: &Self) -> Vec<(Point2<f64>, Point2<f64>, f64)>;
 }
 
 pub trait Shape:
@@ -75,6 +132,19 @@ pub trait Shape:
     }
     fn iter(&self) -> slice::Iter<'_, Self::Component>;
     fn transform(&self, transform: &Transform2) -> Self;
+    /// The name of the shape, which can be modified
+    fn name_mut(&mut self) -> &mut String;
+    /// Create the mirror image of the shape, reflecting across a line through the origin
+    ///
+    /// The line is at an angle `axis` (in radians) from the x axis. The name of the mirror image
+    /// is tagged to distinguish it from the original shape.
+    ///
+    fn mirror(&self, axis: f64) -> Self {
+        let mut mirrored = self.transform(&Transform2::reflection(axis));
+        let name = mirror_name(mirrored.name_mut());
+        *mirrored.name_mut() = name;
+        mirrored
+    }
 }
 
 pub trait FromSymmetry: Sized {
@@ -94,12 +164,63 @@ pub trait State:
     + ToSVG<Value = Document>
 {
     fn score(&self) -> Option<f64>;
-    fn generate_basis(&self) -> Vec<StandardBasis>;
+    /// The values which can be modified to change the state, along with their bounds
+    fn parameters(&self) -> Parameters;
+    /// Update the state from `values`, which are in the same order as `parameters`
+    fn set_parameters(&mut self, values: &[f64]);
     fn total_shapes(&self) -> usize;
     fn as_positions(&self) -> Result<String, Error>;
+
+    /// The contribution to the score from the parts of the state depending on a single parameter
+    ///
+    /// The change in the score from modifying the parameter at `index` of `parameters` is the
+    /// change in this contribution, which only considers the interactions involving the values
+    /// being modified. The outer Option is None when the contribution can't be separated from
+    /// the rest of the score, like for changes to the unit cell, requiring the complete score.
+    /// The inner Option is None when the parameter makes the state invalid.
+    ///
+    fn parameter_score(&self, _index: usize) -> Option<Option<f64>> {
+        None
+    }
+
+    /// The contribution of `parameter_score` before modifying the parameter at `index`
+    ///
+    /// A state is always valid before it is modified, which allows states with a contribution
+    /// which is known for any valid state to skip finding it.
+    ///
+    fn parameter_score_before(&self, index: usize) -> Option<Option<f64>> {
+        self.parameter_score(index)
+    }
+}
+
+/// A state where the score is a smooth function of the values of the parameters
+pub trait ScoreGradient: State {
+    /// The derivative of the score with respect to each value of `State::parameters`
+    fn score_gradient(&self) -> Result<Vec<f64>, Error>;
 }
 
 pub trait ToSVG {
     type Value: svg::Node;
     fn as_svg(&self) -> Self::Value;
 }
+
+/// Create a value from the contents of an SVG document
+///
+/// This is the reverse of the `ToSVG` trait, where curves within the document are approximated
+/// by straight lines which deviate from the curve by at most `tolerance`.
+///
+pub trait FromSVG: Sized {
+    fn from_svg(name: &str, contents: &str, tolerance: f64) -> Result<Self, Error>;
+
+    /// Read an SVG file, naming the value after the stem of the filename
+    fn from_svg_file(path: &Path, tolerance: f64) -> Result<Self, Error> {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        Self::from_svg(name, &contents, tolerance)
+            .with_context(|| format!("Unable to create shape from {}", path.display()))
+    }
+}
@@ -9,7 +9,7 @@ use std::ops::Mul;
 
 #[cfg(test)]
 use approx::AbsDiffEq;
-use nalgebra::{Matrix3, Point2, Translation2};
+use nalgebra::{Matrix3, Point2, Translation2, Vector2};
 use serde::{Deserialize, Serialize};
 
 /// Perform coordinate tranforms on a point in space
@@ -39,9 +39,9 @@ impl From<Matrix3<f64>> for Transform2 {
     }
 }
 
-impl Into<Matrix3<f64>> for Transform2 {
-    fn into(self) -> Matrix3<f64> {
-        *self.0.matrix()
+impl From<Transform2> for Matrix3<f64> {
+    fn from(transform: Transform2) -> Self {
+        *transform.0.matrix()
     }
 }
 
@@ -66,6 +66,15 @@ binop_impl_all!(
     };
 );
 
+// A vector is only affected by the linear part of the transform, ignoring any translation
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Vector2<f64>, Output = Vector2<f64>;
+    [ref ref] => {
+        self.0 * rhs
+    };
+);
+
 binop_impl_all!(
     Mul, mul;
     self: Transform2, rhs: Transform2, Output = Transform2;
@@ -87,6 +96,47 @@ impl Transform2 {
         Self(nalgebra::Transform2::identity())
     }
 
+    /// A reflection across a line through the origin
+    ///
+    /// The line is at an angle `angle` (in radians) anticlockwise from the x axis, so an angle of
+    /// 0 reflects across the x axis, and an angle of $\pi/2$ reflects across the y axis.
+    ///
+    /// ```
+    /// use packing::Transform2;
+    /// let t = Transform2::reflection(std::f64::consts::PI / 2.);
+    /// # assert!(t.is_reflection());
+    /// ```
+    ///
+    pub fn reflection(angle: f64) -> Transform2 {
+        let (sin, cos) = (2. * angle).sin_cos();
+        Transform2::from(Matrix3::new(cos, sin, 0., sin, -cos, 0., 0., 0., 1.))
+    }
+
+    /// Whether the transform changes the handedness of an object
+    ///
+    /// This is the case for reflections and glides, where the determinant of the linear part of
+    /// the transform is negative.
+    ///
+    pub fn is_reflection(&self) -> bool {
+        self.0
+            .matrix()
+            .fixed_slice::<nalgebra::U2, nalgebra::U2>(0, 0)
+            .determinant()
+            < 0.
+    }
+
+    /// The transform which undoes this transform, when one exists
+    ///
+    /// ```
+    /// use packing::Transform2;
+    /// let t = Transform2::from_operations("-y, x+1/2").unwrap();
+    /// # assert!(t.inverse().is_some());
+    /// ```
+    ///
+    pub fn inverse(&self) -> Option<Transform2> {
+        self.0.try_inverse().map(Transform2)
+    }
+
     pub fn position(&self) -> Point2<f64> {
         self.0 * Point2::origin()
     }
@@ -160,8 +210,8 @@ impl Transform2 {
                         let val = c.to_string().parse::<u64>()? as f64;
                         // Is there an operator defined, i.e. is this the first digit
                         constant = match operator {
-                            Some(op) if op == '/' => sign * constant / val,
-                            Some(op) if op == '*' => sign * constant / val,
+                            Some('/') => sign * constant / val,
+                            Some('*') => sign * constant / val,
                             Some(_) => 0.,
                             None => sign * val,
                         };
@@ -176,6 +226,8 @@ impl Transform2 {
             }
             transform[(index, 2)] = constant;
         }
+        // The homogeneous coordinate is unchanged by the transform
+        transform[(2, 2)] = 1.;
         Ok(Transform2::from(transform))
     }
 }
@@ -214,6 +266,21 @@ mod test {
     mod mul_transform_vec {
         use super::*;
 
+        /// Translation doesn't change the direction of a vector
+        #[quickcheck]
+        fn vector_ignores_translation(x: f64, y: f64) -> bool {
+            let t = Transform2::new(0., (x, y));
+            let vector = Vector2::new(1., 2.);
+            t * vector == vector
+        }
+
+        /// Rotation of a vector by a quarter turn
+        #[test]
+        fn vector_rotation() {
+            let t = Transform2::new(std::f64::consts::PI / 2., (1., 1.));
+            assert_abs_diff_eq!(t * Vector2::new(1., 0.), Vector2::new(0., 1.));
+        }
+
         /// Transformation by identity matrix gives same result
         #[quickcheck]
         fn identity_transform(x: f64, y: f64) -> bool {
@@ -252,6 +319,15 @@ mod test {
         }
     }
 
+    #[test]
+    fn inverse() {
+        let t = Transform2::from_operations("x+1/2, -y").unwrap();
+        let inverse = t.inverse().unwrap();
+        assert_abs_diff_eq!(t * inverse, Transform2::identity());
+        let singular = Transform2::from(Matrix3::new(1., 0., 0., 0., 0., 0., 0., 0., 1.));
+        assert!(singular.inverse().is_none());
+    }
+
     /// Testing the multiplication of a Transform2 with a Transform2
     mod mul_transform_transform {
         use super::*;
@@ -413,6 +489,17 @@ mod test {
         assert_abs_diff_eq!(st * point, Point2::new(-0.2, 0.));
     }
 
+    #[test]
+    fn parse_operation_product() {
+        // Applying a glide twice is a translation of the lattice, which requires the translation
+        // of the first operation to be kept in the product.
+        let glide = Transform2::from_operations("(-x+1/2, y+1/2)").unwrap();
+        let expected = Transform2::from_operations("(x, y+1)").unwrap();
+        assert_abs_diff_eq!(glide * glide, expected);
+        let point = Point2::new(0.1, 0.2);
+        assert_abs_diff_eq!(glide * (glide * point), Point2::new(0.1, 1.2));
+    }
+
     #[test]
     #[should_panic]
     fn parse_operation_z() {
@@ -7,9 +7,12 @@
 use anyhow::{anyhow, Error};
 
 use packing::traits::*;
-use packing::wallpaper::Wallpaper;
 use packing::wallpaper::WyckoffSite;
-use packing::{BuildOptimiser, CrystalFamily, LJShape2, PotentialState, Transform2};
+use packing::wallpaper::{get_wallpaper_group, Wallpaper, WallpaperGroups};
+use packing::{
+    BuildMinimiser, BuildOptimiser, CrystalFamily, LJShape2, PairPotential, PotentialState,
+    Transform2,
+};
 
 #[test]
 fn test_score_improves() -> Result<(), Error> {
@@ -50,3 +53,99 @@ fn test_score_improves() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_pressure_compresses() -> Result<(), Error> {
+    let group = get_wallpaper_group(WallpaperGroups::p2)?;
+    let state = PotentialState::from_group(LJShape2::from_trimer(0.63, 120., 1.), &group)?;
+
+    let opt = BuildOptimiser::default().seed(0).build();
+
+    let free = opt.optimise_state(state.clone());
+    let compressed = opt.optimise_state(state.with_pressure(10.)?);
+
+    println!(
+        "Free Area: {}, Compressed Area: {}",
+        free.cell.area(),
+        compressed.cell.area()
+    );
+    assert!(compressed.cell.area() < free.cell.area());
+
+    Ok(())
+}
+
+#[test]
+fn test_minimiser_polishes() -> Result<(), Error> {
+    let group = get_wallpaper_group(WallpaperGroups::p2)?;
+    let state = PotentialState::from_group(LJShape2::from_trimer(0.63, 120., 1.), &group)?;
+
+    let optimised = BuildOptimiser::default()
+        .seed(0)
+        .build()
+        .optimise_state(state);
+    let init_score = optimised
+        .score()
+        .ok_or_else(|| anyhow!("Optimised score is invalid"))?;
+
+    let polished = BuildMinimiser::default()
+        .build()
+        .minimise_state(optimised)?;
+    let final_score = polished
+        .score()
+        .ok_or_else(|| anyhow!("Polished score is invalid"))?;
+
+    println!("Init Score: {}, Final Score: {}", init_score, final_score);
+    assert!(init_score <= final_score);
+
+    Ok(())
+}
+
+#[test]
+fn test_read_single_shape() -> Result<(), Error> {
+    let group = get_wallpaper_group(WallpaperGroups::p2)?;
+    let state = PotentialState::from_group(LJShape2::from_trimer(0.63, 120., 1.), &group)?;
+
+    // States with a single shape used to be written with that shape in the field `shape`
+    let mut value = serde_json::to_value(&state)?;
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("State isn't serialised as an object"))?;
+    let shapes = fields
+        .remove("shapes")
+        .ok_or_else(|| anyhow!("No shapes"))?;
+    fields.insert(String::from("shape"), shapes[0].clone());
+
+    let read: PotentialState<LJShape2> = serde_json::from_value(value)?;
+    assert_eq!(read.shapes.len(), 1);
+    assert_eq!(read.score(), state.score());
+
+    Ok(())
+}
+
+#[test]
+fn test_square_well_escapes_overlap() -> Result<(), Error> {
+    let group = get_wallpaper_group(WallpaperGroups::p2)?;
+    let disk = LJShape2::circle().with_potential(PairPotential::SquareWell { width: 1.5 });
+    let mut state = PotentialState::from_group(disk, &group)?;
+    // Place the disk close to the centre of inversion, overlapping the core with its image
+    let mut parameters = state.parameters();
+    let cell_parameters = state.cell.num_parameters();
+    parameters.set(cell_parameters, 0.176);
+    parameters.set(cell_parameters + 1, 0.176);
+    state.set_parameters(parameters.values());
+    assert_eq!(state.score(), Some(f64::NEG_INFINITY));
+
+    let optimised = BuildOptimiser::default()
+        .seed(0)
+        .build()
+        .optimise_state(state);
+    let final_score = optimised
+        .score()
+        .ok_or_else(|| anyhow!("Optimised score is invalid"))?;
+
+    println!("Final Score: {}", final_score);
+    // Once the overlap is removed, the disks are bound within the well
+    assert!(final_score > 0.);
+
+    Ok(())
+}